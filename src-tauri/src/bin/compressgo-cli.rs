@@ -0,0 +1,626 @@
+// CompressGo 命令行入口：不依赖桌面会话，直接调用压缩核心逻辑
+// 用法示例：
+//   compressgo-cli video input.mov output.mp4 --codec H.265 --crf 28
+//   compressgo-cli image input.png output.webp --crf 75
+//   compressgo-cli video input.mkv output.mp4 --settings settings.json
+use std::process::ExitCode;
+use std::sync::Arc;
+use serde_json::{json, Map, Value};
+use tauri_app_lib::video::{
+    init_binary_resolver, plan_output_paths, run_job, set_binary_override, BinaryKind, CompressionEvent,
+    CompressionJob, CompressionSettings, EventSink, JobKind, JobOptions, OutputNaming,
+};
+
+const USAGE: &str = "Usage: compressgo-cli <video|image> <input> <output> [options]
+
+Options:
+  --settings <file>        JSON file with CompressionSettings fields (flags override it)
+  --format <ext>           Output format (default: output file extension, or the input's with --output-template)
+  --codec <name>           Video codec, e.g. H.264, H.265, AV1, VP9 (default: H.264)
+  --resolution <value>     original | WxH (default: original)
+  --width <px> --height <px>
+                           Custom resolution (implies --resolution custom)
+  --scale <mode:value>     Aspect-preserving scale: fit:1280x720, fill:1080x1080, long_edge:1920,
+                           short_edge:720 or percent:50 (overrides --resolution)
+  --upscale                Allow --scale to enlarge smaller inputs
+  --preset <name>          Encoder speed preset, e.g. slow (x264/x265), 6 (SVT-AV1), p5 (NVENC)
+  --tune <name>            Encoder tune, e.g. film, grain, animation
+  --profile <name>         Encoder profile, e.g. high, main10
+  --level <n>              Encoder level, e.g. 4.1
+  --gop <frames>           Keyframe interval in frames
+  --bframes <n>            Maximum consecutive B-frames
+  --x264-params <p>        Raw libx264 parameters (key=value:key=value)
+  --x265-params <p>        Raw libx265 parameters
+  --svtav1-params <p>      Raw SVT-AV1 parameters
+  --if-larger <policy>     Output not smaller than the input: keep | discard | copy_original (default: keep)
+  --remux <mode>           off | auto (copy the video stream when only the container or time range changes) | copy
+  --hdr <mode>             HDR sources: auto | preserve | tonemap (default: auto)
+  --tonemap <alg>          Tone map HDR to SDR with hable, mobius, reinhard or clip (implies --hdr tonemap)
+  --fps <n>                Fixed output frame rate
+  --max-fps <n>            Lower the frame rate only when the source is above n
+  --interpolate            Convert the frame rate with motion interpolation instead of drop/duplicate
+  --quality-type <type>    crf | bitrate | qv | target_size | auto_crf (default: crf)
+  --crf <n>                CRF value (video) or quality 0-100 (image)
+  --qv <n>                 -q:v value for hardware encoders
+  --bitrate <value>        Target bitrate, e.g. 2M
+  --two-pass               Two-pass encode for --quality-type bitrate
+  --resumable              Encode in 60 s segments so an interrupted run continues where it stopped
+  --metrics                Compute PSNR/SSIM (and VMAF when available) after encoding
+  --quality-target <m:s>   Search the highest CRF reaching a score, e.g. vmaf:93 or ssim:0.98;
+                           implies --quality-type auto_crf
+  --target-size <size>     Target output size, e.g. 25M (K/M/G = 1000^n bytes);
+                           implies --quality-type target_size
+  --crop <w:h:x:y|auto>    Crop rectangle, or auto to detect black bars
+  --rotate <deg>           Rotate clockwise by 90, 180 or 270
+  --flip <dir>             horizontal | vertical | both
+  --deinterlace <filter>   yadif | bwdif
+  --denoise <m[:s]>        hqdn3d or nlmeans with optional strength, e.g. hqdn3d:6
+  --sharpen <amount>       unsharp amount, -1.5..1.5
+  --audio-codec <name>     copy | none | AAC, Opus, MP3, FLAC... (default: copy, Opus for WebM)
+  --audio-bitrate <value>  Audio bitrate, e.g. 128k
+  --audio-rate <hz>        Audio sample rate, e.g. 48000
+  --audio-channels <n>     Output channels; fewer than the source downmixes
+  --all-streams            Keep every audio and subtitle stream (default: FFmpeg's selection)
+  --subtitles <mode>       keep | burn | extract | none (default: keep, converted per container)
+  --subtitle-stream <n>    Subtitle stream index to burn in
+  --subtitle-file <path>   External .srt/.ass file to burn in (implies --subtitles burn)
+  --loudnorm <lufs>        EBU R128 loudness normalization to the given target, e.g. -16
+  --start <sec>            Time range start
+  --end <sec>              Time range end
+  --hardware <cpu|gpu>     Hardware acceleration (default: cpu)
+  --bit-depth <8|10|12>    Output bit depth
+  --keep-metadata          Copy container tags (creation time, GPS, camera) and chapters from the input
+  --strip-metadata         Remove container tags and chapters
+  --keep-file-times        Set the output's modified/accessed times to the input's
+  --output-template <t>    Name the output from a template and treat <output> as a directory;
+                           placeholders: {stem} {codec} {crf} {resolution} {date} {index}
+  --on-conflict <policy>   Existing output: overwrite | skip | auto_number (default: overwrite)
+  --dry-run                Print the final output path without compressing
+  --ffmpeg <path>          FFmpeg binary to use
+  --ffprobe <path>         FFprobe binary to use
+  --task-id <id>           Task id used in events (default: cli)
+  --json                   Print only the final result as JSON on stdout (logs and progress go to stderr)
+  --quiet                  Do not print progress
+  -h, --help               Show this help";
+
+struct CliArgs {
+    kind: String,
+    input: String,
+    output: String,
+    settings_file: Option<String>,
+    overrides: Map<String, Value>,
+    ffmpeg: Option<String>,
+    ffprobe: Option<String>,
+    task_id: String,
+    output_template: Option<String>,
+    on_conflict: Option<String>,
+    dry_run: bool,
+    json: bool,
+    quiet: bool,
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<Value, String> {
+    value
+        .parse::<f64>()
+        .map(|n| if n.fract() == 0.0 && n >= 0.0 { json!(n as u64) } else { json!(n) })
+        .map_err(|_| format!("Invalid number for {}: {}", flag, value))
+}
+
+// 解析大小参数：纯数字为字节，支持 K/M/G 后缀（按 1000 进位）
+fn parse_size(flag: &str, value: &str) -> Result<Value, String> {
+    let upper = value.trim().to_uppercase();
+    let upper = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, multiplier) = match upper.chars().last() {
+        Some('K') => (&upper[..upper.len() - 1], 1e3),
+        Some('M') => (&upper[..upper.len() - 1], 1e6),
+        Some('G') => (&upper[..upper.len() - 1], 1e9),
+        _ => (upper, 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| json!((n * multiplier).round() as u64))
+        .ok_or_else(|| format!("Invalid size for {}: {}", flag, value))
+}
+
+fn parse_args(args: Vec<String>) -> Result<CliArgs, String> {
+    let mut positional: Vec<String> = Vec::new();
+    let mut overrides = Map::new();
+    let mut settings_file = None;
+    let mut ffmpeg = None;
+    let mut ffprobe = None;
+    let mut task_id = "cli".to_string();
+    let mut output_template = None;
+    let mut on_conflict = None;
+    let mut dry_run = false;
+    let mut json_output = false;
+    let mut quiet = false;
+    let mut time_range = Map::new();
+    let mut custom_resolution = Map::new();
+    let mut audio = Map::new();
+    let mut subtitles = Map::new();
+    let mut filters = Map::new();
+    let mut scale = Map::new();
+    let mut frame_rate = Map::new();
+    let mut hdr = Map::new();
+    let mut encoder = Map::new();
+    let mut metadata = Map::new();
+
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "--json" => { json_output = true; continue; }
+            "--quiet" => { quiet = true; continue; }
+            "--dry-run" => { dry_run = true; continue; }
+            "--keep-metadata" | "--strip-metadata" => {
+                let keep = arg == "--keep-metadata";
+                metadata.insert("tags".into(), json!(keep));
+                metadata.insert("chapters".into(), json!(keep));
+                continue;
+            }
+            "--keep-file-times" => { metadata.insert("file_times".into(), json!(true)); continue; }
+            "--metrics" => { overrides.insert("measure_quality".into(), json!(true)); continue; }
+            "--upscale" => { scale.insert("allow_upscale".into(), json!(true)); continue; }
+            "--interpolate" => { frame_rate.insert("method".into(), json!("interpolate")); continue; }
+            "--all-streams" => { overrides.insert("streams".into(), json!({ "mode": "all" })); continue; }
+            "--two-pass" => { overrides.insert("two_pass".into(), json!(true)); continue; }
+            "--resumable" => { overrides.insert("resumable".into(), json!(true)); continue; }
+            _ => {}
+        }
+        let value = iter.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--settings" => settings_file = Some(value),
+            "--ffmpeg" => ffmpeg = Some(value),
+            "--ffprobe" => ffprobe = Some(value),
+            "--task-id" => task_id = value,
+            "--output-template" => output_template = Some(value),
+            "--on-conflict" => on_conflict = Some(value),
+            "--format" => { overrides.insert("format".into(), json!(value.trim_start_matches('.'))); }
+            "--codec" => { overrides.insert("codec".into(), json!(value)); }
+            "--resolution" => { overrides.insert("resolution".into(), json!(value)); }
+            "--quality-type" => { overrides.insert("quality_type".into(), json!(value)); }
+            "--bitrate" => { overrides.insert("bitrate".into(), json!(value)); }
+            "--hardware" => { overrides.insert("hardwareAcceleration".into(), json!(value)); }
+            "--crf" => { overrides.insert("crf_value".into(), parse_number(&arg, &value)?); }
+            "--qv" => { overrides.insert("qv_value".into(), parse_number(&arg, &value)?); }
+            "--quality-target" => {
+                let (metric, score) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected metric:score)", arg, value))?;
+                overrides.insert(
+                    "quality_target".into(),
+                    json!({ "metric": metric, "score": parse_number(&arg, score)? }),
+                );
+                overrides.insert("quality_type".into(), json!("auto_crf"));
+            }
+            "--target-size" => {
+                overrides.insert("target_size".into(), parse_size(&arg, &value)?);
+                overrides.insert("quality_type".into(), json!("target_size"));
+            }
+            "--bit-depth" => { overrides.insert("bitDepth".into(), parse_number(&arg, &value)?); }
+            "--audio-codec" => { audio.insert("codec".into(), json!(value)); }
+            "--audio-bitrate" => { audio.insert("bitrate".into(), json!(value)); }
+            "--audio-rate" => { audio.insert("sample_rate".into(), parse_number(&arg, &value)?); }
+            "--audio-channels" => { audio.insert("channels".into(), parse_number(&arg, &value)?); }
+            "--scale" => {
+                let (mode, amount) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected mode:value)", arg, value))?;
+                scale.insert("mode".into(), json!(mode));
+                match mode {
+                    "fit" | "fill" => {
+                        let (w, h) = amount.split_once('x').unwrap_or((amount, ""));
+                        if !w.is_empty() {
+                            scale.insert("width".into(), parse_number(&arg, w)?);
+                        }
+                        if !h.is_empty() {
+                            scale.insert("height".into(), parse_number(&arg, h)?);
+                        }
+                    }
+                    "percent" => { scale.insert("percent".into(), parse_number(&arg, amount)?); }
+                    _ => { scale.insert("edge".into(), parse_number(&arg, amount)?); }
+                }
+            }
+            "--preset" | "--tune" | "--profile" | "--level" => {
+                encoder.insert(arg.trim_start_matches("--").into(), json!(value));
+            }
+            "--gop" => { encoder.insert("keyframe_interval".into(), parse_number(&arg, &value)?); }
+            "--bframes" => { encoder.insert("bframes".into(), parse_number(&arg, &value)?); }
+            "--x264-params" | "--x265-params" | "--svtav1-params" => {
+                encoder.insert(arg.trim_start_matches("--").replace('-', "_"), json!(value));
+            }
+            "--if-larger" => { overrides.insert("oversize_policy".into(), json!(value)); }
+            "--remux" => { overrides.insert("remux".into(), json!(value)); }
+            "--hdr" => { hdr.insert("mode".into(), json!(value)); }
+            "--tonemap" => {
+                hdr.insert("tonemap".into(), json!(value));
+                hdr.entry("mode").or_insert(json!("tonemap"));
+            }
+            "--fps" | "--max-fps" => {
+                frame_rate.insert("mode".into(), json!(if arg == "--fps" { "fixed" } else { "cap" }));
+                frame_rate.insert("fps".into(), parse_number(&arg, &value)?);
+            }
+            "--crop" => {
+                let crop = if value == "auto" {
+                    json!({ "mode": "auto" })
+                } else {
+                    let parts: Vec<&str> = value.split(':').collect();
+                    if parts.len() != 4 {
+                        return Err(format!("Invalid value for {}: {} (expected w:h:x:y or auto)", arg, value));
+                    }
+                    json!({
+                        "mode": "manual",
+                        "width": parse_number(&arg, parts[0])?,
+                        "height": parse_number(&arg, parts[1])?,
+                        "x": parse_number(&arg, parts[2])?,
+                        "y": parse_number(&arg, parts[3])?,
+                    })
+                };
+                filters.insert("crop".into(), crop);
+            }
+            "--rotate" => { filters.insert("rotate".into(), parse_number(&arg, &value)?); }
+            "--flip" => { filters.insert("flip".into(), json!(value)); }
+            "--deinterlace" => { filters.insert("deinterlace".into(), json!(value)); }
+            "--denoise" => {
+                let (method, strength) = match value.split_once(':') {
+                    Some((m, s)) => (m.to_string(), parse_number(&arg, s)?),
+                    None => (value.clone(), Value::Null),
+                };
+                filters.insert("denoise".into(), json!({ "method": method, "strength": strength }));
+            }
+            "--sharpen" => { filters.insert("sharpen".into(), parse_number(&arg, &value)?); }
+            "--subtitles" => { subtitles.insert("mode".into(), json!(value)); }
+            "--subtitle-stream" => { subtitles.insert("stream_index".into(), parse_number(&arg, &value)?); }
+            "--subtitle-file" => {
+                subtitles.insert("external_file".into(), json!(value));
+                subtitles.entry("mode").or_insert(json!("burn"));
+            }
+            "--loudnorm" => { overrides.insert("loudnorm".into(), json!({ "target_lufs": parse_number(&arg, &value)? })); }
+            "--start" => { time_range.insert("start".into(), parse_number(&arg, &value)?); }
+            "--end" => { time_range.insert("end".into(), parse_number(&arg, &value)?); }
+            "--width" => { custom_resolution.insert("width".into(), parse_number(&arg, &value)?); }
+            "--height" => { custom_resolution.insert("height".into(), parse_number(&arg, &value)?); }
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if !time_range.is_empty() {
+        time_range.entry("start").or_insert(Value::Null);
+        time_range.entry("end").or_insert(Value::Null);
+        overrides.insert("time_range".into(), Value::Object(time_range));
+    }
+    if !scale.is_empty() {
+        if !scale.contains_key("mode") {
+            return Err("--upscale requires --scale".into());
+        }
+        overrides.insert("scale".into(), Value::Object(scale));
+    }
+    if !encoder.is_empty() {
+        overrides.insert("encoder".into(), Value::Object(encoder));
+    }
+    if !metadata.is_empty() {
+        overrides.insert("metadata".into(), Value::Object(metadata));
+    }
+    if !hdr.is_empty() {
+        overrides.insert("hdr".into(), Value::Object(hdr));
+    }
+    if !frame_rate.is_empty() {
+        if !frame_rate.contains_key("mode") {
+            return Err("--interpolate requires --fps or --max-fps".into());
+        }
+        overrides.insert("frame_rate".into(), Value::Object(frame_rate));
+    }
+    if !filters.is_empty() {
+        overrides.insert("filters".into(), Value::Object(filters));
+    }
+    if !subtitles.is_empty() {
+        subtitles.entry("mode").or_insert(json!("burn"));
+        overrides.insert("subtitles".into(), Value::Object(subtitles));
+    }
+    if !audio.is_empty() {
+        overrides.insert("audio".into(), Value::Object(audio));
+    }
+    if !custom_resolution.is_empty() {
+        if custom_resolution.len() != 2 {
+            return Err("--width and --height must be given together".into());
+        }
+        overrides.insert("custom_resolution".into(), Value::Object(custom_resolution));
+        overrides.insert("resolution".into(), json!("custom"));
+    }
+
+    if positional.len() != 3 {
+        return Err("Expected <video|image> <input> <output>".into());
+    }
+    let output = positional.pop().unwrap();
+    let input = positional.pop().unwrap();
+    let kind = positional.pop().unwrap();
+    if kind != "video" && kind != "image" {
+        return Err(format!("Unknown job kind: {} (expected video or image)", kind));
+    }
+
+    Ok(CliArgs {
+        kind,
+        input,
+        output,
+        settings_file,
+        overrides,
+        ffmpeg,
+        ffprobe,
+        task_id,
+        output_template,
+        on_conflict,
+        dry_run,
+        json: json_output,
+        quiet,
+    })
+}
+
+// 组合配置：默认值 < JSON 文件 < 命令行参数
+fn build_settings(args: &CliArgs) -> Result<CompressionSettings, String> {
+    let mut settings = match &args.settings_file {
+        Some(path) => {
+            let data = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read settings file {}: {}", path, e))?;
+            match serde_json::from_str::<Value>(&data)
+                .map_err(|e| format!("Failed to parse settings file {}: {}", path, e))?
+            {
+                Value::Object(map) => map,
+                _ => return Err(format!("Settings file {} must contain a JSON object", path)),
+            }
+        }
+        None => Map::new(),
+    };
+    for (k, v) in &args.overrides {
+        settings.insert(k.clone(), v.clone());
+    }
+
+    // 使用模板时 <output> 是目录，格式需显式指定或沿用输入扩展名
+    let format_source = if args.output_template.is_some() { &args.input } else { &args.output };
+    let output_ext = std::path::Path::new(format_source)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    settings.entry("format").or_insert(json!(output_ext));
+    settings
+        .entry("codec")
+        .or_insert(json!(if args.kind == "image" { "image" } else { "H.264" }));
+    settings.entry("resolution").or_insert(json!("original"));
+    settings.entry("quality_type").or_insert(json!("crf"));
+    if args.kind == "image" {
+        settings.entry("crf_value").or_insert(json!(80));
+    }
+
+    serde_json::from_value(Value::Object(settings)).map_err(|e| format!("Invalid settings: {}", e))
+}
+
+// 把压缩事件打印到终端；--json 时 stdout 只留给最终结果，过程信息写到 stderr
+struct CliSink {
+    quiet: bool,
+    json: bool,
+}
+
+impl CliSink {
+    fn status(&self, line: String) {
+        if self.quiet {
+            return;
+        }
+        if self.json {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+impl EventSink for CliSink {
+    fn emit(&self, _task_id: &str, event: CompressionEvent) {
+        match event {
+            CompressionEvent::Progress { progress } => self.status(format!("[progress] {:.1}%", progress)),
+            CompressionEvent::Command { command, .. } => self.status(format!("[command] {}", command)),
+            CompressionEvent::NoGain { output_path, policy, .. } => {
+                self.status(format!("[no-gain] output is not smaller than the input ({}): {}", policy, output_path))
+            }
+            CompressionEvent::Error { error, stderr } => {
+                eprintln!("[error] {}", error);
+                if let Some(stderr) = stderr {
+                    eprintln!("{}", stderr);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 按模板与同名策略确定输出路径，返回 (路径, 是否跳过)。未指定模板时沿用 <output> 的文件名
+fn resolve_output(args: &CliArgs, job: &CompressionJob) -> Result<(String, bool), String> {
+    if args.output_template.is_none() && args.on_conflict.is_none() {
+        return Ok((args.output.clone(), false));
+    }
+    let output = std::path::Path::new(&args.output);
+    let (template, output_dir) = match &args.output_template {
+        Some(template) => (template.clone(), args.output.clone()),
+        None => (
+            output.file_stem().and_then(|s| s.to_str()).unwrap_or("output").to_string(),
+            output.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+        ),
+    };
+    let naming = OutputNaming {
+        template: Some(template),
+        output_dir: Some(output_dir).filter(|d| !d.is_empty()).or_else(|| Some(".".to_string())),
+        input_root: None,
+        collision: args.on_conflict.clone(),
+    };
+    let planned = plan_output_paths(std::slice::from_ref(job), &naming)?.remove(0);
+    Ok((planned.output_path, planned.skipped))
+}
+
+// --json 时核心库的日志（println!）也会写到 stdout。任务运行期间把 stdout 指向 stderr，结束后恢复，
+// 保证 stdout 上只有结果 JSON
+struct StdoutToStderr {
+    #[cfg(unix)]
+    saved: libc::c_int,
+    #[cfg(windows)]
+    saved: windows_sys::Win32::Foundation::HANDLE,
+}
+
+impl StdoutToStderr {
+    #[cfg(unix)]
+    fn start() -> Option<Self> {
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        // SAFETY: 只复制/替换标准输出的文件描述符
+        unsafe {
+            let saved = libc::dup(libc::STDOUT_FILENO);
+            if saved < 0 {
+                return None;
+            }
+            if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+                libc::close(saved);
+                return None;
+            }
+            Some(Self { saved })
+        }
+    }
+
+    #[cfg(windows)]
+    fn start() -> Option<Self> {
+        use windows_sys::Win32::System::Console::{GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        // SAFETY: 只替换进程的标准输出句柄，std 每次写入时重新获取句柄
+        unsafe {
+            let saved = GetStdHandle(STD_OUTPUT_HANDLE);
+            if SetStdHandle(STD_OUTPUT_HANDLE, GetStdHandle(STD_ERROR_HANDLE)) == 0 {
+                return None;
+            }
+            Some(Self { saved })
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn start() -> Option<Self> {
+        None
+    }
+}
+
+impl Drop for StdoutToStderr {
+    fn drop(&mut self) {
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        #[cfg(unix)]
+        // SAFETY: 恢复 start 中保存的描述符
+        unsafe {
+            libc::dup2(self.saved, libc::STDOUT_FILENO);
+            libc::close(self.saved);
+        }
+        #[cfg(windows)]
+        // SAFETY: 恢复 start 中保存的句柄
+        unsafe {
+            windows_sys::Win32::System::Console::SetStdHandle(
+                windows_sys::Win32::System::Console::STD_OUTPUT_HANDLE,
+                self.saved,
+            );
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    if raw.is_empty() || raw.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return if raw.is_empty() { ExitCode::from(2) } else { ExitCode::SUCCESS };
+    }
+    let args = match parse_args(raw) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let settings = match build_settings(&args) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    // 命令行下没有资源目录，解析顺序为 参数 > 环境变量 > 可执行文件目录 > PATH
+    init_binary_resolver(None, None);
+    for (kind, path) in [(BinaryKind::Ffmpeg, &args.ffmpeg), (BinaryKind::Ffprobe, &args.ffprobe)] {
+        if let Some(p) = path {
+            if let Err(e) = set_binary_override(kind, Some(p.clone())) {
+                eprintln!("error: {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let mut job = CompressionJob {
+        task_id: args.task_id.clone(),
+        kind: if args.kind == "image" { JobKind::Image } else { JobKind::Video },
+        input_path: args.input.clone(),
+        output_path: args.output.clone(),
+        settings,
+    };
+    match resolve_output(&args, &job) {
+        Ok((path, false)) => {
+            job.output_path = path;
+            job.settings.collision = args.on_conflict.clone();
+        }
+        Ok((path, true)) => {
+            eprintln!("[skipped] output already exists: {}", path);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    }
+    if args.dry_run {
+        println!("[dry-run] {} -> {}", args.input, job.output_path);
+        return ExitCode::SUCCESS;
+    }
+    let output = job.output_path.clone();
+    let redirect = if args.json { StdoutToStderr::start() } else { None };
+    let result = run_job(job, Arc::new(CliSink { quiet: args.quiet, json: args.json }), JobOptions::default()).await;
+    drop(redirect);
+
+    match result {
+        Ok(result) => {
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+            } else if result.success {
+                println!(
+                    "[done] {} -> {} ({} -> {} bytes)",
+                    args.input,
+                    result.output_path.as_deref().unwrap_or(&output),
+                    result.original_size,
+                    result.compressed_size.unwrap_or(0)
+                );
+            }
+            if !args.json {
+                for warning in result.warnings.iter().flatten() {
+                    eprintln!("[warning] {}", warning);
+                }
+            }
+            if result.success {
+                ExitCode::SUCCESS
+            } else {
+                if !args.json {
+                    eprintln!("[failed] {}", result.error.as_deref().unwrap_or("compression failed"));
+                }
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("[failed] {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
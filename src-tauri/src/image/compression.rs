@@ -1,7 +1,13 @@
-use crate::video::{CompressionSettings, CompressionResult, get_ffmpeg_binary};
-use tauri::Manager; // 引入 Manager trait 以便使用 AppHandle.path()
-use tauri::Emitter; // 新增：引入 Emitter 以启用 app_handle.emit()
+use crate::video::{CompressionSettings, CompressionResult};
+use crate::video::binaries::{resolve_ffmpeg, resolve_ffprobe};
+use crate::video::events::{CompressionEvent, SharedEventSink, TauriEventSink};
+use crate::video::engine::{run_job, CompressionJob, JobKind, JobOptions};
 use crate::video::utils::tokio_command_with_no_window;
+use crate::video::oversize::{apply_oversize_policy, validate_oversize_policy, STATUS_COMPRESSED, STATUS_NO_GAIN};
+use crate::video::output::{validate_output, PartialOutput};
+use crate::video::metadata::{apply_file_times, metadata_args};
+use crate::image::exif::copy_exif;
+use crate::video::scale::{scale_filters, validate_scale, IMAGE_DIMENSION_MULTIPLE};
 // use std::sync::{Arc, OnceLock};
 // use tokio::sync::Semaphore;
 
@@ -55,6 +61,11 @@ fn map_webp_quality(crf_value: Option<u8>) -> u8 {
 }
 
 fn build_scale_filter(settings: &CompressionSettings) -> Option<String> {
+    // 保持宽高比的缩放模式优先
+    if let Some(scale) = &settings.scale {
+        let filters = scale_filters(scale, IMAGE_DIMENSION_MULTIPLE);
+        return (!filters.is_empty()).then(|| filters.join(","));
+    }
     if settings.resolution.eq_ignore_ascii_case("original") { return None; }
     if settings.resolution.eq_ignore_ascii_case("custom") {
         if let Some(custom) = &settings.custom_resolution {
@@ -77,6 +88,24 @@ pub async fn compress_image(
     outputPath: String,
     settings: CompressionSettings,
     app_handle: tauri::AppHandle,
+) -> Result<CompressionResult, String> {
+    let job = CompressionJob {
+        task_id: taskId,
+        kind: JobKind::Image,
+        input_path: inputPath,
+        output_path: outputPath,
+        settings,
+    };
+    run_job(job, TauriEventSink::shared(&app_handle), JobOptions::default()).await
+}
+
+/// 图片压缩核心逻辑，不依赖 Tauri
+pub async fn run_image_compression(
+    task_id: String,
+    input_path: String,
+    output_path: String,
+    settings: CompressionSettings,
+    sink: SharedEventSink,
 ) -> Result<CompressionResult, String> {
     // 移除：进入排队逻辑
     // let sem = image_compress_semaphore().clone();
@@ -91,40 +120,15 @@ pub async fn compress_image(
     //     }
     // };
 
-    // Resolve ffmpeg path (reuse logic from video module)
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        // 生产环境：构建候选路径并选择第一个存在的
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let mut candidates: Vec<std::path::PathBuf> = Vec::new();
-        candidates.push(resource_dir.join("bin").join(get_ffmpeg_binary()));
-        if let Ok(exe_path) = std::env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                // 带后缀的打包文件名
-                candidates.push(exe_dir.join(get_ffmpeg_binary()));
-                // 无后缀名称（如 Contents/MacOS/ffmpeg）
-                #[cfg(target_os = "windows")]
-                {
-                    candidates.push(exe_dir.join("ffmpeg.exe"));
-                }
-                #[cfg(not(target_os = "windows"))]
-                {
-                    candidates.push(exe_dir.join("ffmpeg"));
-                }
-            }
-        }
-        candidates.into_iter().find(|p| p.exists()).unwrap_or_else(|| resource_dir.join("bin").join(get_ffmpeg_binary()))
-    };
+    // Resolve ffmpeg path (shared resolver from video module)
+    let ffmpeg_path = resolve_ffmpeg()?;
 
     // 基本参数打印
     println!("[Image] ================= compress_image =================");
-    println!("[Image] taskId: {}", taskId);
+    println!("[Image] taskId: {}", task_id);
     println!("[Image] FFmpeg path: {:?}", ffmpeg_path);
-    println!("[Image] input: {}", inputPath);
-    println!("[Image] output: {}", outputPath);
+    println!("[Image] input: {}", input_path);
+    println!("[Image] output: {}", output_path);
     println!("[Image] format: {}", settings.format);
     println!("[Image] quality_type: {}", settings.quality_type);
     println!("[Image] crf_value: {:?}", settings.crf_value);
@@ -135,37 +139,12 @@ pub async fn compress_image(
         println!("[Image] custom_resolution: (none)");
     }
 
-    if !ffmpeg_path.exists() {
-        // 进一步给出候选路径提示，帮助定位打包位置
-        let mut tried: Vec<String> = Vec::new();
-        if cfg!(debug_assertions) {
-            let current_exe = std::env::current_exe().unwrap();
-            let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-            tried.push(src_tauri_dir.join("bin").join(get_ffmpeg_binary()).display().to_string());
-        } else {
-            let resource_dir = app_handle.path().resource_dir().unwrap();
-            tried.push(resource_dir.join("bin").join(get_ffmpeg_binary()).display().to_string());
-            if let Ok(exe_path) = std::env::current_exe() {
-                if let Some(exe_dir) = exe_path.parent() {
-                    tried.push(exe_dir.join(get_ffmpeg_binary()).display().to_string());
-                    #[cfg(target_os = "windows")]
-                    {
-                        tried.push(exe_dir.join("ffmpeg.exe").display().to_string());
-                    }
-                    #[cfg(not(target_os = "windows"))]
-                    {
-                        tried.push(exe_dir.join("ffmpeg").display().to_string());
-                    }
-                }
-            }
-        }
-        return Err(format!(
-            "FFmpeg binary not found. Tried: {}",
-            tried.join(" | ")
-        ));
+    if let Some(scale) = &settings.scale {
+        validate_scale(scale)?;
     }
+    validate_oversize_policy(&settings)?;
 
-    let original_size = std::fs::metadata(&inputPath)
+    let original_size = std::fs::metadata(&input_path)
         .map_err(|e| format!("Failed to get file size: {}", e))?
         .len();
 
@@ -174,9 +153,9 @@ pub async fn compress_image(
     let mut args_for_log: Vec<String> = Vec::new();
     cmd.arg("-y");
     args_for_log.push("-y".to_string());
-    cmd.arg("-i").arg(&inputPath);
+    cmd.arg("-i").arg(&input_path);
     args_for_log.push("-i".to_string());
-    args_for_log.push(inputPath.clone());
+    args_for_log.push(input_path.clone());
 
     // Get scale filter first
     let scale_filter = build_scale_filter(&settings);
@@ -274,23 +253,27 @@ pub async fn compress_image(
         }
     }
 
-    // 输出路径
-    cmd.arg(&outputPath);
-    args_for_log.push(outputPath.clone());
+    // 元数据参数（以输出扩展名判断容器）
+    for arg in metadata_args(&settings, &output_path, 0) {
+        cmd.arg(&arg);
+        args_for_log.push(arg);
+    }
+
+    // 输出路径：先写入临时文件，校验通过后再重命名
+    let mut partial = PartialOutput::new(&output_path);
+    let partial_path = partial.path().to_string();
+    cmd.arg(&partial_path);
+    args_for_log.push(partial_path.clone());
     // 可选：通过事件发送 FFmpeg 命令到前端（若不需要可删除）
     let args_joined = args_for_log
         .iter()
         .map(|a| if a.contains(' ') { format!("\"{}\"", a) } else { a.clone() })
         .collect::<Vec<_>>()
         .join(" ");
-    let _ = app_handle.emit(
-        &format!("compression-command-{}", taskId),
-        serde_json::json!({
-            "taskId": taskId,
-            "command": format!("{:?} {}", ffmpeg_path, args_joined),
-            "args": args_for_log,
-        }),
-    );
+    sink.emit(&task_id, CompressionEvent::Command {
+        command: format!("{:?} {}", ffmpeg_path, args_joined),
+        args: args_for_log.clone(),
+    });
 
     println!("[Image] ffmpeg command: {:?} {}",
         ffmpeg_path,
@@ -305,31 +288,64 @@ pub async fn compress_image(
 
     // 检查输出文件是否存在
     println!("[Image] Checking output file existence...");
-    println!("[Image] Output path: {}", outputPath);
-    println!("[Image] Output path exists: {}", std::path::Path::new(&outputPath).exists());
+    println!("[Image] Output path: {}", partial_path);
+    println!("[Image] Output path exists: {}", std::path::Path::new(&partial_path).exists());
     
-    if !std::path::Path::new(&outputPath).exists() {
-        return Err(format!("Output file was not created: {}", outputPath));
+    if !std::path::Path::new(&partial_path).exists() {
+        return Err(format!("Output file was not created: {}", output_path));
+    }
+    validate_output(&resolve_ffprobe()?, &partial_path, None).await?;
+
+    // 保留元数据：FFmpeg 不写入 EXIF，从源文件复制到输出。失败不影响压缩结果，只在结果中提示
+    let mut warnings: Vec<String> = Vec::new();
+    if settings.metadata.as_ref().and_then(|m| m.tags) == Some(true) {
+        if let Err(e) = copy_exif(&input_path, &partial_path) {
+            let message = format!("EXIF metadata was not preserved: {}", e);
+            println!("⚠️ {}", message);
+            warnings.push(message);
+        }
     }
 
     // 计算压缩前后的文件大小
-    let compressed_size = std::fs::metadata(&outputPath)
+    let compressed_size = std::fs::metadata(&partial_path)
         .map_err(|e| format!("Failed to get compressed file size: {}", e))?
         .len();
 
     println!("[Image] Compression completed successfully!");
     println!("[Image] Original size: {} bytes", original_size);
     println!("[Image] Compressed size: {} bytes", compressed_size);
-    println!("[Image] Final output path: {}", outputPath);
+    println!("[Image] Final output path: {}", output_path);
 
     // 移除：permit 释放（已不存在）
 
+    // 输出没有变小：按策略保留、丢弃或换成原文件；保留编码结果时把临时文件移到目标路径
+    let outcome = apply_oversize_policy(
+        &task_id,
+        &settings,
+        &input_path,
+        &partial_path,
+        &output_path,
+        original_size,
+        Some(compressed_size),
+        &sink,
+    )?;
+    let (output_path, compressed_size, status) = match outcome {
+        Some(outcome) if !outcome.kept_encoded => (outcome.output_path, outcome.size, STATUS_NO_GAIN),
+        Some(outcome) => (partial.commit(&output_path, settings.collision.as_deref())?, outcome.size, STATUS_NO_GAIN),
+        None => (partial.commit(&output_path, settings.collision.as_deref())?, compressed_size, STATUS_COMPRESSED),
+    };
+
+    apply_file_times(&settings, &input_path, &output_path);
+
     Ok(CompressionResult {
         success: true,
-        output_path: Some(outputPath.clone()),
+        output_path: Some(output_path.clone()),
         error: None,
         original_size,
         compressed_size: Some(compressed_size),
         compressed_metadata: None,
+        status: Some(status.to_string()),
+        warnings: (!warnings.is_empty()).then_some(warnings),
+        ..Default::default()
     })
 }
\ No newline at end of file
@@ -0,0 +1,335 @@
+// EXIF 复制：FFmpeg 的 mjpeg / png / libwebp 编码器不会写入 EXIF，保留元数据时直接把源文件的 EXIF 块
+// （TIFF 头开始的原始数据）写入输出：
+//   JPEG  APP1 "Exif\0\0" 段，放在 SOI 与 JFIF APP0 之后
+//   PNG   eXIf 块，放在第一个 IDAT 之前
+//   WebP  EXIF 块；简单格式（VP8 / VP8L）先转换为带 VP8X 头的扩展格式
+// 源可以是 JPEG、PNG 或 WebP。FFmpeg 不按 EXIF 方向旋转像素，方向标记随 EXIF 一起保留。
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+// VP8X 标志位
+const VP8X_EXIF: u8 = 0x08;
+const VP8X_ALPHA: u8 = 0x10;
+
+fn u16_be(data: &[u8], pos: usize) -> Option<usize> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?) as usize)
+}
+
+fn u32_be(data: &[u8], pos: usize) -> Option<usize> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize)
+}
+
+fn u32_le(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn is_tiff(exif: &[u8]) -> bool {
+    exif.starts_with(b"II*\0") || exif.starts_with(b"MM\0*")
+}
+
+fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP"
+}
+
+// JPEG 段：(标记, 段起点, 段数据)，遇到图像数据（SOS）时停止
+fn jpeg_segments(data: &[u8]) -> Vec<(u8, usize, &[u8])> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let Some(len) = u16_be(data, pos + 2).filter(|len| *len >= 2) else { break };
+        let Some(body) = data.get(pos + 4..pos + 2 + len) else { break };
+        segments.push((marker, pos, body));
+        pos += 2 + len;
+    }
+    segments
+}
+
+// PNG 块：(类型, 块起点, 块数据)
+fn png_chunks(data: &[u8]) -> Vec<(&[u8], usize, &[u8])> {
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while let Some(len) = u32_be(data, pos) {
+        let (Some(kind), Some(body)) = (data.get(pos + 4..pos + 8), data.get(pos + 8..pos + 8 + len)) else { break };
+        chunks.push((kind, pos, body));
+        pos += 12 + len;
+    }
+    chunks
+}
+
+// RIFF 块：(FourCC, 块起点, 块数据)，块数据按偶数字节对齐
+fn webp_chunks(data: &[u8]) -> Vec<(&[u8], usize, &[u8])> {
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while let Some(len) = u32_le(data, pos + 4).map(|l| l as usize) {
+        let Some(body) = data.get(pos + 8..pos + 8 + len) else { break };
+        chunks.push((&data[pos..pos + 4], pos, body));
+        pos += 8 + len + (len & 1);
+    }
+    chunks
+}
+
+/// 读取图片中的 EXIF（TIFF 头开始的原始数据），没有时返回 None
+pub(crate) fn read_exif(data: &[u8]) -> Option<&[u8]> {
+    let exif = if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_segments(data)
+            .into_iter()
+            .find(|(marker, _, body)| *marker == 0xE1 && body.starts_with(EXIF_HEADER))
+            .map(|(_, _, body)| &body[EXIF_HEADER.len()..])
+    } else if data.starts_with(PNG_SIGNATURE) {
+        png_chunks(data).into_iter().find(|(kind, _, _)| *kind == b"eXIf").map(|(_, _, body)| body)
+    } else if is_webp(data) {
+        // 部分软件写入的 WebP EXIF 带有 "Exif\0\0" 前缀
+        webp_chunks(data)
+            .into_iter()
+            .find(|(kind, _, _)| *kind == b"EXIF")
+            .map(|(_, _, body)| body.strip_prefix(EXIF_HEADER).unwrap_or(body))
+    } else {
+        None
+    };
+    exif.filter(|e| is_tiff(e))
+}
+
+// PNG 块使用的 CRC-32（IEEE 802.3）
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn insert_jpeg(data: &[u8], exif: &[u8]) -> Result<Vec<u8>, String> {
+    let len = 2 + EXIF_HEADER.len() + exif.len();
+    if len > u16::MAX as usize {
+        return Err(format!("EXIF data ({} bytes) does not fit in a JPEG APP1 segment", exif.len()));
+    }
+    let segments = jpeg_segments(data);
+    if segments.iter().any(|(marker, _, body)| *marker == 0xE1 && body.starts_with(EXIF_HEADER)) {
+        return Err("Output already contains EXIF data".to_string());
+    }
+    // APP0（JFIF）之后插入
+    let pos = segments
+        .iter()
+        .take_while(|(marker, _, _)| *marker == 0xE0)
+        .last()
+        .map_or(2, |(_, start, body)| start + 4 + body.len());
+    let mut out = Vec::with_capacity(data.len() + len + 2);
+    out.extend_from_slice(&data[..pos]);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&(len as u16).to_be_bytes());
+    out.extend_from_slice(EXIF_HEADER);
+    out.extend_from_slice(exif);
+    out.extend_from_slice(&data[pos..]);
+    Ok(out)
+}
+
+fn insert_png(data: &[u8], exif: &[u8]) -> Result<Vec<u8>, String> {
+    let chunks = png_chunks(data);
+    if chunks.iter().any(|(kind, _, _)| *kind == b"eXIf") {
+        return Err("Output already contains EXIF data".to_string());
+    }
+    let pos = chunks
+        .iter()
+        .find(|(kind, _, _)| *kind == b"IDAT")
+        .map(|(_, start, _)| *start)
+        .ok_or_else(|| "PNG output has no image data".to_string())?;
+    let mut chunk = Vec::with_capacity(exif.len() + 12);
+    chunk.extend_from_slice(&(exif.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(b"eXIf");
+    chunk.extend_from_slice(exif);
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+    Ok([&data[..pos], &chunk, &data[pos..]].concat())
+}
+
+// 简单格式 WebP 的画布尺寸与透明度：(宽, 高, 是否有透明通道)
+fn webp_canvas(kind: &[u8], body: &[u8]) -> Option<(u32, u32, bool)> {
+    match kind {
+        b"VP8 " if body.get(3..6)? == [0x9D, 0x01, 0x2A] => {
+            let width = u16::from_le_bytes(body.get(6..8)?.try_into().ok()?) & 0x3FFF;
+            let height = u16::from_le_bytes(body.get(8..10)?.try_into().ok()?) & 0x3FFF;
+            Some((width as u32, height as u32, false))
+        }
+        b"VP8L" if body.first() == Some(&0x2F) => {
+            let bits = u32_le(body, 1)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1, (bits >> 28) & 1 == 1))
+        }
+        _ => None,
+    }
+}
+
+fn insert_webp(data: &[u8], exif: &[u8]) -> Result<Vec<u8>, String> {
+    let chunks = webp_chunks(data);
+    if chunks.iter().any(|(kind, _, _)| *kind == b"EXIF") {
+        return Err("Output already contains EXIF data".to_string());
+    }
+    let Some(&(first, _, first_body)) = chunks.first() else { return Err("WebP output has no chunks".to_string()) };
+    let mut out = data[..12].to_vec();
+    if first == b"VP8X" {
+        out.extend_from_slice(&data[12..]);
+        out[20] |= VP8X_EXIF;
+    } else {
+        let (width, height, alpha) =
+            webp_canvas(first, first_body)
+                .filter(|(w, h, _)| *w > 0 && *h > 0)
+                .ok_or_else(|| "Unrecognized WebP image data".to_string())?;
+        out.extend_from_slice(b"VP8X");
+        out.extend_from_slice(&10u32.to_le_bytes());
+        out.push(VP8X_EXIF | if alpha { VP8X_ALPHA } else { 0 });
+        out.extend_from_slice(&[0, 0, 0]);
+        out.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        out.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        out.extend_from_slice(&data[12..]);
+    }
+    // EXIF 块位于图像数据之后，块起点按偶数字节对齐
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    out.extend_from_slice(b"EXIF");
+    out.extend_from_slice(&(exif.len() as u32).to_le_bytes());
+    out.extend_from_slice(exif);
+    if exif.len() % 2 == 1 {
+        out.push(0);
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(out)
+}
+
+/// 把 EXIF 写入 JPEG / PNG / WebP 图片数据
+pub(crate) fn insert_exif(data: &[u8], exif: &[u8]) -> Result<Vec<u8>, String> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        insert_jpeg(data, exif)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        insert_png(data, exif)
+    } else if is_webp(data) {
+        insert_webp(data, exif)
+    } else {
+        Err("EXIF can only be written to JPEG, PNG or WebP output".to_string())
+    }
+}
+
+/// 把源图片的 EXIF 复制到输出文件。源没有 EXIF 时返回 Ok(false)
+pub fn copy_exif(input_path: &str, output_path: &str) -> Result<bool, String> {
+    let source = std::fs::read(input_path).map_err(|e| format!("Failed to read {}: {}", input_path, e))?;
+    let Some(exif) = read_exif(&source) else { return Ok(false) };
+    let output = std::fs::read(output_path).map_err(|e| format!("Failed to read {}: {}", output_path, e))?;
+    let updated = insert_exif(&output, exif)?;
+    std::fs::write(output_path, updated).map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+    println!("📷 Copied {} bytes of EXIF from {} to {}", exif.len(), input_path, output_path);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXIF: &[u8] = b"MM\0*\0\0\0\x08\0\0";
+
+    fn jpeg(segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        for (marker, body) in segments {
+            data.extend_from_slice(&[0xFF, *marker]);
+            data.extend_from_slice(&((body.len() + 2) as u16).to_be_bytes());
+            data.extend_from_slice(body);
+        }
+        // SOS 之后的扫描数据不按段解析
+        data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+        data
+    }
+
+    fn png_chunk(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(body);
+        let crc = crc32(&chunk[4..]);
+        chunk.extend_from_slice(&crc.to_be_bytes());
+        chunk
+    }
+
+    fn png(chunks: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        for (kind, body) in chunks {
+            data.extend(png_chunk(kind, body));
+        }
+        data
+    }
+
+    fn webp(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut data = b"RIFF\0\0\0\0WEBP".to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            data.push(0);
+        }
+        let size = (data.len() - 8) as u32;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn crc32_matches_png_reference() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn reads_exif_from_each_format() {
+        let app1 = [EXIF_HEADER, EXIF].concat();
+        assert_eq!(read_exif(&jpeg(&[(0xE0, b"JFIF\0"), (0xE1, &app1)])), Some(EXIF));
+        assert_eq!(read_exif(&png(&[(b"IHDR", &[0; 13]), (b"eXIf", EXIF), (b"IEND", b"")])), Some(EXIF));
+        assert_eq!(read_exif(&webp(b"EXIF", &app1)), Some(EXIF));
+        // XMP 的 APP1 与不是 TIFF 头的数据都不算 EXIF
+        assert_eq!(read_exif(&jpeg(&[(0xE1, b"http://ns.adobe.com/xap/1.0/\0")])), None);
+        assert_eq!(read_exif(&png(&[(b"eXIf", b"garbage"), (b"IEND", b"")])), None);
+        assert_eq!(read_exif(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn inserts_exif_into_jpeg_after_jfif() {
+        let output = jpeg(&[(0xE0, b"JFIF\0"), (0xDB, &[0; 4])]);
+        let updated = insert_exif(&output, EXIF).unwrap();
+        let markers: Vec<u8> = jpeg_segments(&updated).iter().map(|(m, _, _)| *m).collect();
+        assert_eq!(markers, vec![0xE0, 0xE1, 0xDB]);
+        assert_eq!(read_exif(&updated), Some(EXIF));
+        assert!(updated.ends_with(&[0xFF, 0xD9]));
+        assert!(insert_exif(&updated, EXIF).is_err());
+    }
+
+    #[test]
+    fn inserts_exif_into_png_before_idat() {
+        let output = png(&[(b"IHDR", &[0; 13]), (b"IDAT", b"pixels"), (b"IEND", b"")]);
+        let updated = insert_exif(&output, EXIF).unwrap();
+        let kinds: Vec<&[u8]> = png_chunks(&updated).iter().map(|(k, _, _)| *k).collect();
+        assert_eq!(kinds, vec![&b"IHDR"[..], b"eXIf", b"IDAT", b"IEND"]);
+        assert!(updated.windows(png_chunk(b"eXIf", EXIF).len()).any(|w| w == png_chunk(b"eXIf", EXIF)));
+    }
+
+    #[test]
+    fn converts_simple_webp_to_extended_format() {
+        // VP8L：宽 300、高 200、带透明通道
+        let bits: u32 = 299 | (199 << 14) | (1 << 28);
+        let mut body = vec![0x2F];
+        body.extend_from_slice(&bits.to_le_bytes());
+        let updated = insert_exif(&webp(b"VP8L", &body), EXIF).unwrap();
+        let chunks = webp_chunks(&updated);
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(k, _, _)| *k).collect();
+        assert_eq!(kinds, vec![&b"VP8X"[..], b"VP8L", b"EXIF"]);
+        assert_eq!(chunks[0].2, &[VP8X_EXIF | VP8X_ALPHA, 0, 0, 0, 43, 1, 0, 199, 0, 0]);
+        assert_eq!(u32_le(&updated, 4), Some((updated.len() - 8) as u32));
+        assert_eq!(read_exif(&updated), Some(EXIF));
+
+        // 已是扩展格式时只设置 EXIF 标志
+        let extended = webp(b"VP8X", &[VP8X_ALPHA, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let updated = insert_exif(&extended, EXIF).unwrap();
+        assert_eq!(updated[20], VP8X_ALPHA | VP8X_EXIF);
+        assert_eq!(read_exif(&updated), Some(EXIF));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod compression;
+pub mod exif;
 
 pub use compression::*;
\ No newline at end of file
@@ -1,5 +1,5 @@
-mod video;
-mod image;
+pub mod video;
+pub mod image;
 
 use video::*;
 use image::*;
@@ -42,6 +42,14 @@ pub fn run() {
         .setup(|app| {
             // 全局关闭状态跟踪（每个窗口共享同一状态即可）
             app.manage(CloseTracker::default());
+            // 统一的 FFmpeg/FFprobe 解析器：资源目录 + 用户自定义路径配置
+            init_binary_resolver(app.path().resource_dir().ok(), app.path().app_local_data_dir().ok());
+            // 任务历史与后端任务队列：事件转发到 webview，恢复上次未完成的任务
+            let data_dir = app.path().app_local_data_dir().ok();
+            init_task_history(data_dir.clone());
+            if init_job_queue(TauriEventSink::shared(app.handle()), data_dir) > 0 {
+                tauri::async_runtime::spawn(start_restored_jobs(app.handle().clone()));
+            }
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -86,6 +94,22 @@ pub fn run() {
             refresh_hardware_encoder_support,
             terminate_all_tasks,
             remove_file,
+            get_ffmpeg_info,
+            set_ffmpeg_paths,
+            detect_black_bars,
+            // queue
+            enqueue_compression_jobs,
+            get_queue_state,
+            reorder_queue,
+            set_queue_job_priority,
+            set_queue_concurrency,
+            pause_queue,
+            resume_queue,
+            cancel_queue_job,
+            clear_finished_queue_jobs,
+            query_task_history,
+            clear_task_history,
+            preview_output_paths,
             // image
             compress_image
         ])
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use crate::video::utils::{command_with_no_window, get_ffmpeg_binary, get_ffprobe_binary};
+
+// FFmpeg / FFprobe 统一解析：
+// 用户自定义路径 > 环境变量 > 开发目录 src-tauri/bin > 资源目录 bin > 可执行文件目录 > PATH
+// 解析结果会被缓存，所有命令共享同一份结果。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryKind {
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl BinaryKind {
+    fn display_name(&self) -> &'static str {
+        match self {
+            BinaryKind::Ffmpeg => "FFmpeg",
+            BinaryKind::Ffprobe => "FFprobe",
+        }
+    }
+
+    // 打包时带平台后缀的文件名，例如 ffmpeg-aarch64-apple-darwin
+    fn bundled_name(&self) -> &'static str {
+        match self {
+            BinaryKind::Ffmpeg => get_ffmpeg_binary(),
+            BinaryKind::Ffprobe => get_ffprobe_binary(),
+        }
+    }
+
+    // 不带后缀的名称（系统安装 / Contents/MacOS 下的 sidecar）
+    fn plain_name(&self) -> &'static str {
+        match self {
+            BinaryKind::Ffmpeg => if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" },
+            BinaryKind::Ffprobe => if cfg!(windows) { "ffprobe.exe" } else { "ffprobe" },
+        }
+    }
+
+    fn env_var(&self) -> &'static str {
+        match self {
+            BinaryKind::Ffmpeg => "COMPRESSGO_FFMPEG",
+            BinaryKind::Ffprobe => "COMPRESSGO_FFPROBE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedBinary {
+    pub kind: BinaryKind,
+    pub path: PathBuf,
+    pub source: String, // custom | env | dev | resource | exe_dir | path
+    pub version: Option<String>,
+}
+
+// 结构化的“未找到”错误，列出所有尝试过的候选路径
+#[derive(Debug, Clone)]
+pub struct BinaryNotFound {
+    pub kind: BinaryKind,
+    pub tried: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for BinaryNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tried = self
+            .tried
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+        write!(f, "{} binary not found. Tried: {}", self.kind.display_name(), tried)
+    }
+}
+
+impl std::error::Error for BinaryNotFound {}
+
+impl From<BinaryNotFound> for String {
+    fn from(e: BinaryNotFound) -> Self {
+        e.to_string()
+    }
+}
+
+// 用户自定义的二进制路径，持久化在 app_local_data_dir/binaries.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BinaryOverrides {
+    #[serde(rename = "ffmpegPath")]
+    pub ffmpeg_path: Option<String>,
+    #[serde(rename = "ffprobePath")]
+    pub ffprobe_path: Option<String>,
+}
+
+impl BinaryOverrides {
+    fn get(&self, kind: BinaryKind) -> Option<&String> {
+        match kind {
+            BinaryKind::Ffmpeg => self.ffmpeg_path.as_ref(),
+            BinaryKind::Ffprobe => self.ffprobe_path.as_ref(),
+        }
+    }
+
+    fn set(&mut self, kind: BinaryKind, path: Option<String>) {
+        match kind {
+            BinaryKind::Ffmpeg => self.ffmpeg_path = path,
+            BinaryKind::Ffprobe => self.ffprobe_path = path,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ResolverState {
+    resource_dir: Option<PathBuf>,
+    config_path: Option<PathBuf>,
+    overrides: BinaryOverrides,
+    cache: HashMap<BinaryKind, ResolvedBinary>,
+}
+
+static RESOLVER: OnceLock<Mutex<ResolverState>> = OnceLock::new();
+
+fn resolver_state() -> &'static Mutex<ResolverState> {
+    RESOLVER.get_or_init(|| Mutex::new(ResolverState::default()))
+}
+
+/// 在启动时调用：记录资源目录并加载用户自定义路径
+pub fn init_binary_resolver(resource_dir: Option<PathBuf>, config_dir: Option<PathBuf>) {
+    let config_path = config_dir.map(|dir| dir.join("binaries.json"));
+    let overrides = config_path
+        .as_ref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|data| serde_json::from_str::<BinaryOverrides>(&data).ok())
+        .unwrap_or_default();
+    let mut state = resolver_state().lock().unwrap();
+    state.resource_dir = resource_dir;
+    state.config_path = config_path;
+    state.overrides = overrides;
+    state.cache.clear();
+}
+
+// 开发模式：current_exe 位于 src-tauri/target/debug/[app]，上移 3 层到 src-tauri
+fn dev_bin_dir() -> Option<PathBuf> {
+    let current_exe = std::env::current_exe().ok()?;
+    Some(current_exe.parent()?.parent()?.parent()?.join("bin"))
+}
+
+fn search_path_dirs(name: &str) -> Vec<PathBuf> {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).map(|dir| dir.join(name)).collect())
+        .unwrap_or_default()
+}
+
+fn candidates(kind: BinaryKind, state: &ResolverState) -> Vec<(&'static str, PathBuf)> {
+    let mut list: Vec<(&'static str, PathBuf)> = Vec::new();
+    if let Some(custom) = state.overrides.get(kind) {
+        list.push(("custom", PathBuf::from(custom)));
+    }
+    if let Some(env_path) = std::env::var_os(kind.env_var()) {
+        list.push(("env", PathBuf::from(env_path)));
+    }
+    if cfg!(debug_assertions) {
+        if let Some(dir) = dev_bin_dir() {
+            list.push(("dev", dir.join(kind.bundled_name())));
+            list.push(("dev", dir.join(kind.plain_name())));
+        }
+    }
+    if let Some(resource_dir) = &state.resource_dir {
+        let bin = resource_dir.join("bin");
+        list.push(("resource", bin.join(kind.bundled_name())));
+        list.push(("resource", bin.join(kind.plain_name())));
+    }
+    if let Some(exe_dir) = std::env::current_exe().ok().and_then(|p| p.parent().map(|p| p.to_path_buf())) {
+        list.push(("exe_dir", exe_dir.join(kind.bundled_name())));
+        list.push(("exe_dir", exe_dir.join(kind.plain_name())));
+    }
+    for p in search_path_dirs(kind.plain_name()) {
+        list.push(("path", p));
+    }
+    // 去重，保持顺序
+    let mut seen = std::collections::HashSet::new();
+    list.retain(|(_, p)| seen.insert(p.clone()));
+    list
+}
+
+fn probe_version(path: &Path) -> Option<String> {
+    let output = command_with_no_window(path).arg("-version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+/// 解析指定二进制（带缓存）
+pub fn resolve_binary(kind: BinaryKind) -> Result<ResolvedBinary, BinaryNotFound> {
+    let list = {
+        let state = resolver_state().lock().unwrap();
+        if let Some(cached) = state.cache.get(&kind) {
+            if cached.path.is_file() {
+                return Ok(cached.clone());
+            }
+        }
+        candidates(kind, &state)
+    };
+
+    match list.iter().find(|(_, p)| p.is_file()) {
+        Some((source, path)) => {
+            let resolved = ResolvedBinary {
+                kind,
+                path: path.clone(),
+                source: source.to_string(),
+                version: probe_version(path),
+            };
+            info!(
+                "[Binaries] {} resolved from {}: {:?} ({})",
+                kind.display_name(),
+                source,
+                resolved.path,
+                resolved.version.as_deref().unwrap_or("unknown version")
+            );
+            resolver_state().lock().unwrap().cache.insert(kind, resolved.clone());
+            Ok(resolved)
+        }
+        None => {
+            let err = BinaryNotFound {
+                kind,
+                tried: list.into_iter().map(|(_, p)| p).collect(),
+            };
+            warn!("[Binaries] {}", err);
+            Err(err)
+        }
+    }
+}
+
+pub fn resolve_ffmpeg() -> Result<PathBuf, String> {
+    Ok(resolve_binary(BinaryKind::Ffmpeg)?.path)
+}
+
+pub fn resolve_ffprobe() -> Result<PathBuf, String> {
+    Ok(resolve_binary(BinaryKind::Ffprobe)?.path)
+}
+
+/// 设置（或清除）用户自定义路径，并持久化
+pub fn set_binary_override(kind: BinaryKind, path: Option<String>) -> Result<(), String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(p) = &path {
+        if !Path::new(p).is_file() {
+            return Err(format!("{} binary does not exist: {}", kind.display_name(), p));
+        }
+    }
+    let mut state = resolver_state().lock().unwrap();
+    state.overrides.set(kind, path);
+    state.cache.remove(&kind);
+    if let Some(config_path) = &state.config_path {
+        if let Some(dir) = config_path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let data = serde_json::to_string_pretty(&state.overrides).map_err(|e| e.to_string())?;
+        std::fs::write(config_path, data).map_err(|e| format!("Failed to save binary overrides: {}", e))?;
+    }
+    debug!("[Binaries] Override for {} updated", kind.display_name());
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinaryReport {
+    pub found: bool,
+    pub path: Option<String>,
+    pub source: Option<String>,
+    pub version: Option<String>,
+    pub tried: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl BinaryReport {
+    fn from_result(result: Result<ResolvedBinary, BinaryNotFound>) -> Self {
+        match result {
+            Ok(b) => BinaryReport {
+                found: true,
+                path: Some(b.path.display().to_string()),
+                source: Some(b.source),
+                version: b.version,
+                tried: Vec::new(),
+                error: None,
+            },
+            Err(e) => BinaryReport {
+                found: false,
+                path: None,
+                source: None,
+                version: None,
+                tried: e.tried.iter().map(|p| p.display().to_string()).collect(),
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FfmpegInfo {
+    pub ffmpeg: BinaryReport,
+    pub ffprobe: BinaryReport,
+}
+
+fn current_ffmpeg_info() -> FfmpegInfo {
+    FfmpegInfo {
+        ffmpeg: BinaryReport::from_result(resolve_binary(BinaryKind::Ffmpeg)),
+        ffprobe: BinaryReport::from_result(resolve_binary(BinaryKind::Ffprobe)),
+    }
+}
+
+#[tauri::command]
+pub fn get_ffmpeg_info() -> Result<FfmpegInfo, String> {
+    Ok(current_ffmpeg_info())
+}
+
+/// 只修改传入的路径：未传（null）时保持原有设置，传空字符串时清除该项自定义路径
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn set_ffmpeg_paths(ffmpegPath: Option<String>, ffprobePath: Option<String>) -> Result<FfmpegInfo, String> {
+    for (kind, path) in [(BinaryKind::Ffmpeg, ffmpegPath), (BinaryKind::Ffprobe, ffprobePath)] {
+        if path.is_some() {
+            set_binary_override(kind, path)?;
+        }
+    }
+    Ok(current_ffmpeg_info())
+}
@@ -0,0 +1,418 @@
+use std::path::Path;
+use serde_json::Value;
+use crate::video::compression::merge_codec_params;
+use crate::video::quality::has_filter;
+use crate::video::types::{ColorInfo, CompressionSettings, ContentLight, MasteringDisplay};
+use crate::video::utils::tokio_command_with_no_window;
+
+// HDR 处理：探测源视频的色域、传输特性与母版元数据，
+//   preserve  输出带上相同的色彩标记，x265 / SVT-AV1 额外写入母版显示与内容亮度元数据
+//   tonemap   用 zscale + tonemap 转换为 BT.709 SDR（需要 FFmpeg 带 libzimg）
+// auto 时 10-bit 输出保留 HDR，8-bit 输出色调映射为 SDR。
+
+const TONEMAP_ALGORITHMS: [&str; 4] = ["hable", "mobius", "reinhard", "clip"];
+const DEFAULT_TONEMAP: &str = "hable";
+
+fn is_hdr_transfer(transfer: &str) -> bool {
+    matches!(transfer, "smpte2084" | "arib-std-b67")
+}
+
+// 解析 "34000/50000" 形式的分数，允许 0
+fn parse_fraction(value: &Value) -> Option<f64> {
+    let text = value.as_str()?;
+    match text.split_once('/') {
+        Some((num, den)) => {
+            let den = den.parse::<f64>().ok().filter(|d| *d != 0.0)?;
+            Some(num.parse::<f64>().ok()? / den)
+        }
+        None => text.parse::<f64>().ok(),
+    }
+}
+
+/// 从 side_data_list（流级或帧级）解析母版显示与内容亮度元数据
+fn parse_side_data(list: &Value) -> (Option<MasteringDisplay>, Option<ContentLight>) {
+    let Some(list) = list.as_array() else { return (None, None) };
+    let mut mastering = None;
+    let mut content_light = None;
+    for data in list {
+        match data["side_data_type"].as_str() {
+            Some("Mastering display metadata") => {
+                let xy = |name: &str| -> Option<[f64; 2]> {
+                    Some([parse_fraction(&data[format!("{}_x", name)])?, parse_fraction(&data[format!("{}_y", name)])?])
+                };
+                mastering = (|| {
+                    Some(MasteringDisplay {
+                        red: xy("red")?,
+                        green: xy("green")?,
+                        blue: xy("blue")?,
+                        white_point: xy("white_point")?,
+                        max_luminance: parse_fraction(&data["max_luminance"])?,
+                        min_luminance: parse_fraction(&data["min_luminance"])?,
+                    })
+                })();
+            }
+            Some("Content light level metadata") => {
+                content_light = Some(ContentLight {
+                    max_cll: data["max_content"].as_u64().unwrap_or(0) as u32,
+                    max_fall: data["max_average"].as_u64().unwrap_or(0) as u32,
+                });
+            }
+            _ => {}
+        }
+    }
+    (mastering, content_light)
+}
+
+/// 从 ffprobe JSON（-show_streams）解析主视频流的色彩信息
+pub fn parse_color_info(probe: &Value) -> Option<ColorInfo> {
+    let video = probe["streams"]
+        .as_array()?
+        .iter()
+        .find(|s| s["codec_type"].as_str() == Some("video") && s["disposition"]["attached_pic"].as_i64() != Some(1))?;
+    // "unknown" / "reserved" 等同于未标记
+    let tag = |name: &str| {
+        video[name]
+            .as_str()
+            .filter(|v| !matches!(*v, "" | "unknown" | "unspecified" | "reserved"))
+            .map(|v| v.to_string())
+    };
+    let (mastering_display, content_light) = parse_side_data(&video["side_data_list"]);
+    let transfer = tag("color_transfer");
+    Some(ColorInfo {
+        primaries: tag("color_primaries"),
+        is_hdr: transfer.as_deref().is_some_and(is_hdr_transfer),
+        transfer,
+        space: tag("color_space"),
+        range: tag("color_range"),
+        mastering_display,
+        content_light,
+    })
+}
+
+/// 部分 HEVC 文件只在帧的 SEI 中携带 HDR 元数据，读取第一帧补全
+pub(crate) async fn fill_frame_hdr_metadata(ffprobe_path: &Path, input_path: &str, color: &mut ColorInfo) {
+    if !color.is_hdr || (color.mastering_display.is_some() && color.content_light.is_some()) {
+        return;
+    }
+    let output = tokio_command_with_no_window(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-select_streams", "v:0",
+            "-read_intervals", "%+#1",
+            "-show_frames",
+            "-show_entries", "frame=side_data_list",
+            input_path,
+        ])
+        .output()
+        .await;
+    let Ok(output) = output else { return };
+    let Ok(json) = serde_json::from_slice::<Value>(&output.stdout) else { return };
+    let (mastering, content_light) = parse_side_data(&json["frames"][0]["side_data_list"]);
+    color.mastering_display = color.mastering_display.take().or(mastering);
+    color.content_light = color.content_light.take().or(content_light);
+}
+
+/// 探测源视频后决定的 HDR 处理方式，只用于本次压缩，不写回用户设置
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedHdr {
+    // "preserve" | "tonemap"
+    pub mode: &'static str,
+    pub tonemap: Option<String>,
+    pub source: ColorInfo,
+}
+
+/// 决定 HDR 源的处理方式（preserve / tonemap）；源不是 HDR 时返回 None
+pub(crate) fn resolve_hdr(
+    settings: &CompressionSettings,
+    source: Option<ColorInfo>,
+    ffmpeg_path: &Path,
+) -> Result<Option<ResolvedHdr>, String> {
+    let requested = settings.hdr.clone().unwrap_or_default();
+    let mode = if requested.mode.is_empty() { "auto" } else { requested.mode.as_str() };
+    if !matches!(mode, "auto" | "preserve" | "tonemap") {
+        return Err(format!("Unknown HDR mode: {}", mode));
+    }
+    if let Some(algorithm) = &requested.tonemap {
+        if !TONEMAP_ALGORITHMS.contains(&algorithm.as_str()) {
+            return Err(format!("Unknown tone mapping algorithm: {}", algorithm));
+        }
+    }
+    let Some(source) = source.filter(|c| c.is_hdr) else {
+        if mode != "auto" {
+            println!("Source is not HDR; HDR mode \"{}\" has no effect", mode);
+        }
+        return Ok(None);
+    };
+
+    let ten_bit = settings.bit_depth.is_some_and(|d| d >= 10);
+    let zscale = has_filter(ffmpeg_path, "zscale");
+    let resolved = match mode {
+        "tonemap" if !zscale => {
+            return Err("Tone mapping requires an FFmpeg build with the zscale filter (libzimg)".to_string());
+        }
+        "tonemap" => "tonemap",
+        "preserve" => "preserve",
+        _ if ten_bit => "preserve",
+        _ if zscale => "tonemap",
+        _ => {
+            println!("zscale is not available; keeping HDR color tags on 8-bit output");
+            "preserve"
+        }
+    };
+    println!(
+        "🌈 HDR source ({}), mode: {}",
+        source.transfer.as_deref().unwrap_or("unknown"),
+        resolved
+    );
+    Ok(Some(ResolvedHdr { mode: resolved, tonemap: requested.tonemap, source }))
+}
+
+fn resolved_hdr<'a>(hdr: Option<&'a ResolvedHdr>, mode: &str) -> Option<&'a ResolvedHdr> {
+    hdr.filter(|h| h.mode == mode)
+}
+
+/// 色调映射滤镜：线性化 → BT.709 色域 → tonemap → BT.709 传输特性，最后转换为输出像素格式
+pub(crate) fn tonemap_filters(hdr: Option<&ResolvedHdr>, pix_fmt: &str) -> Vec<String> {
+    let Some(hdr) = resolved_hdr(hdr, "tonemap") else { return Vec::new() };
+    let algorithm = hdr.tonemap.as_deref().unwrap_or(DEFAULT_TONEMAP);
+    vec![
+        "zscale=t=linear:npl=100".to_string(),
+        "format=gbrpf32le".to_string(),
+        "zscale=p=bt709".to_string(),
+        format!("tonemap=tonemap={}:desat=0", algorithm),
+        "zscale=t=bt709:m=bt709:r=tv".to_string(),
+        format!("format={}", pix_fmt),
+    ]
+}
+
+// x265 master-display：色度坐标单位 0.00002，亮度单位 0.0001 cd/m²
+fn x265_master_display(md: &MasteringDisplay) -> String {
+    let xy = |p: [f64; 2]| format!("({},{})", (p[0] * 50000.0).round(), (p[1] * 50000.0).round());
+    format!(
+        "G{}B{}R{}WP{}L({},{})",
+        xy(md.green),
+        xy(md.blue),
+        xy(md.red),
+        xy(md.white_point),
+        (md.max_luminance * 10000.0).round(),
+        (md.min_luminance * 10000.0).round()
+    )
+}
+
+// SVT-AV1 mastering-display：直接使用小数坐标与 cd/m²
+fn svtav1_master_display(md: &MasteringDisplay) -> String {
+    let xy = |p: [f64; 2]| format!("({:.4},{:.4})", p[0], p[1]);
+    format!(
+        "G{}B{}R{}WP{}L({:.4},{:.4})",
+        xy(md.green),
+        xy(md.blue),
+        xy(md.red),
+        xy(md.white_point),
+        md.max_luminance,
+        md.min_luminance
+    )
+}
+
+/// 输出色彩参数：色调映射时标记为 BT.709；保留 HDR 时沿用源的色彩标记并写入母版元数据
+pub(crate) fn color_args(settings: &CompressionSettings, hdr: Option<&ResolvedHdr>, ffmpeg_codec: &str, args: &mut Vec<String>) {
+    if resolved_hdr(hdr, "tonemap").is_some() {
+        for arg in ["-color_primaries", "bt709", "-color_trc", "bt709", "-colorspace", "bt709", "-color_range", "tv"] {
+            args.push(arg.to_string());
+        }
+        return;
+    }
+    let Some(source) = resolved_hdr(hdr, "preserve").map(|h| &h.source) else { return };
+    for (flag, value) in [
+        ("-color_primaries", &source.primaries),
+        ("-color_trc", &source.transfer),
+        ("-colorspace", &source.space),
+        ("-color_range", &source.range),
+    ] {
+        if let Some(value) = value {
+            args.push(flag.to_string());
+            args.push(value.clone());
+        }
+    }
+
+    let pq = source.transfer.as_deref() == Some("smpte2084");
+    match ffmpeg_codec {
+        "libx265" => {
+            let mut params: Vec<String> = Vec::new();
+            if pq && settings.bit_depth.is_some_and(|d| d >= 10) {
+                params.push("hdr10=1:hdr10-opt=1".to_string());
+            }
+            if let Some(md) = &source.mastering_display {
+                params.push(format!("master-display={}", x265_master_display(md)));
+            }
+            if let Some(cl) = &source.content_light {
+                params.push(format!("max-cll={},{}", cl.max_cll, cl.max_fall));
+            }
+            if !params.is_empty() {
+                merge_codec_params(args, "-x265-params", &params.join(":"));
+            }
+        }
+        "libsvtav1" => {
+            let mut params: Vec<String> = Vec::new();
+            if let Some(md) = &source.mastering_display {
+                params.push(format!("mastering-display={}", svtav1_master_display(md)));
+            }
+            if let Some(cl) = &source.content_light {
+                params.push(format!("content-light={},{}", cl.max_cll, cl.max_fall));
+            }
+            if !params.is_empty() {
+                params.insert(0, "enable-hdr=1".to_string());
+                merge_codec_params(args, "-svtav1-params", &params.join(":"));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // iPhone 录制的 HLG HEVC（Dolby Vision 8.4），ffprobe -show_streams 输出节选：没有母版元数据
+    fn iphone_hlg() -> Value {
+        json!({
+            "streams": [
+                {
+                    "index": 0, "codec_name": "hevc", "codec_type": "video", "profile": "Main 10",
+                    "width": 1920, "height": 1080, "pix_fmt": "yuv420p10le",
+                    "color_range": "tv", "color_space": "bt2020nc", "color_transfer": "arib-std-b67", "color_primaries": "bt2020",
+                    "disposition": { "default": 1, "attached_pic": 0 },
+                    "side_data_list": [
+                        {
+                            "side_data_type": "DOVI configuration record",
+                            "dv_version_major": 1, "dv_version_minor": 0, "dv_profile": 8, "dv_level": 4,
+                            "rpu_present_flag": 1, "el_present_flag": 0, "bl_present_flag": 1, "dv_bl_signal_compatibility_id": 4
+                        },
+                        { "side_data_type": "Display Matrix", "displaymatrix": "\n00000000:            0       65536           0\n", "rotation": -90 }
+                    ]
+                },
+                { "index": 1, "codec_name": "aac", "codec_type": "audio", "disposition": { "default": 1, "attached_pic": 0 } },
+                { "index": 2, "codec_type": "data", "codec_tag_string": "mebx", "disposition": { "default": 1, "attached_pic": 0 } }
+            ]
+        })
+    }
+
+    // HDR10（PQ）HEVC，流级 side_data_list 带母版显示（P3 D65，1000/0.005 cd/m²）与内容亮度
+    fn hdr10_pq() -> Value {
+        json!({
+            "streams": [
+                {
+                    "index": 0, "codec_name": "hevc", "codec_type": "video", "profile": "Main 10",
+                    "width": 3840, "height": 2160, "pix_fmt": "yuv420p10le",
+                    "color_range": "tv", "color_space": "bt2020nc", "color_transfer": "smpte2084", "color_primaries": "bt2020",
+                    "disposition": { "default": 1, "attached_pic": 0 },
+                    "side_data_list": [
+                        {
+                            "side_data_type": "Mastering display metadata",
+                            "red_x": "34000/50000", "red_y": "16000/50000",
+                            "green_x": "13250/50000", "green_y": "34500/50000",
+                            "blue_x": "7500/50000", "blue_y": "3000/50000",
+                            "white_point_x": "15635/50000", "white_point_y": "16450/50000",
+                            "min_luminance": "50/10000", "max_luminance": "10000000/10000"
+                        },
+                        { "side_data_type": "Content light level metadata", "max_content": 1000, "max_average": 400 }
+                    ]
+                }
+            ]
+        })
+    }
+
+    fn preserve(source: ColorInfo) -> ResolvedHdr {
+        ResolvedHdr { mode: "preserve", tonemap: None, source }
+    }
+
+    fn settings() -> CompressionSettings {
+        serde_json::from_value(json!({
+            "format": "mp4",
+            "codec": "H.265",
+            "resolution": "original",
+            "quality_type": "crf",
+            "bitDepth": 10,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_hlg_without_mastering_metadata() {
+        let color = parse_color_info(&iphone_hlg()).unwrap();
+        assert!(color.is_hdr);
+        assert_eq!(color.transfer.as_deref(), Some("arib-std-b67"));
+        assert_eq!(color.primaries.as_deref(), Some("bt2020"));
+        assert_eq!(color.space.as_deref(), Some("bt2020nc"));
+        assert_eq!(color.range.as_deref(), Some("tv"));
+        assert!(color.mastering_display.is_none());
+        assert!(color.content_light.is_none());
+
+        // HLG 不写 hdr10 标志，只沿用色彩标记
+        let mut args = Vec::new();
+        color_args(&settings(), Some(&preserve(color)), "libx265", &mut args);
+        assert_eq!(
+            args.join(" "),
+            "-color_primaries bt2020 -color_trc arib-std-b67 -colorspace bt2020nc -color_range tv"
+        );
+    }
+
+    #[test]
+    fn parses_pq_mastering_display_and_content_light() {
+        let color = parse_color_info(&hdr10_pq()).unwrap();
+        assert!(color.is_hdr);
+        let md = color.mastering_display.as_ref().unwrap();
+        assert_eq!(md.red, [0.68, 0.32]);
+        assert_eq!(md.white_point, [0.3127, 0.329]);
+        assert_eq!(md.max_luminance, 1000.0);
+        assert_eq!(md.min_luminance, 0.005);
+        assert_eq!(x265_master_display(md), "G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,50)");
+        assert_eq!(
+            svtav1_master_display(md),
+            "G(0.2650,0.6900)B(0.1500,0.0600)R(0.6800,0.3200)WP(0.3127,0.3290)L(1000.0000,0.0050)"
+        );
+
+        let hdr = preserve(color);
+        let mut args = Vec::new();
+        color_args(&settings(), Some(&hdr), "libx265", &mut args);
+        let params = &args[args.iter().position(|a| a == "-x265-params").unwrap() + 1];
+        assert_eq!(
+            params,
+            "hdr10=1:hdr10-opt=1:master-display=G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,50):max-cll=1000,400"
+        );
+
+        // 与两遍编码等已有的 x265-params 合并
+        let mut args = vec!["-x265-params".to_string(), "pass=1".to_string()];
+        color_args(&settings(), Some(&hdr), "libx265", &mut args);
+        assert!(args[1].starts_with("pass=1:hdr10=1:"));
+        assert!(args[1].ends_with(":max-cll=1000,400"));
+    }
+
+    #[test]
+    fn frame_side_data_and_incomplete_entries() {
+        // -show_frames 的帧级 side_data_list 与流级格式相同
+        let frames = json!({
+            "frames": [{
+                "side_data_list": [
+                    { "side_data_type": "Content light level metadata", "max_content": 0, "max_average": 0 },
+                    { "side_data_type": "Mastering display metadata", "red_x": "34000/50000" }
+                ]
+            }]
+        });
+        let (mastering, content_light) = parse_side_data(&frames["frames"][0]["side_data_list"]);
+        assert!(mastering.is_none());
+        let content_light = content_light.unwrap();
+        assert_eq!((content_light.max_cll, content_light.max_fall), (0, 0));
+        assert!(matches!(parse_side_data(&Value::Null), (None, None)));
+    }
+
+    #[test]
+    fn tonemap_marks_output_as_bt709() {
+        let hdr = ResolvedHdr { mode: "tonemap", tonemap: None, source: parse_color_info(&hdr10_pq()).unwrap() };
+        let mut args = Vec::new();
+        color_args(&settings(), Some(&hdr), "libx265", &mut args);
+        assert_eq!(args.join(" "), "-color_primaries bt709 -color_trc bt709 -colorspace bt709 -color_range tv");
+        assert_eq!(tonemap_filters(Some(&hdr), "yuv420p")[3], "tonemap=tonemap=hable:desat=0");
+    }
+}
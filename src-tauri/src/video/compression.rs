@@ -1,30 +1,47 @@
 use std::process::Stdio;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 use tokio::process::{Child};
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tauri::{Manager, Emitter};
-use crate::video::{CompressionSettings, CompressionResult, get_ffmpeg_binary, get_video_metadata};
-use crate::video::utils::get_ffprobe_binary;
-use crate::video::utils::get_hardware_encoder_support;
+use crate::video::events::{CompressionEvent, ProgressRangeSink, SharedEventSink, TauriEventSink};
+use crate::video::engine::{run_job, CompressionJob, JobKind, JobOptions};
+use crate::video::history::record_task_cancelled;
+use crate::video::queue::mark_job_cancelled;
+use crate::video::quality::{measure_quality, search_crf, CrfChoice, CRF_SEARCH_PROGRESS_SHARE, METRICS_PROGRESS_SHARE};
+use crate::video::loudness::{analyze_loudness, loudnorm_filter, mapped_audio_tracks, LOUDNORM_PROGRESS_SHARE};
+use crate::video::color::{color_args, fill_frame_hdr_metadata, parse_color_info, resolve_hdr, tonemap_filters, ResolvedHdr};
+use crate::video::encoder::{encoder_args, validate_encoder_settings};
+use crate::video::output::{validate_output, PartialOutput};
+use crate::video::metadata::{apply_file_times, metadata_args};
+use crate::video::oversize::{apply_oversize_policy, validate_oversize_policy, STATUS_COMPRESSED, STATUS_NO_GAIN};
+use crate::video::remux::{keyframe_at_or_before, should_stream_copy};
+use crate::video::filters::{post_scale_filters, pre_scale_filters, resolve_auto_crop, resolve_frame_rate, validate_video_filters};
+use crate::video::scale::{round_dimension, scale_filters, validate_scale, VIDEO_DIMENSION_MULTIPLE};
+use crate::video::streams::{build_stream_map_args, parse_stream_infos};
+use crate::video::subtitles::{extract_subtitles, resolve_subtitle_burn, subtitle_codec_args, subtitle_fits_container, SubtitleBurn};
+use crate::video::resume::{remove_temp_dir, should_segment, ResumeState, SEGMENT_SECONDS};
+use crate::video::{CompressionSettings, CompressionResult, LoudnessReport, get_video_metadata};
+use crate::video::binaries::{resolve_ffmpeg, resolve_ffprobe};
+use crate::video::utils::{get_hardware_encoder_support, HardwareSupport};
 use crate::video::utils::tokio_command_with_no_window;
-use serde_json::json;
 use tracing::{info, warn, debug};
-use std::path::Path; // for existence checks
+use std::path::{Path, PathBuf}; // for existence checks
 
 // 任务信息结构
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 struct TaskInfo {
-    input_path: String,
-    #[allow(dead_code)]
-    total_duration: f64,
-    app_handle: tauri::AppHandle,
-    output_path: String,
+    sink: SharedEventSink,
     #[allow(dead_code)]
     settings: CompressionSettings,
+    // 任务使用的临时目录（分段、passlog），删除任务时一并清理
+    temp_dirs: Vec<PathBuf>,
+    // 压缩流程的最终结果，resume_job 等待它而不是自行判断输出
+    finished: TaskOutcome,
 }
 
+type TaskOutcome = watch::Receiver<Option<Result<CompressionResult, String>>>;
+
 // 全局进程管理器
 static RUNNING_PROCESSES: std::sync::OnceLock<Arc<Mutex<HashMap<String, Child>>>> = std::sync::OnceLock::new();
 // 全局任务信息管理器
@@ -62,7 +79,6 @@ fn map_codec_to_ffmpeg(codec: &str) -> &str {
 }
 
 // 将前端音频编码器名称映射为FFmpeg编码器名称
-#[allow(dead_code)]
 fn map_audio_codec_to_ffmpeg(codec: &str) -> &str {
     match codec {
         "AAC" => "aac",
@@ -79,11 +95,171 @@ fn map_audio_codec_to_ffmpeg(codec: &str) -> &str {
     }
 }
 
+// 音频处理方式
+enum AudioMode {
+    Copy,
+    Drop,
+    Encode(String),
+}
+
+fn output_extension(output_path: &str) -> String {
+    Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default()
+}
+
+fn resolve_audio_mode(settings: &CompressionSettings, output_path: &str) -> AudioMode {
+    let codec = settings.audio.as_ref().and_then(|a| a.codec.as_deref()).filter(|c| !c.is_empty());
+    match codec {
+        Some(c) if c.eq_ignore_ascii_case("copy") => AudioMode::Copy,
+        Some(c) if c.eq_ignore_ascii_case("none") => AudioMode::Drop,
+        Some(c) => AudioMode::Encode(map_audio_codec_to_ffmpeg(c).to_string()),
+        // 默认：WebM 只能容纳 Opus/Vorbis，转为 Opus；其它容器直接复制，需要响度标准化时重新编码
+        None if output_extension(output_path) == "webm" => AudioMode::Encode("libopus".to_string()),
+        None if settings.loudnorm.is_some() => AudioMode::Encode(default_audio_encoder(output_path).to_string()),
+        None => AudioMode::Copy,
+    }
+}
+
+// 需要重新编码音频时各容器的默认编码器
+fn default_audio_encoder(output_path: &str) -> &'static str {
+    match output_extension(output_path).as_str() {
+        "webm" => "libopus",
+        "wmv" | "asf" => "wmav2",
+        _ => "aac",
+    }
+}
+
+// FFmpeg 编码器对应的码流格式名称（与 ffprobe 的 codec_name 一致）
+fn audio_stream_codec_name(encoder: &str) -> &str {
+    match encoder {
+        "libmp3lame" => "mp3",
+        "libvorbis" => "vorbis",
+        "libopus" => "opus",
+        "libopencore_amrnb" => "amr_nb",
+        "libfdk_aac" => "aac",
+        _ => encoder,
+    }
+}
+
+// 容器可以容纳的音频格式；None 表示不限制（如 MKV）
+fn container_audio_codecs(ext: &str) -> Option<&'static [&'static str]> {
+    match ext {
+        "mp4" | "m4v" => Some(&["aac", "mp3", "ac3", "eac3", "opus", "flac", "alac"]),
+        "mov" => Some(&["aac", "mp3", "ac3", "eac3", "alac", "flac", "pcm_s16le", "pcm_s24le"]),
+        "webm" => Some(&["opus", "vorbis"]),
+        "avi" => Some(&["mp3", "ac3", "aac", "pcm_s16le", "wmav2"]),
+        "flv" => Some(&["aac", "mp3"]),
+        "wmv" | "asf" => Some(&["wmav2", "mp3", "aac"]),
+        "3gp" => Some(&["aac", "amr_nb"]),
+        "ts" | "m2ts" | "mts" => Some(&["aac", "mp3", "ac3", "eac3", "dts", "opus"]),
+        _ => None,
+    }
+}
+
+// 源文件音频流的格式名称
+fn source_audio_codecs(probe: &serde_json::Value) -> Vec<String> {
+    probe["streams"]
+        .as_array()
+        .map(|streams| {
+            streams
+                .iter()
+                .filter(|s| s["codec_type"].as_str() == Some("audio"))
+                .filter_map(|s| s["codec_name"].as_str().map(|c| c.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 在运行 FFmpeg 之前检查音频设置与输出容器是否兼容
+fn validate_audio_settings(settings: &CompressionSettings, output_path: &str, probe: &serde_json::Value) -> Result<(), String> {
+    let ext = output_extension(output_path);
+    let audio = settings.audio.clone().unwrap_or_default();
+    let allowed = container_audio_codecs(&ext);
+    let mode = resolve_audio_mode(settings, output_path);
+    if settings.loudnorm.is_some() && !matches!(mode, AudioMode::Encode(_)) {
+        return Err("Loudness normalization requires re-encoding audio; audio codec cannot be copy or none".to_string());
+    }
+    match mode {
+        AudioMode::Drop => Ok(()),
+        AudioMode::Copy => {
+            if audio.bitrate.is_some() || audio.sample_rate.is_some() || audio.channels.is_some() {
+                return Err("Audio bitrate, sample rate and channels require re-encoding; choose an audio codec instead of copy".to_string());
+            }
+            let Some(allowed) = allowed else { return Ok(()) };
+            match source_audio_codecs(probe).into_iter().find(|c| !allowed.contains(&c.as_str())) {
+                Some(codec) => Err(format!(
+                    "Source audio ({}) cannot be copied into .{}; choose an audio codec or drop audio",
+                    codec, ext
+                )),
+                None => Ok(()),
+            }
+        }
+        AudioMode::Encode(encoder) => {
+            let codec_name = audio_stream_codec_name(&encoder);
+            if allowed.is_some_and(|allowed| !allowed.contains(&codec_name)) {
+                return Err(format!("Audio codec {} is not supported in .{} files", encoder, ext));
+            }
+            if audio.channels == Some(0) || audio.sample_rate == Some(0) {
+                return Err("Audio channels and sample rate must be greater than zero".to_string());
+            }
+            Ok(())
+        }
+    }
+}
+
+// 音频参数（位于 -i 之后、输出文件之前）
+fn build_audio_args(settings: &CompressionSettings, output_path: &str) -> Vec<String> {
+    let audio = settings.audio.clone().unwrap_or_default();
+    let mut args: Vec<String> = Vec::new();
+    match resolve_audio_mode(settings, output_path) {
+        AudioMode::Drop => args.push("-an".to_string()),
+        AudioMode::Copy => {
+            args.push("-c:a".to_string());
+            args.push("copy".to_string());
+        }
+        AudioMode::Encode(encoder) => {
+            // 无损/PCM 编码不使用码率参数
+            let lossless = matches!(encoder.as_str(), "flac" | "alac") || encoder.starts_with("pcm_");
+            // 未指定编码器（WebM 默认 Opus）时沿用 128k
+            let bitrate = audio.bitrate.clone().or_else(|| audio.codec.is_none().then(|| "128k".to_string()));
+            args.push("-c:a".to_string());
+            args.push(encoder);
+            if let Some(bitrate) = bitrate.filter(|_| !lossless) {
+                args.push("-b:a".to_string());
+                args.push(bitrate);
+            }
+            if let Some(rate) = audio.sample_rate {
+                args.push("-ar".to_string());
+                args.push(rate.to_string());
+            }
+            if let Some(channels) = audio.channels {
+                args.push("-ac".to_string());
+                args.push(channels.to_string());
+            }
+        }
+    }
+    args
+}
+
+// 解析码率字符串（"128k"、"2M"、"96000"）为 bps
+fn parse_bitrate_bps(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last()? {
+        'k' | 'K' => (&value[..value.len() - 1], 1_000.0),
+        'm' | 'M' => (&value[..value.len() - 1], 1_000_000.0),
+        _ => (value, 1.0),
+    };
+    number.trim().parse::<f64>().ok().map(|n| n * multiplier)
+}
+
 // 解析FFmpeg进度信息
 fn parse_ffmpeg_progress(line: &str, total_duration: f64) -> Option<f64> {
     // FFmpeg -progress 输出格式: 每个字段单独一行
     // out_time=00:15:58.610500
-    // 查找以 out_time= 开头的行
+    // 查找以 out_time= 开头的行（按输出时间计算，不依赖帧数，帧率转换与插帧不影响进度）
     if line.starts_with("out_time=") {
         if let Some(time_str) = line.strip_prefix("out_time=") {
             // 解析时间格式 HH:MM:SS.ss
@@ -114,208 +290,44 @@ pub async fn compress_video(
     settings: CompressionSettings,
     app_handle: tauri::AppHandle,
 ) -> Result<CompressionResult, String> {
-    // In development mode, use the bin directory in src-tauri
-    // In production, use the resource directory
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        // Development mode: use bin directory relative to src-tauri
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
+    // Windows 下 GPU 编码需要根据已检测的硬件编码器选择，其它情况无需检测
+    let hardware_support = if cfg!(target_os = "windows") && settings.hardware_acceleration.as_deref() == Some("gpu") {
+        get_hardware_encoder_support(app_handle.clone()).ok()
     } else {
-        // Production mode: prefer resource dir, then fallback to exe dir with both suffixed and unsuffixed names
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let mut candidates: Vec<std::path::PathBuf> = Vec::new();
-        candidates.push(resource_dir.join("bin").join(get_ffmpeg_binary()));
-        if let Ok(exe_path) = std::env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                candidates.push(exe_dir.join(get_ffmpeg_binary()));
-                #[cfg(target_os = "windows")]
-                {
-                    candidates.push(exe_dir.join("ffmpeg.exe"));
-                }
-                #[cfg(not(target_os = "windows"))]
-                {
-                    candidates.push(exe_dir.join("ffmpeg"));
-                }
-            }
-        }
-        candidates.into_iter().find(|p| p.exists()).unwrap_or_else(|| resource_dir.join("bin").join(get_ffmpeg_binary()))
+        None
     };
-    
-    println!("FFmpeg path: {:?}", ffmpeg_path);
-    
-    if !ffmpeg_path.exists() {
-        // Build helpful error message with tried paths
-        let mut tried: Vec<String> = Vec::new();
-        if cfg!(debug_assertions) {
-            let current_exe = std::env::current_exe().unwrap();
-            let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-            tried.push(src_tauri_dir.join("bin").join(get_ffmpeg_binary()).display().to_string());
-        } else {
-            let resource_dir = app_handle.path().resource_dir().unwrap();
-            tried.push(resource_dir.join("bin").join(get_ffmpeg_binary()).display().to_string());
-            if let Ok(exe_path) = std::env::current_exe() {
-                if let Some(exe_dir) = exe_path.parent() {
-                    tried.push(exe_dir.join(get_ffmpeg_binary()).display().to_string());
-                    #[cfg(target_os = "windows")]
-                    {
-                        tried.push(exe_dir.join("ffmpeg.exe").display().to_string());
-                    }
-                    #[cfg(not(target_os = "windows"))]
-                    {
-                        tried.push(exe_dir.join("ffmpeg").display().to_string());
-                    }
-                }
-            }
-        }
-        return Err(format!("FFmpeg binary not found. Tried: {}", tried.join(" | ")));
-    }
-    
-    let original_size = std::fs::metadata(&inputPath)
-        .map_err(|e| format!("Failed to get file size: {}", e))?
-        .len();
-    
-    // 使用ffprobe快速获取视频时长用于进度计算（使用打包的 ffprobe 二进制）
-    let ffprobe_path = if cfg!(debug_assertions) {
-        // Development mode: use bin directory relative to src-tauri
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffprobe_binary())
-    } else {
-        // Production mode: prefer resource dir, then fallback to exe dir with both suffixed and unsuffixed names
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let mut candidates: Vec<std::path::PathBuf> = Vec::new();
-        candidates.push(resource_dir.join("bin").join(get_ffprobe_binary()));
-        if let Ok(exe_path) = std::env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                candidates.push(exe_dir.join(get_ffprobe_binary()));
-                #[cfg(target_os = "windows")]
-                {
-                    candidates.push(exe_dir.join("ffprobe.exe"));
-                }
-                #[cfg(not(target_os = "windows"))]
-                {
-                    candidates.push(exe_dir.join("ffprobe"));
-                }
-            }
-        }
-        candidates.into_iter().find(|p| p.exists()).unwrap_or_else(|| resource_dir.join("bin").join(get_ffprobe_binary()))
-    };
-    
-    println!("FFprobe path: {:?}", ffprobe_path);
-    
-    if !ffprobe_path.exists() {
-        let mut tried: Vec<String> = Vec::new();
-        if cfg!(debug_assertions) {
-            let current_exe = std::env::current_exe().unwrap();
-            let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-            tried.push(src_tauri_dir.join("bin").join(get_ffprobe_binary()).display().to_string());
-        } else {
-            let resource_dir = app_handle.path().resource_dir().unwrap();
-            tried.push(resource_dir.join("bin").join(get_ffprobe_binary()).display().to_string());
-            if let Ok(exe_path) = std::env::current_exe() {
-                if let Some(exe_dir) = exe_path.parent() {
-                    tried.push(exe_dir.join(get_ffprobe_binary()).display().to_string());
-                    #[cfg(target_os = "windows")]
-                    {
-                        tried.push(exe_dir.join("ffprobe.exe").display().to_string());
-                    }
-                    #[cfg(not(target_os = "windows"))]
-                    {
-                        tried.push(exe_dir.join("ffprobe").display().to_string());
-                    }
-                }
-            }
-        }
-        return Err(format!("FFprobe binary not found. Tried: {}", tried.join(" | ")));
-    }
-    
-    let duration_cmd = tokio_command_with_no_window(&ffprobe_path)
-        .args([
-            "-v", "quiet",
-            "-print_format", "json",
-            "-show_format",
-            &inputPath
-        ])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to get video duration: {}", e))?;
-    
-    if !duration_cmd.status.success() {
-        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&duration_cmd.stderr)));
-    }
-    
-    let json_str = String::from_utf8(duration_cmd.stdout)
-        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
-    
-    let json_value: serde_json::Value = serde_json::from_str(&json_str)
-        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
-    
-    let total_duration = json_value["format"]["duration"]
-        .as_str()
-        .and_then(|d| d.parse::<f64>().ok())
-        .unwrap_or(0.0);
-    
-    println!("Video duration: {} seconds", total_duration);
-    
-    // 计算实际要压缩的时长（用于进度计算）
-    let actual_compression_duration = if let Some(time_range) = &settings.time_range {
-        if let Some(end) = time_range.end {
-            if let Some(start) = time_range.start {
-                let duration = end - start;
-                if duration > 0.0 {
-                    duration
-                } else {
-                    total_duration
-                }
-            } else {
-                // If only end time is specified, treat it as duration from start
-                end
-            }
-        } else {
-            total_duration
-        }
-    } else {
-        total_duration
+    let job = CompressionJob {
+        task_id: taskId,
+        kind: JobKind::Video,
+        input_path: inputPath,
+        output_path: outputPath,
+        settings,
     };
-    
-    println!("Actual compression duration: {} seconds", actual_compression_duration);
-    
-    let mut cmd = tokio_command_with_no_window(&ffmpeg_path);
-    let mut args_for_log: Vec<String> = Vec::new();
-    
-    // Add time range parameters if specified
-    if let Some(time_range) = &settings.time_range {
-        if let Some(start) = time_range.start {
-            cmd.arg("-ss").arg(start.to_string());
-            args_for_log.push("-ss".to_string());
-            args_for_log.push(start.to_string());
-        }
-    }
-    
-    cmd.arg("-i").arg(&inputPath);
-    args_for_log.push("-i".to_string());
-    args_for_log.push(inputPath.clone());
-    
-    // Add duration parameter if end time is specified
-    if let Some(time_range) = &settings.time_range {
-        if let Some(end) = time_range.end {
-            if let Some(start) = time_range.start {
-                let duration = end - start;
-                if duration > 0.0 {
-                    cmd.arg("-t").arg(duration.to_string());
-                    args_for_log.push("-t".to_string());
-                    args_for_log.push(duration.to_string());
-                }
-            } else {
-                // If only end time is specified, treat it as duration from start
-                cmd.arg("-t").arg(end.to_string());
-                args_for_log.push("-t".to_string());
-                args_for_log.push(end.to_string());
-            }
-        }
+    run_job(job, TauriEventSink::shared(&app_handle), JobOptions { hardware_support }).await
+}
+
+// 根据 time_range 计算输入起点与压缩时长（只有结束时间时视为从头开始的时长）
+fn time_range_window(settings: &CompressionSettings) -> (Option<f64>, Option<f64>) {
+    match &settings.time_range {
+        Some(time_range) => match (time_range.start, time_range.end) {
+            (Some(start), Some(end)) => (Some(start), if end - start > 0.0 { Some(end - start) } else { None }),
+            (None, Some(end)) => (None, Some(end)),
+            (start, None) => (start, None),
+        },
+        None => (None, None),
     }
-    
+}
+
+// 构建编码参数（位于 -i 之后、输出文件之前），返回 (FFmpeg 编码器名称, 参数)
+pub(crate) fn build_video_encode_args(
+    settings: &CompressionSettings,
+    hardware_support: Option<&HardwareSupport>,
+    output_path: &str,
+    subtitle_burn: Option<&SubtitleBurn>,
+    hdr: Option<&ResolvedHdr>,
+) -> (String, Vec<String>) {
+    let mut args: Vec<String> = Vec::new();
+
     // 添加调试日志
     debug!("Hardware acceleration setting: {:?}", settings.hardware_acceleration);
     info!("Video codec: {}", settings.codec);
@@ -362,7 +374,7 @@ pub async fn compress_video(
              };
              let mut selected: Option<String> = None;
              if !base.is_empty() {
-                 if let Ok(hs) = get_hardware_encoder_support(app_handle.clone()) {
+                 if let Some(hs) = hardware_support {
                      // 过滤出支持且与当前 codec 匹配的编码器，例如 h264_amf / h264_nvenc / h264_qsv
                       let candidates: Vec<_> = hs
                           .encoders
@@ -381,7 +393,7 @@ pub async fn compress_video(
                           selected = Some(best.name.clone());
                       }
                  } else {
-                        warn!("Hardware support detection failed; falling back to defaults");
+                        warn!("Hardware support info unavailable; falling back to defaults");
                  }
              }
              match selected {
@@ -405,15 +417,13 @@ pub async fn compress_video(
      };
      
     println!("Final FFmpeg codec: {}", ffmpeg_codec);
-    cmd.arg("-c:v").arg(&ffmpeg_codec);
-    args_for_log.push("-c:v".to_string());
-    args_for_log.push(ffmpeg_codec.clone());
+    args.push("-c:v".to_string());
+    args.push(ffmpeg_codec.clone());
     
     // Add H.265 specific tag for better compatibility
-    if ffmpeg_codec.contains("265") || ffmpeg_codec.contains("hevc") {
-        cmd.arg("-tag:v").arg("hvc1");
-        args_for_log.push("-tag:v".to_string());
-        args_for_log.push("hvc1".to_string());
+    if is_hevc_encoder(&ffmpeg_codec) {
+        args.push("-tag:v".to_string());
+        args.push("hvc1".to_string());
     }
     
     // Set pixel format based on bit depth
@@ -437,18 +447,22 @@ pub async fn compress_video(
         }
     };
     println!("Using pix_fmt: {}", pix_fmt);
-    cmd.arg("-pix_fmt").arg(pix_fmt);
-    args_for_log.push("-pix_fmt".to_string());
-    args_for_log.push(pix_fmt.to_string());
+    args.push("-pix_fmt".to_string());
+    args.push(pix_fmt.to_string());
 
-    // For hevc_videotoolbox, set main10 profile when requesting >=10-bit
-    if is_videotoolbox {
+    // HDR：保留色彩标记与母版元数据，或色调映射后标记为 BT.709
+    color_args(settings, hdr, &ffmpeg_codec, &mut args);
+    // 预设、tune、profile/level、GOP、B 帧与原始私有参数
+    encoder_args(settings, &ffmpeg_codec, &mut args);
+
+    // For hevc_videotoolbox, set main10 profile when requesting >=10-bit（未手动指定 profile 时）
+    let custom_profile = settings.encoder.as_ref().is_some_and(|e| e.profile.is_some());
+    if is_videotoolbox && !custom_profile {
         if let Some(depth) = settings.bit_depth {
             if depth >= 10 {
                 println!("Setting VideoToolbox profile to main10 for {}-bit request", depth);
-                cmd.arg("-profile:v").arg("main10");
-                args_for_log.push("-profile:v".to_string());
-                args_for_log.push("main10".to_string());
+                args.push("-profile:v".to_string());
+                args.push("main10".to_string());
             }
         }
     }
@@ -457,104 +471,185 @@ pub async fn compress_video(
     match settings.quality_type.as_str() {
         "crf" => {
             if let Some(crf) = settings.crf_value {
-                cmd.arg("-crf").arg(crf.to_string());
-                args_for_log.push("-crf".to_string());
-                args_for_log.push(crf.to_string());
+                args.push("-crf".to_string());
+                args.push(crf.to_string());
             }
         }
         "bitrate" => {
             if let Some(bitrate) = &settings.bitrate {
-                cmd.arg("-b:v").arg(bitrate);
-                args_for_log.push("-b:v".to_string());
-                args_for_log.push(bitrate.clone());
+                args.push("-b:v".to_string());
+                args.push(bitrate.clone());
             }
         }
         "qv" => {
             let q = settings.qv_value.unwrap_or(80).min(100);
-            cmd.arg("-q:v").arg(q.to_string());
-            args_for_log.push("-q:v".to_string());
-            args_for_log.push(q.to_string());
+            args.push("-q:v".to_string());
+            args.push(q.to_string());
         }
         _ => {}
     }
     
-    // Set resolution
-    let mut scale_filter = String::new();
-    if settings.resolution == "custom" {
+    // 滤镜链：去隔行 → 帧率（丢帧）→ 裁剪 → 降噪 → 旋转/翻转 → 缩放 → 色调映射 → 帧率（插帧）→ 锐化 → 字幕烧录 → 像素格式
+    let mut filters: Vec<String> = pre_scale_filters(settings);
+
+    // Set resolution（scale 设置优先，保持宽高比）
+    if let Some(scale) = &settings.scale {
+        filters.extend(scale_filters(scale, VIDEO_DIMENSION_MULTIPLE));
+    } else if settings.resolution == "custom" {
         if let Some(custom_res) = &settings.custom_resolution {
-            scale_filter = format!("scale={}:{}", custom_res.width, custom_res.height);
+            filters.push(format!(
+                "scale={}:{}",
+                round_dimension(custom_res.width, VIDEO_DIMENSION_MULTIPLE),
+                round_dimension(custom_res.height, VIDEO_DIMENSION_MULTIPLE)
+            ));
         }
     } else if settings.resolution != "original" {
-        scale_filter = format!("scale={}", settings.resolution.replace("x", ":"));
+        filters.push(format!("scale={}", settings.resolution.replace("x", ":")));
+    }
+
+    // 色调映射在缩放之后，按输出分辨率计算
+    filters.extend(tonemap_filters(hdr, pix_fmt));
+    filters.extend(post_scale_filters(settings));
+
+    // 文本字幕烧录在缩放之后，按输出分辨率渲染
+    if let Some(SubtitleBurn::Text { filter }) = subtitle_burn {
+        filters.push(filter.clone());
     }
 
     // Ensure 10-bit is preserved through filters when using VideoToolbox
     if is_videotoolbox {
         if let Some(depth) = settings.bit_depth {
             if depth >= 10 {
-                if scale_filter.is_empty() {
-                    scale_filter = "format=p010le".to_string();
-                } else {
-                    scale_filter = format!("{},format=p010le", scale_filter);
-                }
+                filters.push("format=p010le".to_string());
             }
         }
     }
-    
-    if !scale_filter.is_empty() {
-        println!("Using filter chain: {}", scale_filter);
-        cmd.arg("-vf").arg(scale_filter.clone());
-        args_for_log.push("-vf".to_string());
-        args_for_log.push(scale_filter);
+    let filter_chain = filters.join(",");
+
+    if let Some(SubtitleBurn::Bitmap { video_index, subtitle_index }) = subtitle_burn {
+        // 图形字幕按源分辨率叠加后再缩放，输出标签 [vout] 由流映射引用
+        let mut graph = format!("[0:{}][0:{}]overlay=eof_action=pass", video_index, subtitle_index);
+        if !filter_chain.is_empty() {
+            graph = format!("{},{}", graph, filter_chain);
+        }
+        graph.push_str("[vout]");
+        println!("Using filter graph: {}", graph);
+        args.push("-filter_complex".to_string());
+        args.push(graph);
+    } else if !filter_chain.is_empty() {
+        println!("Using filter chain: {}", filter_chain);
+        args.push("-vf".to_string());
+        args.push(filter_chain);
     }
     
-    // Set audio codec based on output format
-    if outputPath.to_lowercase().ends_with(".webm") {
-        // For WebM format, use specific audio encoding parameters
-        cmd.arg("-c:a").arg("libopus");
-        cmd.arg("-b:a").arg("128k");
-        cmd.arg("-c:s").arg("webvtt");
-        args_for_log.push("-c:a".to_string());
-        args_for_log.push("libopus".to_string());
-        args_for_log.push("-b:a".to_string());
-        args_for_log.push("128k".to_string());
-        args_for_log.push("-c:s".to_string());
-        args_for_log.push("webvtt".to_string());
+    args.extend(build_audio_args(settings, output_path));
+    args.extend(subtitle_codec_args(settings, output_path));
+
+    (ffmpeg_codec, args)
+}
+
+fn is_hevc_encoder(ffmpeg_codec: &str) -> bool {
+    ffmpeg_codec.contains("265") || ffmpeg_codec.contains("hevc")
+}
+
+// 输入相关参数：-ss 起点、-i 输入、-t 时长
+pub(crate) fn build_input_args(input_path: &str, start_time: Option<f64>, window: Option<f64>) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    // Add time range parameters if specified
+    if let Some(start) = start_time {
+        args.push("-ss".to_string());
+        args.push(start.to_string());
+    }
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+    // Add duration parameter if end time is specified
+    if let Some(duration) = window {
+        args.push("-t".to_string());
+        args.push(duration.to_string());
+    }
+    args
+}
+
+// 支持两遍编码的软件编码器
+fn supports_two_pass(ffmpeg_codec: &str) -> bool {
+    matches!(ffmpeg_codec, "libx264" | "libx265" | "libvpx-vp9" | "libsvtav1")
+}
+
+// x265-params 以 ':' 分隔，路径中的 '\\' 与 ':' 需要转义（Windows 盘符）
+fn escape_x265_param(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(':', "\\:")
+}
+
+/// 追加编码器私有参数（-x265-params / -svtav1-params 等）：已存在时以 ':' 合并，FFmpeg 只认最后一个同名选项
+pub(crate) fn merge_codec_params(args: &mut Vec<String>, flag: &str, params: &str) {
+    match args.iter().position(|a| a == flag) {
+        Some(pos) if pos + 1 < args.len() => {
+            args[pos + 1] = format!("{}:{}", args[pos + 1], params);
+        }
+        _ => {
+            args.push(flag.to_string());
+            args.push(params.to_string());
+        }
+    }
+}
+
+// 指定遍数的参数：libx265 通过 x265-params 传递（与已有参数合并），其它编码器使用 -pass/-passlogfile
+fn push_two_pass_args(args: &mut Vec<String>, ffmpeg_codec: &str, pass: u8, passlog_dir: &Path) {
+    if ffmpeg_codec == "libx265" {
+        let stats = passlog_dir.join("x265.log");
+        merge_codec_params(
+            args,
+            "-x265-params",
+            &format!("pass={}:stats={}", pass, escape_x265_param(&stats.to_string_lossy())),
+        );
     } else {
-        // For other formats, copy audio and subtitle streams
-        cmd.arg("-c:a").arg("copy");
-        cmd.arg("-c:s").arg("copy");
-        args_for_log.push("-c:a".to_string());
-        args_for_log.push("copy".to_string());
-        args_for_log.push("-c:s".to_string());
-        args_for_log.push("copy".to_string());
+        args.push("-pass".to_string());
+        args.push(pass.to_string());
+        args.push("-passlogfile".to_string());
+        args.push(passlog_dir.join("pass").to_string_lossy().to_string());
+    }
+}
+
+// 单次 FFmpeg 运行的结果
+pub(crate) struct FfmpegRun {
+    pub status: std::process::ExitStatus,
+    pub stderr: String,
+}
+
+/// 运行一次 FFmpeg（参数需包含 -progress pipe:1），并登记到进程管理器以支持暂停/删除。
+/// to_overall 把本次运行的进度百分比换算为整个任务的进度
+pub(crate) async fn run_ffmpeg_tracked<F>(
+    task_id: &str,
+    ffmpeg_path: &Path,
+    args: &[String],
+    run_duration: f64,
+    display_name: &str,
+    sink: &SharedEventSink,
+    to_overall: F,
+) -> Result<FfmpegRun, String>
+where
+    F: Fn(f64) -> f64 + Send + 'static,
+{
+    // 多次运行的任务（两遍、分段、样片等）在两次运行之间被删除时不再启动新进程
+    if !task_is_active(task_id).await {
+        return Err("Process was interrupted".to_string());
     }
-    
-    cmd.arg("-y").arg(&outputPath);
-    args_for_log.push("-y".to_string());
-    args_for_log.push(outputPath.clone());
-    
-    // 添加进度输出参数 - 输出到stdout
-    cmd.arg("-progress").arg("pipe:1");
-    args_for_log.push("-progress".to_string());
-    args_for_log.push("pipe:1".to_string());
-    
     // 发送最终命令事件到前端
-    let args_joined = args_for_log
+    let args_joined = args
         .iter()
         .map(|a| if a.contains(' ') { format!("\"{}\"", a) } else { a.clone() })
         .collect::<Vec<_>>()
         .join(" ");
-    let _ = app_handle.emit(&format!("compression-command-{}", taskId), json!({
-        "taskId": taskId,
-        "command": format!("{:?} {}", ffmpeg_path, args_joined),
-        "args": args_for_log,
-    }));
+    sink.emit(task_id, CompressionEvent::Command {
+        command: format!("{:?} {}", ffmpeg_path, args_joined),
+        args: args.to_vec(),
+    });
     
     println!("Executing FFmpeg command: {:?} {}", ffmpeg_path, args_joined);
     
     // 使用管道方式执行命令以实时监控进度
-    let mut child = cmd
+    let mut child = tokio_command_with_no_window(ffmpeg_path)
+        .args(args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
@@ -583,35 +678,13 @@ pub async fn compress_video(
     {
         let process_manager = get_process_manager();
         let mut processes = process_manager.lock().await;
-        processes.insert(taskId.clone(), child);
-    }
-    
-    // 保存任务信息用于恢复时重新建立进度监听
-    {
-        let task_info_manager = get_task_info_manager();
-        let mut task_infos = task_info_manager.lock().await;
-        task_infos.insert(taskId.clone(), TaskInfo {
-            input_path: inputPath.clone(),
-            total_duration: actual_compression_duration,
-            app_handle: app_handle.clone(),
-            output_path: outputPath.clone(),
-            settings: settings.clone(),
-        });
+        processes.insert(task_id.to_string(), child);
     }
     
     // 在后台线程中监控进度
-    let app_handle_clone = app_handle.clone();
-    let task_id_clone = taskId.clone();
-    let display_name = if cfg!(target_os = "windows") {
-        std::path::Path::new(&inputPath)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or(&taskId)
-            .to_string()
-    } else {
-        taskId.clone()
-    };
-    let display_name_clone = display_name.clone();
+    let sink_clone = sink.clone();
+    let task_id_clone = task_id.to_string();
+    let display_name_clone = display_name.to_string();
     
     let progress_handle = tokio::spawn(async move {
         let mut lines = reader.lines();
@@ -619,19 +692,11 @@ pub async fn compress_video(
         while let Some(line) = lines.next_line().await.unwrap_or(None) {
             // println!("FFmpeg stdout line: {}", line); // muted noisy stdout lines
             // 解析进度信息
-            if let Some(progress) = parse_ffmpeg_progress(&line, actual_compression_duration) {
+            if let Some(progress) = parse_ffmpeg_progress(&line, run_duration) {
+                let progress = to_overall(progress);
                 println!("✅ Parsed progress: {}% for {}", progress, display_name_clone);
                 // 发送进度事件到前端 - 使用任务特定的事件名称
-                let event_name = format!("compression-progress-{}", task_id_clone);
-                let emit_result = app_handle_clone.emit(&event_name, json!({
-                    "taskId": task_id_clone,
-                    "progress": progress
-                }));
-                if let Err(e) = emit_result {
-                    println!("❌ Failed to emit progress event: {}", e);
-                } else {
-                    println!("📡 Progress event emitted successfully: {}%", progress);
-                }
+                sink_clone.emit(&task_id_clone, CompressionEvent::Progress { progress });
             }
         }
         println!("🏁 Progress monitoring ended for task: {}", display_name_clone);
@@ -644,12 +709,12 @@ pub async fn compress_video(
             let process_manager = get_process_manager();
             let mut processes = process_manager.lock().await;
             
-            if let Some(child) = processes.get_mut(&taskId) {
+            if let Some(child) = processes.get_mut(task_id) {
                 // 检查进程是否已经完成
                 match child.try_wait() {
                     Ok(Some(status)) => {
                         // 进程已完成，从管理器中移除
-                        processes.remove(&taskId);
+                        processes.remove(task_id);
                         break status;
                     }
                     Ok(None) => {
@@ -660,16 +725,16 @@ pub async fn compress_video(
                     }
                     Err(e) => {
                         // 进程出错
-                        processes.remove(&taskId);
+                        processes.remove(task_id);
                         return Err(format!("Failed to check FFmpeg process status: {}", e));
                     }
                 }
             } else {
                 // 进程不在管理器中，说明被暂停或删除了
-                let _ = app_handle.emit(&format!("compression-error-{}", taskId), json!({
-                    "taskId": taskId,
-                    "error": "Process was interrupted"
-                }));
+                sink.emit(task_id, CompressionEvent::Error {
+                    error: "Process was interrupted".to_string(),
+                    stderr: None,
+                });
                 return Err("Process was interrupted".to_string());
             }
         }
@@ -681,19 +746,820 @@ pub async fn compress_video(
     let _ = stderr_handle.await;
     
     println!("FFmpeg exit status: {}", status);
+    let stderr = stderr_acc.lock().await.clone();
+    Ok(FfmpegRun { status, stderr })
+}
+
+// 任务是否仍然有效（delete_task 会移除任务信息）
+pub(crate) async fn task_is_active(task_id: &str) -> bool {
+    get_task_info_manager().lock().await.contains_key(task_id)
+}
+
+// 保存任务信息，用于暂停/恢复/删除时查找事件接收端与临时目录。
+// 任务开始时即登记，准备阶段（探测、黑边检测等）删除任务时 cancel_job 移除登记即可中止任务
+async fn register_task(task_id: &str, sink: &SharedEventSink, settings: &CompressionSettings, finished: TaskOutcome) {
+    let task_info_manager = get_task_info_manager();
+    let mut task_infos = task_info_manager.lock().await;
+    task_infos.insert(task_id.to_string(), TaskInfo {
+        sink: sink.clone(),
+        settings: settings.clone(),
+        temp_dirs: Vec::new(),
+        finished,
+    });
+}
+
+// 准备阶段的检查点：任务已被删除时中止，不再启动编码
+async fn ensure_task_active(task_id: &str) -> Result<(), String> {
+    if task_is_active(task_id).await {
+        return Ok(());
+    }
+    println!("Task {} was cancelled before encoding started", task_id);
+    Err("Process was interrupted".to_string())
+}
+
+// 记录任务当前使用的临时目录；任务已被删除时返回中断错误
+pub(crate) async fn track_temp_dirs(task_id: &str, dirs: Vec<PathBuf>) -> Result<(), String> {
+    let task_info_manager = get_task_info_manager();
+    let mut task_infos = task_info_manager.lock().await;
+    match task_infos.get_mut(task_id) {
+        Some(info) => {
+            info.temp_dirs = dirs;
+            Ok(())
+        }
+        None => Err("Process was interrupted".to_string()),
+    }
+}
+
+/// 两遍编码：第一遍只做码率分析（丢弃输出与音频），第二遍正式编码。
+/// 进度按两遍各占一半合并
+#[allow(clippy::too_many_arguments)]
+async fn run_two_pass_encode(
+    task_id: &str,
+    ffmpeg_path: &Path,
+    input_args: &[String],
+    encode_args: &[String],
+    output_args: &[String],
+    ffmpeg_codec: &str,
+    output_path: &str,
+    duration: f64,
+    passlog_dir: &Path,
+    display_name: &str,
+    sink: &SharedEventSink,
+) -> Result<FfmpegRun, String> {
+    println!("🎯 Two-pass encode with {} for {}", ffmpeg_codec, display_name);
+    let mut first: Vec<String> = input_args.to_vec();
+    first.extend(encode_args.iter().cloned());
+    push_two_pass_args(&mut first, ffmpeg_codec, 1, passlog_dir);
+    for arg in ["-an", "-sn", "-f", "null", "-y", "-", "-progress", "pipe:1"] {
+        first.push(arg.to_string());
+    }
+    let run = run_ffmpeg_tracked(task_id, ffmpeg_path, &first, duration, display_name, sink, |p| p / 2.0).await?;
+    if !run.status.success() {
+        return Ok(run);
+    }
+    // 两遍之间被删除的任务不再继续
+    if !task_is_active(task_id).await {
+        return Err("Process was interrupted".to_string());
+    }
+
+    let mut second: Vec<String> = input_args.to_vec();
+    second.extend(encode_args.iter().cloned());
+    push_two_pass_args(&mut second, ffmpeg_codec, 2, passlog_dir);
+    second.extend(output_args.iter().cloned());
+    for arg in ["-y", output_path, "-progress", "pipe:1"] {
+        second.push(arg.to_string());
+    }
+    run_ffmpeg_tracked(task_id, ffmpeg_path, &second, duration, display_name, sink, |p| 50.0 + p / 2.0).await
+}
+
+/// 分段编码：跳过已完成的分段，从中断处继续编码，全部完成后无损拼接为最终输出
+#[allow(clippy::too_many_arguments)]
+async fn run_segmented_encode(
+    task_id: &str,
+    ffmpeg_path: &Path,
+    input_path: &str,
+    output_path: &str,
+    start_time: Option<f64>,
+    total: f64,
+    ffmpeg_codec: &str,
+    encode_args: &[String],
+    // 拼接时附加的元数据参数（元数据来源为第二个输入，即源文件）
+    metadata: &[String],
+    state: &ResumeState,
+    display_name: &str,
+    sink: &SharedEventSink,
+) -> Result<FfmpegRun, String> {
+    let completed = state.completed_segments();
+    let done: f64 = completed.iter().map(|s| s.duration).sum();
+    let remaining = total - done;
+    if !completed.is_empty() {
+        println!(
+            "⏩ Resuming task {} at {:.1}s / {:.1}s ({} segments done)",
+            display_name, done, total, completed.len()
+        );
+        sink.emit(task_id, CompressionEvent::Progress { progress: (done / total * 100.0).min(100.0) });
+    }
+
+    // 剩余不足半秒视为已全部完成，直接拼接
+    if remaining > 0.5 {
+        let mut args: Vec<String> = Vec::new();
+        let seek = start_time.unwrap_or(0.0) + done;
+        if seek > 0.0 {
+            args.push("-ss".to_string());
+            args.push(seek.to_string());
+        }
+        args.push("-i".to_string());
+        args.push(input_path.to_string());
+        args.push("-t".to_string());
+        args.push(remaining.to_string());
+        args.extend(encode_args.iter().cloned());
+        // 固定间隔强制关键帧，保证分段边界一致
+        args.push("-force_key_frames".to_string());
+        args.push(format!("expr:gte(t,n_forced*{})", SEGMENT_SECONDS));
+        args.push("-f".to_string());
+        args.push("segment".to_string());
+        args.push("-segment_time".to_string());
+        args.push(SEGMENT_SECONDS.to_string());
+        args.push("-reset_timestamps".to_string());
+        args.push("1".to_string());
+        args.push("-segment_start_number".to_string());
+        args.push(completed.len().to_string());
+        args.push("-segment_list".to_string());
+        args.push(state.next_list_path().to_string_lossy().to_string());
+        args.push("-segment_list_type".to_string());
+        args.push("csv".to_string());
+        args.push("-y".to_string());
+        args.push(state.segment_pattern().to_string_lossy().to_string());
+        args.push("-progress".to_string());
+        args.push("pipe:1".to_string());
+
+        let run = run_ffmpeg_tracked(task_id, ffmpeg_path, &args, remaining, display_name, sink, move |p| {
+            ((done + p / 100.0 * remaining) / total * 100.0).min(100.0)
+        })
+        .await?;
+        if !run.status.success() {
+            return Ok(run);
+        }
+    }
+
+    let segments = state.completed_segments();
+    if segments.is_empty() {
+        return Err("Segmented encode produced no segments".to_string());
+    }
+    let concat_list = state.write_concat_list(&segments)?;
+    let mut args: Vec<String> = vec![
+        "-f".to_string(),
+        "concat".to_string(),
+        "-safe".to_string(),
+        "0".to_string(),
+        "-i".to_string(),
+        concat_list.to_string_lossy().to_string(),
+    ];
+    // 分段不带源文件的元数据与章节，拼接时从源文件（同样的时间范围）读取
+    if !metadata.is_empty() {
+        args.extend(build_input_args(input_path, start_time, Some(total)));
+    }
+    for arg in ["-map", "0", "-c", "copy"] {
+        args.push(arg.to_string());
+    }
+    args.extend(metadata.iter().cloned());
+    if is_hevc_encoder(ffmpeg_codec) {
+        args.push("-tag:v".to_string());
+        args.push("hvc1".to_string());
+    }
+    args.push("-y".to_string());
+    args.push(output_path.to_string());
+    args.push("-progress".to_string());
+    args.push("pipe:1".to_string());
+    println!("🔗 Concatenating {} segments into {}", segments.len(), output_path);
+    // 拼接很快，进度保持在 99%，完成后统一发送 100%
+    run_ffmpeg_tracked(task_id, ffmpeg_path, &args, total, display_name, sink, |_| 99.0).await
+}
+
+// 一次视频压缩任务的上下文，供多次编码尝试（目标大小重试等）复用
+pub(crate) struct VideoJob<'a> {
+    pub task_id: &'a str,
+    pub ffmpeg_path: &'a Path,
+    pub input_path: &'a str,
+    pub output_path: &'a str,
+    pub hardware_support: Option<&'a HardwareSupport>,
+    pub start_time: Option<f64>,
+    pub window: Option<f64>,
+    // 实际压缩时长（秒）
+    pub duration: f64,
+    pub display_name: String,
+    pub sink: &'a SharedEventSink,
+    // 编码时附加的音频滤镜参数（响度标准化第二遍，多音轨时按 -filter:a:N 分别设置）
+    pub audio_filters: Vec<String>,
+    // 流映射参数（-map / -disposition）
+    pub stream_map: Vec<String>,
+    // 字幕烧录
+    pub subtitle_burn: Option<SubtitleBurn>,
+    // 探测后决定的 HDR 处理方式，源不是 HDR 时为 None
+    pub hdr: Option<ResolvedHdr>,
+}
+
+impl<'a> VideoJob<'a> {
+    // 使用另一个事件接收端（例如把进度映射到某个阶段）
+    pub fn with_sink<'b>(&'b self, sink: &'b SharedEventSink) -> VideoJob<'b> {
+        VideoJob {
+            task_id: self.task_id,
+            ffmpeg_path: self.ffmpeg_path,
+            input_path: self.input_path,
+            output_path: self.output_path,
+            hardware_support: self.hardware_support,
+            start_time: self.start_time,
+            window: self.window,
+            duration: self.duration,
+            display_name: self.display_name.clone(),
+            sink,
+            audio_filters: self.audio_filters.clone(),
+            stream_map: self.stream_map.clone(),
+            subtitle_burn: self.subtitle_burn.clone(),
+            hdr: self.hdr.clone(),
+        }
+    }
+}
+
+/// 按给定参数完成一次编码：自动选择单遍、两遍或分段续传
+async fn encode_video(job: &VideoJob<'_>, settings: &CompressionSettings) -> Result<FfmpegRun, String> {
+    let (ffmpeg_codec, mut encode_args) = build_video_encode_args(settings, job.hardware_support, job.output_path, job.subtitle_burn.as_ref(), job.hdr.as_ref());
+    encode_args.splice(0..0, job.stream_map.iter().cloned());
+    encode_args.extend(job.audio_filters.iter().cloned());
+
+    // 码率模式的两遍编码，仅支持部分软件编码器
+    let two_pass = settings.quality_type == "bitrate" && settings.bitrate.is_some() && settings.two_pass == Some(true);
+    let two_pass = if two_pass && !supports_two_pass(&ffmpeg_codec) {
+        warn!("Two-pass encoding is not supported by {}, using a single pass", ffmpeg_codec);
+        false
+    } else {
+        two_pass
+    };
+    let passlog_dir = if two_pass {
+        let dir = std::env::temp_dir().join("compressgo-passlog").join(job.task_id);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create passlog dir {:?}: {}", dir, e))?;
+        Some(dir)
+    } else {
+        None
+    };
+
+    // 长视频分段编码，中断后可续传（两遍编码的统计数据无法从中途继续，不分段；
+    // 烧录字幕的时间平移基于任务起点，也不分段）
+    let resume = if passlog_dir.is_none() && job.subtitle_burn.is_none() && should_segment(settings, job.duration) {
+        match ResumeState::open(job.input_path, job.output_path, settings) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                warn!("Resumable encode unavailable, encoding in one pass: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    
+    // 登记临时目录，删除任务时清理；任务已被删除则不再继续
+    track_temp_dirs(job.task_id, resume.iter().map(|r| r.dir.clone()).chain(passlog_dir.clone()).collect()).await?;
+    
+    let run = match &resume {
+        Some(state) => {
+            run_segmented_encode(
+                job.task_id,
+                job.ffmpeg_path,
+                job.input_path,
+                job.output_path,
+                job.start_time,
+                job.duration,
+                &ffmpeg_codec,
+                &encode_args,
+                &metadata_args(settings, job.output_path, 1),
+                state,
+                &job.display_name,
+                job.sink,
+            )
+            .await?
+        }
+        None if passlog_dir.is_some() => {
+            let passlog = passlog_dir.as_deref().unwrap_or(Path::new("."));
+            let input_args = build_input_args(job.input_path, job.start_time, job.window);
+            let outcome = run_two_pass_encode(
+                job.task_id,
+                job.ffmpeg_path,
+                &input_args,
+                &encode_args,
+                &metadata_args(settings, job.output_path, 0),
+                &ffmpeg_codec,
+                job.output_path,
+                job.duration,
+                passlog,
+                &job.display_name,
+                job.sink,
+            )
+            .await;
+            remove_temp_dir(passlog);
+            outcome?
+        }
+        None => {
+            let mut args = build_input_args(job.input_path, job.start_time, job.window);
+            args.extend(encode_args.iter().cloned());
+            args.extend(metadata_args(settings, job.output_path, 0));
+            args.push("-y".to_string());
+            args.push(job.output_path.to_string());
+            // 添加进度输出参数 - 输出到stdout
+            args.push("-progress".to_string());
+            args.push("pipe:1".to_string());
+            run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, job.duration, &job.display_name, job.sink, |p| p).await?
+        }
+    };
+    if run.status.success() {
+        if let Some(state) = &resume {
+            state.remove();
+        }
+    }
+    Ok(run)
+}
+
+/// 无损转封装：复制视频流，音频与字幕仍按设置处理（复制或转换）
+async fn run_stream_copy(job: &VideoJob<'_>, settings: &CompressionSettings, source_codec: &str) -> Result<FfmpegRun, String> {
+    let mut args = build_input_args(job.input_path, job.start_time, job.window);
+    args.extend(job.stream_map.iter().cloned());
+    args.push("-c:v".to_string());
+    args.push("copy".to_string());
+    if source_codec == "hevc" && matches!(output_extension(job.output_path).as_str(), "mp4" | "m4v" | "mov") {
+        args.push("-tag:v".to_string());
+        args.push("hvc1".to_string());
+    }
+    args.extend(build_audio_args(settings, job.output_path));
+    args.extend(job.audio_filters.iter().cloned());
+    args.extend(subtitle_codec_args(settings, job.output_path));
+    args.extend(metadata_args(settings, job.output_path, 0));
+    // 关键帧起点之前的 B 帧时间戳可能为负，统一平移到 0
+    for arg in ["-avoid_negative_ts", "make_zero", "-y", job.output_path, "-progress", "pipe:1"] {
+        args.push(arg.to_string());
+    }
+    println!("📦 Copying video stream for {}", job.display_name);
+    run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, job.duration, &job.display_name, job.sink, |p| p).await
+}
+
+// 容器开销预留比例
+const TARGET_SIZE_OVERHEAD: f64 = 0.03;
+// 低于该视频码率时认为目标大小不可行
+const MIN_TARGET_VIDEO_KBPS: f64 = 32.0;
+// 输出超出目标大小时最多重试的次数
+const TARGET_SIZE_MAX_RETRIES: usize = 2;
+
+// 估算输出中音频的总码率（bps）：WebM 重新编码为 128k Opus，其余容器直接复制源音频
+fn estimate_audio_bitrate(probe: &serde_json::Value, settings: &CompressionSettings, output_path: &str) -> f64 {
+    let audio = settings.audio.clone().unwrap_or_default();
+    let mode = resolve_audio_mode(settings, output_path);
+    probe["streams"]
+        .as_array()
+        .map(|streams| {
+            streams
+                .iter()
+                .filter(|s| s["codec_type"].as_str() == Some("audio"))
+                .map(|s| {
+                    let source_bps = s["bit_rate"]
+                        .as_str()
+                        .and_then(|b| b.parse::<f64>().ok())
+                        .unwrap_or(128_000.0);
+                    match &mode {
+                        AudioMode::Drop => 0.0,
+                        AudioMode::Copy => source_bps,
+                        AudioMode::Encode(encoder) if encoder == "flac" || encoder == "alac" || encoder.starts_with("pcm_") => {
+                            // 无损：按 PCM 码率估算（FLAC/ALAC 通常更小，这里取上限）
+                            let rate = audio
+                                .sample_rate
+                                .map(|r| r as f64)
+                                .or_else(|| s["sample_rate"].as_str().and_then(|r| r.parse::<f64>().ok()))
+                                .unwrap_or(48_000.0);
+                            let channels = audio
+                                .channels
+                                .map(|c| c as f64)
+                                .or_else(|| s["channels"].as_f64())
+                                .unwrap_or(2.0);
+                            rate * channels * 16.0
+                        }
+                        AudioMode::Encode(_) => audio.bitrate.as_deref().and_then(parse_bitrate_bps).unwrap_or(128_000.0),
+                    }
+                })
+                .sum()
+        })
+        .unwrap_or(0.0)
+}
+
+// 输出的时长：起点之后的剩余时长，再受时间范围限制；源时长未知时返回 None
+fn output_duration(total_duration: f64, start_time: Option<f64>, window: Option<f64>) -> Option<f64> {
+    if total_duration <= 0.0 {
+        return None;
+    }
+    let remaining = (total_duration - start_time.unwrap_or(0.0)).max(0.0);
+    Some(window.map_or(remaining, |w| w.min(remaining)))
+}
+
+// 根据目标大小计算视频码率（kbps）：扣除容器开销与输出时长内的音频后，余下的全部留给视频
+fn target_video_kbps(target_bytes: u64, duration: f64, audio_bps: f64) -> Result<f64, String> {
+    if duration <= 0.0 {
+        return Err("Cannot compute a target bitrate: video duration is unknown".to_string());
+    }
+    let budget_bits = target_bytes as f64 * 8.0 * (1.0 - TARGET_SIZE_OVERHEAD);
+    let audio_bits = audio_bps * duration;
+    if audio_bits >= budget_bits {
+        return Err(format!(
+            "Target size {} bytes leaves no room for video: {:.1}s of audio at {:.0} kbps needs about {:.0} bytes",
+            target_bytes,
+            duration,
+            audio_bps / 1000.0,
+            (audio_bits / 8.0 / (1.0 - TARGET_SIZE_OVERHEAD)).ceil()
+        ));
+    }
+    let kbps = ((budget_bits - audio_bits) / duration / 1000.0).floor();
+    if kbps < MIN_TARGET_VIDEO_KBPS {
+        return Err(format!(
+            "Target size {} bytes is too small for {:.1}s of video ({:.0} kbps of audio)",
+            target_bytes,
+            duration,
+            audio_bps / 1000.0
+        ));
+    }
+    Ok(kbps)
+}
+
+// 输出超出目标大小时的下一次视频码率：按超出比例降低，并多留 5% 余量；低于下限时返回 None
+fn retry_video_kbps(kbps: f64, target_bytes: u64, output_bytes: u64) -> Option<f64> {
+    let next = (kbps * target_bytes as f64 / output_bytes as f64 * 0.95).floor();
+    (next >= MIN_TARGET_VIDEO_KBPS).then_some(next)
+}
+
+/// 目标大小模式：计算码率后两遍编码，输出仍超出目标时按比例降低码率重试。
+/// duration 为输出的实际时长（已按时间范围截取）。
+/// 返回最后一次编码结果，以及重试用尽后仍超出目标时的错误信息
+async fn run_target_size_encode(
+    job: &VideoJob<'_>,
+    settings: &CompressionSettings,
+    probe: &serde_json::Value,
+    duration: f64,
+) -> Result<(FfmpegRun, Option<String>), String> {
+    let target = settings
+        .target_size
+        .filter(|t| *t > 0)
+        .ok_or_else(|| "quality_type \"target_size\" requires target_size (bytes)".to_string())?;
+    let audio_bps = estimate_audio_bitrate(probe, settings, job.output_path);
+    let mut kbps = target_video_kbps(target, duration, audio_bps)?;
+
+    let mut attempt = 0;
+    loop {
+        println!(
+            "🎯 Target size {} bytes: attempt {} with video bitrate {}k (audio ~{:.0}k)",
+            target,
+            attempt + 1,
+            kbps,
+            audio_bps / 1000.0
+        );
+        let mut attempt_settings = settings.clone();
+        attempt_settings.quality_type = "bitrate".to_string();
+        attempt_settings.bitrate = Some(format!("{}k", kbps));
+        attempt_settings.two_pass = Some(true);
+        let run = encode_video(job, &attempt_settings).await?;
+        if !run.status.success() {
+            return Ok((run, None));
+        }
+
+        let size = std::fs::metadata(job.output_path).map(|m| m.len()).unwrap_or(0);
+        if size <= target {
+            println!("✅ Output {} bytes fits target {} bytes", size, target);
+            return Ok((run, None));
+        }
+        if attempt >= TARGET_SIZE_MAX_RETRIES {
+            return Ok((
+                run,
+                Some(format!(
+                    "Output is {} bytes, still above the target of {} bytes after {} attempts",
+                    size,
+                    target,
+                    attempt + 1
+                )),
+            ));
+        }
+        if !task_is_active(job.task_id).await {
+            return Err("Process was interrupted".to_string());
+        }
+        let Some(next) = retry_video_kbps(kbps, target, size) else {
+            return Ok((run, Some(format!("Output is {} bytes, cannot reach target of {} bytes", size, target))));
+        };
+        println!("⚠️ Output {} bytes exceeds target {} bytes, retrying with {}k", size, target, next);
+        kbps = next;
+        attempt += 1;
+    }
+}
+
+/// 视频压缩核心逻辑，不依赖 Tauri，可供命令行等其它入口复用
+pub async fn run_video_compression(
+    task_id: String,
+    input_path: String,
+    output_path: String,
+    settings: CompressionSettings,
+    hardware_support: Option<HardwareSupport>,
+    sink: SharedEventSink,
+) -> Result<CompressionResult, String> {
+    let (finished_tx, finished_rx) = watch::channel(None);
+    let outcome = compress_video_task(task_id, input_path, output_path, settings, hardware_support, sink, finished_rx).await;
+    // 通知等待中的 resume_job
+    finished_tx.send_replace(Some(outcome.clone()));
+    outcome
+}
+
+async fn compress_video_task(
+    task_id: String,
+    input_path: String,
+    output_path: String,
+    settings: CompressionSettings,
+    hardware_support: Option<HardwareSupport>,
+    sink: SharedEventSink,
+    finished: TaskOutcome,
+) -> Result<CompressionResult, String> {
+    register_task(&task_id, &sink, &settings, finished).await;
+    let ffmpeg_path = resolve_ffmpeg()?;
+    
+    println!("FFmpeg path: {:?}", ffmpeg_path);
+    
+    let original_size = std::fs::metadata(&input_path)
+        .map_err(|e| format!("Failed to get file size: {}", e))?
+        .len();
+    
+    // 使用ffprobe快速获取视频时长用于进度计算
+    let ffprobe_path = resolve_ffprobe()?;
+    
+    println!("FFprobe path: {:?}", ffprobe_path);
+    
+    let duration_cmd = tokio_command_with_no_window(&ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-show_format",
+            "-show_streams",
+            &input_path
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to get video duration: {}", e))?;
+    ensure_task_active(&task_id).await?;
+    
+    if !duration_cmd.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&duration_cmd.stderr)));
+    }
+    
+    let json_str = String::from_utf8(duration_cmd.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+    
+    let json_value: serde_json::Value = serde_json::from_str(&json_str)
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    
+    let total_duration = json_value["format"]["duration"]
+        .as_str()
+        .and_then(|d| d.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    
+    println!("Video duration: {} seconds", total_duration);
+    
+    // 计算实际要压缩的时长（用于进度计算）
+    let (start_time, window) = time_range_window(&settings);
+    let actual_compression_duration = window.unwrap_or(total_duration);
+    
+    println!("Actual compression duration: {} seconds", actual_compression_duration);
+    
+    // 流选择与字幕处理
+    let streams = parse_stream_infos(&json_value);
+    let source_video = streams.iter().find(|s| s.codec_type == "video" && !s.attached_pic);
+
+    if let Some(scale) = &settings.scale {
+        validate_scale(scale)?;
+    }
+
+    // 校验视频滤镜；自动裁剪先检测黑边并换成具体的裁剪参数
+    let mut settings = settings;
+    if let Some(filters) = settings.filters.as_mut() {
+        validate_video_filters(filters, source_video)?;
+        if filters.crop.as_ref().is_some_and(|c| c.mode == "auto") {
+            filters.crop = resolve_auto_crop(
+                &ffmpeg_path,
+                &input_path,
+                start_time.unwrap_or(0.0),
+                actual_compression_duration,
+                source_video,
+            )
+            .await?;
+            ensure_task_active(&task_id).await?;
+        }
+    }
+    // 帧率：cap 与可变帧率源在这里换成具体的输出帧率（或保持源帧率）
+    settings.frame_rate = resolve_frame_rate(settings.frame_rate.as_ref(), source_video, &output_path)?;
+    // HDR：补全帧级母版元数据后决定保留 HDR 还是色调映射为 SDR
+    let mut color = parse_color_info(&json_value);
+    if let Some(color) = color.as_mut() {
+        fill_frame_hdr_metadata(&ffprobe_path, &input_path, color).await;
+        ensure_task_active(&task_id).await?;
+    }
+    // 决定结果单独保存，不写回 settings（续传指纹与历史记录保留用户原始设置）
+    let hdr = resolve_hdr(&settings, color, &ffmpeg_path)?;
+    let subtitle_burn = resolve_subtitle_burn(&settings, &input_path, &streams, start_time)?;
+    // 无损转封装：复制视频流时起点只能落在关键帧上，前移起点并保持终点不变
+    let stream_copy = should_stream_copy(&settings, &json_value, source_video, &output_path, subtitle_burn.is_some(), hdr.as_ref())?;
+    let mut warnings: Vec<String> = Vec::new();
+    let (start_time, window, actual_compression_duration) = match start_time.filter(|s| stream_copy && *s > 0.0) {
+        Some(start) => {
+            let keyframe = keyframe_at_or_before(&ffprobe_path, &input_path, start).await.unwrap_or(start);
+            ensure_task_active(&task_id).await?;
+            let shift = start - keyframe;
+            if shift > 0.001 {
+                let message = format!(
+                    "Stream copy cuts on keyframes: output starts at {:.3}s instead of {:.3}s",
+                    keyframe, start
+                );
+                warn!("{}", message);
+                warnings.push(message);
+            }
+            (Some(keyframe), window.map(|w| w + shift), actual_compression_duration + shift)
+        }
+        None => (start_time, window, actual_compression_duration),
+    };
+    let processing_mode = Some(if stream_copy { "copy" } else { "encode" }.to_string());
+    let burn_output = matches!(subtitle_burn, Some(SubtitleBurn::Bitmap { .. })).then_some("[vout]");
+    let stream_map = build_stream_map_args(settings.streams.as_ref(), &streams, burn_output, |s| {
+        subtitle_fits_container(&settings, &output_path, s)
+    })?;
+
+    let display_name = if cfg!(target_os = "windows") {
+        std::path::Path::new(&input_path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or(&task_id)
+            .to_string()
+    } else {
+        task_id.clone()
+    };
+    let mut job = VideoJob {
+        task_id: &task_id,
+        ffmpeg_path: &ffmpeg_path,
+        input_path: &input_path,
+        output_path: &output_path,
+        hardware_support: hardware_support.as_ref(),
+        start_time,
+        window,
+        duration: actual_compression_duration,
+        display_name,
+        sink: &sink,
+        audio_filters: Vec::new(),
+        stream_map,
+        subtitle_burn,
+        hdr,
+    };
+
+    validate_audio_settings(&settings, &output_path, &json_value)?;
+    validate_oversize_policy(&settings)?;
+    if let Some(encoder) = &settings.encoder {
+        let (ffmpeg_codec, _) = build_video_encode_args(&settings, job.hardware_support, &output_path, None, job.hdr.as_ref());
+        validate_encoder_settings(encoder, &ffmpeg_codec)?;
+    }
+    ensure_task_active(&task_id).await?;
+
+    // 先写入同目录的临时文件，校验通过后再替换目标文件；失败或删除任务时只删除临时文件
+    let mut partial = PartialOutput::new(&output_path);
+    let partial_path = partial.path().to_string();
+    job.output_path = &partial_path;
+    // 输出应有的时长，用于校验
+    let expected_duration = output_duration(total_duration, start_time, window);
+
+    // 进度分配：[源响度分析][样片搜索][编码][输出响度测量][画质评估]
+    // 复制的视频流与源一致，不做画质评估
+    let measure = settings.measure_quality == Some(true) && !stream_copy;
+    let source_audio = json_value["streams"]
+        .as_array()
+        .and_then(|streams| streams.iter().find(|s| s["codec_type"].as_str() == Some("audio")))
+        .cloned();
+    // 响度标准化作用于输出中的每条音轨
+    let audio_tracks = mapped_audio_tracks(&job.stream_map, &streams);
+    let loudnorm = match (&settings.loudnorm, &source_audio) {
+        (Some(_), None) => {
+            warn!("Loudness normalization skipped: input has no audio stream");
+            None
+        }
+        (Some(_), Some(_)) if audio_tracks.is_empty() => {
+            warn!("Loudness normalization skipped: no audio stream is selected");
+            None
+        }
+        (loudnorm, _) => loudnorm.clone(),
+    };
+    let loudness_half = if loudnorm.is_some() { LOUDNORM_PROGRESS_SHARE / 2.0 } else { 0.0 };
+    let search_share = if settings.quality_type == "auto_crf" { CRF_SEARCH_PROGRESS_SHARE } else { 0.0 };
+    let metrics_share = if measure { METRICS_PROGRESS_SHARE } else { 0.0 };
+    let encode_start = loudness_half + search_share;
+    let encode_end = 100.0 - loudness_half - metrics_share;
+
+    // 响度标准化第一遍：逐条分析源音轨，每条音轨代入各自的测量值
+    let mut source_loudness = None;
+    if let Some(loudnorm) = &loudnorm {
+        let share = loudness_half / audio_tracks.len() as f64;
+        for (position, track) in audio_tracks.iter().enumerate() {
+            let analysis_sink = ProgressRangeSink::shared(sink.clone(), share * position as f64, share);
+            let map = track.map(|index| format!("0:{}", index));
+            let measured = analyze_loudness(&job.with_sink(&analysis_sink), &input_path, map.as_deref(), start_time, window, loudnorm).await?;
+            let source_rate = match track {
+                Some(index) => streams.iter().find(|s| s.index == *index).and_then(|s| s.sample_rate),
+                None => source_audio
+                    .as_ref()
+                    .and_then(|s| s["sample_rate"].as_str())
+                    .and_then(|r| r.parse::<u32>().ok()),
+            };
+            let filter = loudnorm_filter(&settings, &measured, source_rate);
+            let flag = match track {
+                Some(_) => format!("-filter:a:{}", position),
+                None => "-af".to_string(),
+            };
+            job.audio_filters.extend([flag, filter]);
+            if position == 0 {
+                source_loudness = Some(measured);
+            }
+        }
+    }
+
+    let encode_sink = ProgressRangeSink::shared(sink.clone(), encode_start, encode_end - encode_start);
+    let encode_job = job.with_sink(&encode_sink);
+
+    // 目标大小模式：按大小反推码率，超出时降低码率重试
+    let mut size_error: Option<String> = None;
+    let mut crf_choice: Option<CrfChoice> = None;
+    let run = if stream_copy {
+        let source_codec = source_video.map(|s| s.codec_name.as_str()).unwrap_or("");
+        run_stream_copy(&encode_job, &settings, source_codec).await?
+    } else if settings.quality_type == "target_size" {
+        // 只设起点或终点超出源时长时，job.duration 不是输出的实际时长
+        let duration = expected_duration.unwrap_or(job.duration);
+        let (run, error) = run_target_size_encode(&encode_job, &settings, &json_value, duration).await?;
+        size_error = error;
+        run
+    } else if settings.quality_type == "auto_crf" {
+        // 自动 CRF：先用样片搜索满足画质目标的最大 CRF，再正式编码
+        let search_sink = ProgressRangeSink::shared(sink.clone(), loudness_half, search_share);
+        let choice = search_crf(&job.with_sink(&search_sink), &settings).await?;
+        let mut final_settings = settings.clone();
+        final_settings.quality_type = "crf".to_string();
+        final_settings.crf_value = Some(choice.crf);
+        crf_choice = Some(choice);
+        encode_video(&encode_job, &final_settings).await?
+    } else {
+        encode_video(&encode_job, &settings).await?
+    };
+    let status = run.status;
 
     if status.success() {
-        let compressed_size = std::fs::metadata(&outputPath)
-            .map(|m| m.len())
-            .ok();
+        // 校验临时文件：能被探测且时长在误差范围内，否则视为失败（临时文件随 partial 删除）
+        if let Err(e) = validate_output(&ffprobe_path, &partial_path, expected_duration).await {
+            sink.emit(&task_id, CompressionEvent::Error {
+                error: e.clone(),
+                stderr: None,
+            });
+            return Ok(CompressionResult {
+                success: false,
+                output_path: None,
+                error: Some(e),
+                original_size,
+                processing_mode,
+                ..Default::default()
+            });
+        }
+        let encoded_size = std::fs::metadata(&partial_path).map(|m| m.len()).ok();
+
+        // 输出没有变小：按策略保留、丢弃或换成原文件（超出目标大小或复制视频流时不处理，保留输出）
+        let oversize = match size_error {
+            None if !stream_copy => apply_oversize_policy(
+                &task_id,
+                &settings,
+                &input_path,
+                &partial_path,
+                &output_path,
+                original_size,
+                encoded_size,
+                &sink,
+            )?,
+            _ => None,
+        };
+        let result_status = if oversize.is_some() { STATUS_NO_GAIN } else { STATUS_COMPRESSED };
+        // 保留编码结果时替换到目标路径；换成原文件时临时文件已删除，不再做输出相关的测量
+        let (output_path, compressed_size, keep_encoded) = match oversize {
+            Some(outcome) if !outcome.kept_encoded => (outcome.output_path, Some(outcome.size), false),
+            _ => {
+                (partial.commit(&output_path, settings.collision.as_deref())?, encoded_size, true)
+            }
+        };
+        job.output_path = &output_path;
         println!(
             "[COMPLETE] Output path = {:?}, exists? {} size={:?}",
-            outputPath,
-            Path::new(&outputPath).exists(),
+            output_path,
+            Path::new(&output_path).exists(),
             compressed_size
         );
         // 获取压缩后文件的元数据
-        let compressed_metadata = match get_video_metadata(app_handle.clone(), outputPath.clone()) {
+        let compressed_metadata = match get_video_metadata(output_path.clone()) {
             Ok(metadata) => Some(metadata),
             Err(e) => {
                 println!("Warning: Failed to get compressed video metadata: {}", e);
@@ -701,41 +1567,119 @@ pub async fn compress_video(
             }
         };
         
+        // 测量输出响度。失败不影响压缩结果，但删除任务会中止
+        let loudness = match (&loudnorm, source_loudness) {
+            (Some(loudnorm), Some(input)) if keep_encoded => {
+                let output_sink = ProgressRangeSink::shared(sink.clone(), encode_end, loudness_half);
+                let output = match analyze_loudness(&job.with_sink(&output_sink), &output_path, Some("0:a:0"), None, None, loudnorm).await {
+                    Ok(measured) => Some(measured),
+                    Err(e) if !task_is_active(&task_id).await => return Err(e),
+                    Err(e) => {
+                        warn!("Output loudness measurement skipped: {}", e);
+                        None
+                    }
+                };
+                Some(LoudnessReport {
+                    input_integrated: input.integrated,
+                    input_true_peak: input.true_peak,
+                    input_lra: input.lra,
+                    output_integrated: output.as_ref().map(|m| m.integrated),
+                    output_true_peak: output.as_ref().map(|m| m.true_peak),
+                    output_lra: output.as_ref().map(|m| m.lra),
+                })
+            }
+            _ => None,
+        };
+
+        // 导出字幕为独立文件。失败不影响压缩结果，但删除任务会中止
+        let subtitle_files = match settings.subtitles.as_ref().filter(|s| s.mode == "extract") {
+            Some(subtitles) => {
+                let extract_sink = ProgressRangeSink::shared(sink.clone(), encode_end, 0.0);
+                match extract_subtitles(&job.with_sink(&extract_sink), subtitles, &streams).await {
+                    Ok(files) => Some(files),
+                    Err(e) if !task_is_active(&task_id).await => return Err(e),
+                    Err(e) => {
+                        warn!("Subtitle extraction skipped: {}", e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        // 可选：与源视频比较画质。评估失败不影响压缩结果，但删除任务会中止
+        let quality_metrics = if measure && keep_encoded {
+            let metrics_sink = ProgressRangeSink::shared(sink.clone(), 100.0 - metrics_share, metrics_share);
+            match measure_quality(&job.with_sink(&metrics_sink), &settings, &output_path).await {
+                Ok(metrics) => Some(metrics),
+                Err(e) if !task_is_active(&task_id).await => return Err(e),
+                Err(e) => {
+                    warn!("Quality measurement skipped: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        
+        // 多次重试后仍超出目标大小：保留输出文件，但任务记为失败
+        if let Some(err_msg) = size_error {
+            sink.emit(&task_id, CompressionEvent::Error {
+                error: err_msg.clone(),
+                stderr: None,
+            });
+            return Ok(CompressionResult {
+                success: false,
+                output_path: Some(output_path),
+                error: Some(err_msg),
+                original_size,
+                compressed_size,
+                compressed_metadata,
+                quality_metrics,
+                loudness,
+                subtitle_files,
+                processing_mode,
+                warnings: (!warnings.is_empty()).then_some(warnings),
+                ..Default::default()
+            });
+        }
+        
+        // 最后设置文件时间，之后不再写入输出
+        apply_file_times(&settings, &input_path, &output_path);
+
         // 兜底：发送一次100%的进度事件 & 完成事件
-        let _ = app_handle.emit(&format!("compression-progress-{}", taskId), json!({
-            "taskId": taskId,
-            "progress": 100.0
-        }));
-        let completed_payload = json!({
-            "taskId": taskId,
-            "output_path": outputPath.clone(),
-            "original_size": original_size,
-            "compressed_size": compressed_size,
-            "compressed_metadata": compressed_metadata
+        sink.emit(&task_id, CompressionEvent::Progress { progress: 100.0 });
+        sink.emit(&task_id, CompressionEvent::Completed {
+            output_path: output_path.clone(),
+            original_size,
+            compressed_size,
+            compressed_metadata: compressed_metadata.clone().map(Box::new),
         });
-        let _ = app_handle.emit(&format!("compression-completed-{}", taskId), completed_payload.clone());
-        let _ = app_handle.emit(&format!("compression-finished-{}", taskId), completed_payload.clone());
             
         Ok(CompressionResult {
             success: true,
-            output_path: Some(outputPath),
+            output_path: Some(output_path),
             error: None,
             original_size,
             compressed_size,
             compressed_metadata,
+            chosen_crf: crf_choice.as_ref().map(|c| c.crf),
+            quality_metric: crf_choice.as_ref().map(|c| c.metric.clone()),
+            quality_score: crf_choice.as_ref().map(|c| c.score),
+            quality_metrics,
+            loudness,
+            subtitle_files,
+            processing_mode,
+            warnings: (!warnings.is_empty()).then_some(warnings),
+            status: Some(result_status.to_string()),
         })
     } else {
-        // 获取stderr详情
-        let stderr_text = {
-            let acc = stderr_acc.lock().await;
-            acc.clone()
-        };
+        // 获取stderr详情（分段目录保留，重新执行时可续传）
         let err_msg = format!("FFmpeg process failed with exit code: {}", status);
-        let _ = app_handle.emit(&format!("compression-error-{}", taskId), json!({
-            "taskId": taskId,
-            "error": err_msg,
-            "stderr": stderr_text
-        }));
+        sink.emit(&task_id, CompressionEvent::Error {
+            error: err_msg,
+            stderr: Some(run.stderr),
+        });
         Ok(CompressionResult {
             success: false,
             output_path: None,
@@ -743,6 +1687,8 @@ pub async fn compress_video(
             original_size,
             compressed_size: None,
             compressed_metadata: None,
+            processing_mode,
+            ..Default::default()
         })
     }
 }
@@ -817,15 +1763,28 @@ fn resume_process(pid: u32) -> Result<(), String> {
     Ok(())
 }
 
+// 读取任务登记的事件接收端
+async fn task_sink(task_id: &str) -> Option<SharedEventSink> {
+    let task_info_manager = get_task_info_manager();
+    let task_infos = task_info_manager.lock().await;
+    task_infos.get(task_id).map(|info| info.sink.clone())
+}
+
 #[allow(non_snake_case)]
 #[tauri::command]
-pub async fn pause_task(taskId: String, app_handle: tauri::AppHandle) -> Result<(), String> {
-    println!("Pausing task: {}", taskId);
+pub async fn pause_task(taskId: String) -> Result<(), String> {
+    pause_job(&taskId).await
+}
+
+/// 暂停正在运行的任务（SIGSTOP / SuspendThread）
+pub async fn pause_job(task_id: &str) -> Result<(), String> {
+    println!("Pausing task: {}", task_id);
+    let sink = task_sink(task_id).await;
     
     let process_manager = get_process_manager();
     let mut processes = process_manager.lock().await;
     
-    if let Some(child) = processes.get_mut(&taskId) {
+    if let Some(child) = processes.get_mut(task_id) {
         // 使用系统信号暂停进程 (SIGSTOP)
         if let Some(pid) = child.id() {
             #[cfg(unix)]
@@ -838,16 +1797,16 @@ pub async fn pause_task(taskId: String, app_handle: tauri::AppHandle) -> Result<
                 
                 match output {
                     Ok(result) if result.status.success() => {
-                        println!("Successfully paused task: {} (PID: {})", taskId, pid);
+                        println!("Successfully paused task: {} (PID: {})", task_id, pid);
                         // 广播暂停事件
-                        let _ = app_handle.emit(&format!("compression-paused-{}", taskId), json!({
-                            "taskId": taskId
-                        }));
+                        if let Some(sink) = &sink {
+                            sink.emit(task_id, CompressionEvent::Paused);
+                        }
                         Ok(())
                     }
                     Ok(result) => {
                         let error = String::from_utf8_lossy(&result.stderr);
-                        println!("Failed to pause task {}: {}", taskId, error);
+                        println!("Failed to pause task {}: {}", task_id, error);
                         Err(format!("Failed to pause task: {}", error))
                     }
                     Err(e) => {
@@ -860,14 +1819,14 @@ pub async fn pause_task(taskId: String, app_handle: tauri::AppHandle) -> Result<
             {
                 match suspend_process(pid) {
                     Ok(_) => {
-                        println!("Successfully suspended task: {} (PID: {})", taskId, pid);
-                        let _ = app_handle.emit(&format!("compression-paused-{}", taskId), json!({
-                            "taskId": taskId
-                        }));
+                        println!("Successfully suspended task: {} (PID: {})", task_id, pid);
+                        if let Some(sink) = &sink {
+                            sink.emit(task_id, CompressionEvent::Paused);
+                        }
                         Ok(())
                     }
                     Err(e) => {
-                        println!("Failed to suspend task {}: {}", taskId, e);
+                        println!("Failed to suspend task {}: {}", task_id, e);
                         Err(format!("Failed to pause task: {}", e))
                     }
                 }
@@ -880,24 +1839,31 @@ pub async fn pause_task(taskId: String, app_handle: tauri::AppHandle) -> Result<
             Err("Failed to get process ID".to_string())
         }
     } else {
-        println!("Task {} not found in running processes", taskId);
-        Err(format!("Task {} not found", taskId))
+        println!("Task {} not found in running processes", task_id);
+        Err(format!("Task {} not found", task_id))
     }
 }
 
 #[allow(non_snake_case)]
-#[allow(unused_variables)]
 #[tauri::command]
-pub async fn resume_task(
-    taskId: String,
-    app_handle: tauri::AppHandle,
-) -> Result<CompressionResult, String> {
-    println!("Resuming task: {}", taskId);
+pub async fn resume_task(taskId: String) -> Result<CompressionResult, String> {
+    resume_job(&taskId).await
+}
+
+/// 恢复已暂停的任务，并等待其完成。
+/// 这里只恢复进程；校验、替换临时文件与完成事件都由原来的 run_video_compression 流程负责，结果从那里取得
+pub async fn resume_job(task_id: &str) -> Result<CompressionResult, String> {
+    println!("Resuming task: {}", task_id);
+    let mut finished = match get_task_info_manager().lock().await.get(task_id) {
+        Some(info) => info.finished.clone(),
+        None => return Err(format!("Task {} not found", task_id)),
+    };
+    let sink = task_sink(task_id).await;
 
     let process_manager = get_process_manager();
     let processes = process_manager.lock().await;
 
-    if let Some(child) = processes.get(&taskId) {
+    if let Some(child) = processes.get(task_id) {
         // 使用系统信号恢复进程 (SIGCONT)
         if let Some(pid) = child.id() {
             #[cfg(unix)]
@@ -910,16 +1876,15 @@ pub async fn resume_task(
 
                 match output {
                     Ok(result) if result.status.success() => {
-                        println!("Successfully resumed task: {} (PID: {})", taskId, pid);
+                        println!("Successfully resumed task: {} (PID: {})", task_id, pid);
                         // 恢复事件
-                        let _ = app_handle.emit(&format!("compression-resumed-{}", taskId), json!({
-                            "taskId": taskId
-                        }));
-                        // 恢复后，我们需要像compress_video一样等待它完成
+                        if let Some(sink) = &sink {
+                            sink.emit(task_id, CompressionEvent::Resumed);
+                        }
                     }
                     Ok(result) => {
                         let error = String::from_utf8_lossy(&result.stderr);
-                        println!("Failed to resume task {}: {}", taskId, error);
+                        println!("Failed to resume task {}: {}", task_id, error);
                         return Err(format!("Failed to resume task: {}", error));
                     }
                     Err(e) => {
@@ -932,13 +1897,13 @@ pub async fn resume_task(
             {
                 match resume_process(pid) {
                     Ok(_) => {
-                        println!("Successfully resumed task: {} (PID: {})", taskId, pid);
-                        let _ = app_handle.emit(&format!("compression-resumed-{}", taskId), json!({
-                            "taskId": taskId
-                        }));
+                        println!("Successfully resumed task: {} (PID: {})", task_id, pid);
+                        if let Some(sink) = &sink {
+                            sink.emit(task_id, CompressionEvent::Resumed);
+                        }
                     }
                     Err(e) => {
-                        println!("Failed to resume task {}: {}", taskId, e);
+                        println!("Failed to resume task {}: {}", task_id, e);
                         return Err(format!("Failed to resume task: {}", e));
                     }
                 }
@@ -951,174 +1916,60 @@ pub async fn resume_task(
             return Err("Failed to get process ID".to_string());
         }
     } else {
-        println!("Task {} not found in running processes, it might have already finished or been deleted.", taskId);
-        // 如果任务已经不在运行列表中，可能已经完成或被删除。
-        // 我们可以检查任务信息是否存在来决定如何响应。
-        let task_info_manager = get_task_info_manager();
-        let task_infos = task_info_manager.lock().await;
-        if let Some(task_info) = task_infos.get(&taskId) {
-             // 任务信息还在，但进程不在了，说明可能已完成。
-             // 尝试返回一个表示成功的结果，让前端可以更新状态。
-             let original_size = std::fs::metadata(&task_info.input_path)
-                .map(|m| m.len())
-                .unwrap_or(0);
-             let compressed_size = std::fs::metadata(&task_info.output_path)
-                .map(|m| m.len())
-                .ok();
-             println!(
-                "[RESUME-NOT-RUNNING] Output path = {:?}, exists? {} size={:?}",
-                task_info.output_path,
-                Path::new(&task_info.output_path).exists(),
-                compressed_size
-             );
-             let compressed_metadata = get_video_metadata(task_info.app_handle.clone(), task_info.output_path.clone()).ok();
-
-             // 兜底：广播完成事件
-             let _ = task_info.app_handle.emit(&format!("compression-progress-{}", taskId), json!({
-                "taskId": taskId,
-                "progress": 100.0
-             }));
-             let completed_payload = json!({
-                "taskId": taskId,
-                "output_path": task_info.output_path.clone(),
-                "original_size": original_size,
-                "compressed_size": compressed_size,
-                "compressed_metadata": compressed_metadata
-             });
-             let _ = task_info.app_handle.emit(&format!("compression-completed-{}", taskId), completed_payload.clone());
-             let _ = task_info.app_handle.emit(&format!("compression-finished-{}", taskId), completed_payload.clone());
- 
-             return Ok(CompressionResult {
-                success: true,
-                output_path: Some(task_info.output_path.clone()),
-                error: None,
-                original_size,
-                compressed_size,
-                compressed_metadata,
-             });
-        } else {
-            // 进程和任务信息都不在了，返回错误。
-            return Err(format!("Task {} not found", taskId));
-        }
+        // 两次 FFmpeg 调用之间（校验、画质评估等）没有运行中的进程，直接等待流程结束
+        println!("Task {} has no running FFmpeg process, waiting for it to finish", task_id);
     }
-    
-    // 释放锁，因为下面的循环会需要它
     drop(processes);
 
-    // 等待进程完成或被中断
-    let status = {
-        loop {
-            let process_manager = get_process_manager();
-            let mut processes = process_manager.lock().await;
-
-            if let Some(child) = processes.get_mut(&taskId) {
-                match child.try_wait() {
-                    Ok(Some(status)) => {
-                        processes.remove(&taskId);
-                        break status;
-                    }
-                    Ok(None) => {
-                        drop(processes);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                        continue;
-                    }
-                    Err(e) => {
-                        processes.remove(&taskId);
-                        return Err(format!("Failed to check FFmpeg process status: {}", e));
-                    }
-                }
-            } else {
-                return Err("Process was interrupted".to_string());
-            }
-        }
-    };
-    
-    println!("Resumed FFmpeg exit status: {}", status);
-
-    // 获取任务信息以返回结果
-    let task_info_manager = get_task_info_manager();
-    let task_infos = task_info_manager.lock().await;
-    let task_info = task_infos.get(&taskId).ok_or("Task info not found after resume")?;
-
-    if status.success() {
-        let compressed_size = std::fs::metadata(&task_info.output_path)
-            .map(|m| m.len())
-            .ok();
-        
-        let compressed_metadata = match get_video_metadata(task_info.app_handle.clone(), task_info.output_path.clone()) {
-            Ok(metadata) => Some(metadata),
-            Err(e) => {
-                println!("Warning: Failed to get compressed video metadata: {}", e);
-                None
-            }
-        };
-        
-        // 兜底：广播完成事件
-        let _ = task_info.app_handle.emit(&format!("compression-progress-{}", taskId), json!({
-            "taskId": taskId,
-            "progress": 100.0
-        }));
-        let completed_payload = json!({
-            "taskId": taskId,
-            "output_path": task_info.output_path.clone(),
-            "original_size": std::fs::metadata(&task_info.input_path).map(|m| m.len()).unwrap_or(0),
-            "compressed_size": compressed_size,
-            "compressed_metadata": compressed_metadata
-        });
-        let _ = task_info.app_handle.emit(&format!("compression-completed-{}", taskId), completed_payload.clone());
-        let _ = task_info.app_handle.emit(&format!("compression-finished-{}", taskId), completed_payload.clone());
-            
-        Ok(CompressionResult {
-            success: true,
-            output_path: Some(task_info.output_path.clone()),
-            error: None,
-            original_size: std::fs::metadata(&task_info.input_path).map(|m| m.len()).unwrap_or(0),
-            compressed_size,
-            compressed_metadata,
-        })
-    } else {
-        Ok(CompressionResult {
-            success: false,
-            output_path: None,
-            error: Some(format!("FFmpeg process failed with exit code: {}", status)),
-            original_size: std::fs::metadata(&task_info.input_path).map(|m| m.len()).unwrap_or(0),
-            compressed_size: None,
-            compressed_metadata: None,
-        })
-    }
+    // 等待压缩流程结束（删除任务时流程返回 "Process was interrupted"）
+    let outcome = finished
+        .wait_for(|outcome| outcome.is_some())
+        .await
+        .map_err(|_| "Process was interrupted".to_string())?
+        .clone();
+    outcome.unwrap_or_else(|| Err("Process was interrupted".to_string()))
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
 pub async fn delete_task(taskId: String) -> Result<(), String> {
-  println!("Deleting task: {}", taskId);
+  // 若任务来自后端队列，先标记取消，避免被当作失败或再次调度
+  mark_job_cancelled(&taskId);
+  cancel_job(&taskId).await
+}
+
+/// 终止任务进程并移除任务信息
+pub async fn cancel_job(task_id: &str) -> Result<(), String> {
+  println!("Deleting task: {}", task_id);
+  // 先标记历史记录，避免进程被杀后被记为失败
+  record_task_cancelled(task_id);
 
   // 从运行中的进程管理器中移除并尝试强制终止进程
   let process_manager = get_process_manager();
   let mut processes = process_manager.lock().await;
 
-  if let Some(mut child) = processes.remove(&taskId) {
+  if let Some(mut child) = processes.remove(task_id) {
     // 进程存在，尝试强制终止
     #[cfg(unix)]
     {
       if let Err(e) = child.kill().await {
-        println!("Failed to kill task process {}: {}", taskId, e);
+        println!("Failed to kill task process {}: {}", task_id, e);
       } else {
-        println!("Successfully killed task process: {}", taskId);
+        println!("Successfully killed task process: {}", task_id);
       }
     }
     #[cfg(not(unix))]
     {
       if let Err(e) = child.kill().await {
-        println!("Failed to kill task process {} on this platform: {}", taskId, e);
+        println!("Failed to kill task process {} on this platform: {}", task_id, e);
       } else {
-        println!("Successfully killed task process: {}", taskId);
+        println!("Successfully killed task process: {}", task_id);
       }
     }
   } else {
     println!(
       "Task {} not found in running processes (it may have already finished, been paused, or removed).",
-      taskId
+      task_id
     );
   }
   drop(processes);
@@ -1126,8 +1977,12 @@ pub async fn delete_task(taskId: String) -> Result<(), String> {
   // 从任务信息管理器中删除记录，释放资源
   let task_info_manager = get_task_info_manager();
   let mut task_infos = task_info_manager.lock().await;
-  if task_infos.remove(&taskId).is_some() {
-    println!("Removed task info for {}", taskId);
+  if let Some(info) = task_infos.remove(task_id) {
+    println!("Removed task info for {}", task_id);
+    // 主动删除的任务不再续传，清理分段与 passlog 目录
+    for dir in &info.temp_dirs {
+      remove_temp_dir(dir);
+    }
   }
 
   Ok(())
@@ -1164,20 +2019,16 @@ pub async fn terminate_all_running_processes() {
         }
     }
     // 在清空任务信息之前，向前端发送取消事件
-    let snapshot: Vec<(String, tauri::AppHandle)> = {
+    let snapshot: Vec<(String, SharedEventSink)> = {
         let task_info_manager = get_task_info_manager();
         let task_infos = task_info_manager.lock().await;
         task_infos
             .iter()
-            .map(|(id, info)| (id.clone(), info.app_handle.clone()))
+            .map(|(id, info)| (id.clone(), info.sink.clone()))
             .collect()
     };
-    for (task_id, app_handle) in snapshot {
-        let event_name = format!("compression-cancelled-{}", task_id);
-        let _ = app_handle.emit(&event_name, json!({
-            "taskId": task_id,
-            "status": "cancelled"
-        }));
+    for (task_id, sink) in snapshot {
+        sink.emit(&task_id, CompressionEvent::Cancelled);
     }
     // 清空任务信息
     {
@@ -1193,4 +2044,86 @@ pub async fn terminate_all_running_processes() {
 pub async fn terminate_all_tasks() -> Result<(), String> {
     terminate_all_running_processes().await;
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(extra: serde_json::Value) -> CompressionSettings {
+        let mut base = json!({
+            "format": "mp4",
+            "codec": "H.264",
+            "resolution": "original",
+            "quality_type": "target_size",
+            "target_size": 10_000_000,
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            base[key] = value.clone();
+        }
+        serde_json::from_value(base).unwrap()
+    }
+
+    fn probe() -> serde_json::Value {
+        json!({
+            "format": { "duration": "120.000000" },
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "h264" },
+                { "index": 1, "codec_type": "audio", "codec_name": "aac", "bit_rate": "192000", "sample_rate": "44100", "channels": 2 },
+                { "index": 2, "codec_type": "audio", "codec_name": "aac" },
+            ]
+        })
+    }
+
+    #[test]
+    fn output_duration_follows_the_time_range() {
+        assert_eq!(output_duration(120.0, None, None), Some(120.0));
+        assert_eq!(output_duration(120.0, Some(30.0), None), Some(90.0));
+        assert_eq!(output_duration(120.0, Some(30.0), Some(20.0)), Some(20.0));
+        // 终点超出源时长
+        assert_eq!(output_duration(120.0, Some(100.0), Some(60.0)), Some(20.0));
+        assert_eq!(output_duration(120.0, Some(150.0), None), Some(0.0));
+        assert_eq!(output_duration(0.0, None, Some(20.0)), None);
+    }
+
+    #[test]
+    fn audio_estimate_per_mode() {
+        // 复制源音频：未标码率的音轨按 128k 估算
+        assert_eq!(estimate_audio_bitrate(&probe(), &settings(json!({})), "out.mp4"), 320_000.0);
+        assert_eq!(estimate_audio_bitrate(&probe(), &settings(json!({})), "out.webm"), 256_000.0);
+        let aac = settings(json!({ "audio": { "codec": "aac", "bitrate": "96k" } }));
+        assert_eq!(estimate_audio_bitrate(&probe(), &aac, "out.mp4"), 192_000.0);
+        let flac = settings(json!({ "audio": { "codec": "flac", "sample_rate": 48000 } }));
+        assert_eq!(estimate_audio_bitrate(&probe(), &flac, "out.mkv"), 2.0 * 48_000.0 * 2.0 * 16.0);
+        let none = settings(json!({ "audio": { "codec": "none" } }));
+        assert_eq!(estimate_audio_bitrate(&probe(), &none, "out.mp4"), 0.0);
+    }
+
+    #[test]
+    fn video_bitrate_subtracts_overhead_and_audio() {
+        // 10 MB × 8 × 0.97 = 77.6 Mbit，60 s 的 128k 音频占 7.68 Mbit
+        assert_eq!(target_video_kbps(10_000_000, 60.0, 128_000.0), Ok(1165.0));
+        assert_eq!(target_video_kbps(10_000_000, 60.0, 0.0), Ok(1293.0));
+        // 同样的目标，截取 20 s 时码率按截取后的时长计算
+        assert_eq!(target_video_kbps(10_000_000, 20.0, 128_000.0), Ok(3752.0));
+        assert!(target_video_kbps(10_000_000, 0.0, 128_000.0).is_err());
+    }
+
+    #[test]
+    fn rejects_targets_consumed_by_audio_and_overhead() {
+        // 60 s 的 128k 音频约需 989691 字节
+        let error = target_video_kbps(900_000, 60.0, 128_000.0).unwrap_err();
+        assert!(error.contains("leaves no room for video"), "{}", error);
+        assert!(error.contains("989691 bytes"), "{}", error);
+        // 有剩余但低于视频码率下限
+        let error = target_video_kbps(1_200_000, 60.0, 128_000.0).unwrap_err();
+        assert!(error.contains("too small"), "{}", error);
+    }
+
+    #[test]
+    fn retry_reduces_by_the_overshoot_with_margin() {
+        assert_eq!(retry_video_kbps(1000.0, 10_000_000, 12_500_000), Some(760.0));
+        assert_eq!(retry_video_kbps(1000.0, 10_000_000, 10_000_001), Some(949.0));
+        assert_eq!(retry_video_kbps(40.0, 10_000_000, 20_000_000), None);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::video::compression::merge_codec_params;
+use crate::video::types::{CompressionSettings, EncoderSettings};
+
+// 编码器高级参数：速度预设、tune、profile/level、关键帧间隔、B 帧与原始私有参数。
+// 各编码器支持的取值不同，在运行 FFmpeg 之前按实际选用的编码器校验。
+//   libx264/libx265  -preset ultrafast…placebo，-tune，-profile:v，level（x265 通过 level-idc）
+//   libsvtav1        -preset 0…13，tune vq/psnr/ssim 写入 svtav1-params
+//   libvpx-vp9       preset 0…8 对应 -cpu-used，profile 0…3
+//   *_nvenc          -preset p1…p7，-tune hq/ll/ull/lossless
+//   *_qsv            -preset veryfast…veryslow
+//   *_amf            preset speed/balanced/quality 对应 -quality
+//   *_videotoolbox   仅 profile、关键帧间隔与 B 帧
+
+const X26X_PRESETS: [&str; 10] = [
+    "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow", "placebo",
+];
+const QSV_PRESETS: [&str; 7] = ["veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow"];
+const MAX_BFRAMES: u32 = 16;
+const MAX_KEYFRAME_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Family {
+    X264,
+    X265,
+    SvtAv1,
+    Vp9,
+    Nvenc,
+    Qsv,
+    Amf,
+    VideoToolbox,
+    Other,
+}
+
+fn family(ffmpeg_codec: &str) -> Family {
+    match ffmpeg_codec {
+        "libx264" => Family::X264,
+        "libx265" => Family::X265,
+        "libsvtav1" => Family::SvtAv1,
+        "libvpx-vp9" => Family::Vp9,
+        c if c.ends_with("_nvenc") => Family::Nvenc,
+        c if c.ends_with("_qsv") => Family::Qsv,
+        c if c.ends_with("_amf") => Family::Amf,
+        c if c.contains("videotoolbox") => Family::VideoToolbox,
+        _ => Family::Other,
+    }
+}
+
+fn presets(family: Family) -> Vec<String> {
+    let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+    match family {
+        Family::X264 | Family::X265 => names(&X26X_PRESETS),
+        Family::SvtAv1 => (0..=13).map(|p| p.to_string()).collect(),
+        Family::Vp9 => (0..=8).map(|p| p.to_string()).collect(),
+        Family::Nvenc => (1..=7).map(|p| format!("p{}", p)).collect(),
+        Family::Qsv => names(&QSV_PRESETS),
+        Family::Amf => names(&["speed", "balanced", "quality"]),
+        Family::VideoToolbox | Family::Other => Vec::new(),
+    }
+}
+
+fn tunes(family: Family) -> &'static [&'static str] {
+    match family {
+        Family::X264 => &["film", "animation", "grain", "stillimage", "psnr", "ssim", "fastdecode", "zerolatency"],
+        Family::X265 => &["psnr", "ssim", "grain", "zerolatency", "fastdecode", "animation"],
+        Family::SvtAv1 => &["vq", "psnr", "ssim"],
+        Family::Nvenc => &["hq", "ll", "ull", "lossless"],
+        _ => &[],
+    }
+}
+
+fn profiles(family: Family, ffmpeg_codec: &str) -> &'static [&'static str] {
+    let hevc = ffmpeg_codec.contains("hevc");
+    match family {
+        Family::X264 => &["baseline", "main", "high", "high10", "high422", "high444"],
+        Family::X265 => &[
+            "main", "main10", "main12", "main422-10", "main422-12", "main444-8", "main444-10", "main444-12", "mainstillpicture",
+        ],
+        Family::SvtAv1 => &["main", "high", "professional"],
+        Family::Vp9 => &["0", "1", "2", "3"],
+        Family::Nvenc if hevc => &["main", "main10", "rext"],
+        Family::Nvenc if ffmpeg_codec.starts_with("av1") => &["main"],
+        Family::Nvenc => &["baseline", "main", "high", "high444p"],
+        Family::Qsv | Family::VideoToolbox if hevc => &["main", "main10"],
+        Family::Qsv | Family::VideoToolbox => &["baseline", "main", "high"],
+        Family::Amf if hevc => &["main"],
+        Family::Amf => &["main", "high", "constrained_baseline", "constrained_high"],
+        Family::Other => &[],
+    }
+}
+
+fn supports_level(family: Family) -> bool {
+    matches!(family, Family::X264 | Family::X265 | Family::Nvenc)
+}
+
+fn supports_bframes(family: Family) -> bool {
+    !matches!(family, Family::SvtAv1 | Family::Vp9 | Family::Other)
+}
+
+// level 形如 "4" / "4.1"，范围 1～6.2
+fn is_valid_level(level: &str) -> bool {
+    let mut parts = level.splitn(2, '.');
+    let major = parts.next().and_then(|m| m.parse::<u8>().ok());
+    let minor = match parts.next() {
+        Some(m) => m.parse::<u8>().ok(),
+        None => Some(0),
+    };
+    matches!((major, minor), (Some(1..=6), Some(0..=3)))
+}
+
+// 原始私有参数："key=value" 以 ':' 分隔，值中的 ':' 需写作 "\:"
+fn validate_raw_params(flag: &str, raw: &str) -> Result<(), String> {
+    let mut pending = String::new();
+    for part in raw.split(':') {
+        pending.push_str(part);
+        if part.ends_with('\\') {
+            pending.push(':');
+            continue;
+        }
+        let entry = std::mem::take(&mut pending);
+        match entry.split_once('=') {
+            Some((key, _)) if !key.trim().is_empty() => {}
+            _ => return Err(format!("Invalid {} entry \"{}\" (expected key=value)", flag, entry)),
+        }
+    }
+    // 末尾的 '\' 没有可转义的 ':'
+    if !pending.is_empty() {
+        return Err(format!("Invalid {} entry \"{}\" (dangling escape)", flag, pending.trim_end_matches(':')));
+    }
+    Ok(())
+}
+
+fn raw_params(encoder: &EncoderSettings) -> [(&'static str, Family, &Option<String>); 3] {
+    [
+        ("x264-params", Family::X264, &encoder.x264_params),
+        ("x265-params", Family::X265, &encoder.x265_params),
+        ("svtav1-params", Family::SvtAv1, &encoder.svtav1_params),
+    ]
+}
+
+/// 按实际选用的编码器校验高级参数
+pub(crate) fn validate_encoder_settings(encoder: &EncoderSettings, ffmpeg_codec: &str) -> Result<(), String> {
+    let family = family(ffmpeg_codec);
+    if let Some(preset) = &encoder.preset {
+        let allowed = presets(family);
+        if allowed.is_empty() {
+            return Err(format!("Encoder {} does not support presets", ffmpeg_codec));
+        }
+        if !allowed.contains(preset) {
+            return Err(format!("Invalid preset \"{}\" for {} (expected one of {})", preset, ffmpeg_codec, allowed.join(", ")));
+        }
+    }
+    for (name, value, allowed) in [
+        ("tune", &encoder.tune, tunes(family)),
+        ("profile", &encoder.profile, profiles(family, ffmpeg_codec)),
+    ] {
+        let Some(value) = value else { continue };
+        if allowed.is_empty() {
+            return Err(format!("Encoder {} does not support {}", ffmpeg_codec, name));
+        }
+        if !allowed.contains(&value.as_str()) {
+            return Err(format!("Invalid {} \"{}\" for {} (expected one of {})", name, value, ffmpeg_codec, allowed.join(", ")));
+        }
+    }
+    if let Some(level) = &encoder.level {
+        if !supports_level(family) {
+            return Err(format!("Encoder {} does not support setting the level", ffmpeg_codec));
+        }
+        if !is_valid_level(level) {
+            return Err(format!("Invalid level \"{}\" (expected e.g. 4.1)", level));
+        }
+    }
+    if let Some(bframes) = encoder.bframes {
+        if !supports_bframes(family) {
+            return Err(format!("Encoder {} does not support setting B-frames", ffmpeg_codec));
+        }
+        if bframes > MAX_BFRAMES {
+            return Err(format!("B-frames must be between 0 and {}", MAX_BFRAMES));
+        }
+    }
+    if let Some(interval) = encoder.keyframe_interval {
+        if interval == 0 || interval > MAX_KEYFRAME_INTERVAL {
+            return Err(format!("Keyframe interval must be between 1 and {} frames", MAX_KEYFRAME_INTERVAL));
+        }
+    }
+    for (flag, owner, value) in raw_params(encoder) {
+        let Some(value) = value.as_deref().filter(|v| !v.is_empty()) else { continue };
+        if family != owner {
+            return Err(format!("{} only applies to the matching encoder, not {}", flag, ffmpeg_codec));
+        }
+        validate_raw_params(flag, value)?;
+    }
+    Ok(())
+}
+
+/// 追加编码器高级参数（已通过校验），私有参数与 HDR、两遍编码的同名参数合并
+pub(crate) fn encoder_args(settings: &CompressionSettings, ffmpeg_codec: &str, args: &mut Vec<String>) {
+    let Some(encoder) = &settings.encoder else { return };
+    let family = family(ffmpeg_codec);
+    let mut push = |flag: &str, value: String| {
+        args.push(flag.to_string());
+        args.push(value);
+    };
+    if let Some(preset) = &encoder.preset {
+        match family {
+            Family::Vp9 => push("-cpu-used", preset.clone()),
+            Family::Amf => push("-quality", preset.clone()),
+            _ => push("-preset", preset.clone()),
+        }
+    }
+    if let Some(tune) = &encoder.tune {
+        if family != Family::SvtAv1 {
+            push("-tune", tune.clone());
+        }
+    }
+    if let Some(profile) = &encoder.profile {
+        push("-profile:v", profile.clone());
+    }
+    if let Some(level) = &encoder.level {
+        if family != Family::X265 {
+            push("-level", level.clone());
+        }
+    }
+    if let Some(bframes) = encoder.bframes {
+        push("-bf", bframes.to_string());
+    }
+    if let Some(interval) = encoder.keyframe_interval {
+        push("-g", interval.to_string());
+    }
+
+    match family {
+        Family::X264 => {
+            if let Some(raw) = encoder.x264_params.as_deref().filter(|v| !v.is_empty()) {
+                merge_codec_params(args, "-x264-params", raw);
+            }
+        }
+        Family::X265 => {
+            if let Some(level) = &encoder.level {
+                merge_codec_params(args, "-x265-params", &format!("level-idc={}", level));
+            }
+            if let Some(raw) = encoder.x265_params.as_deref().filter(|v| !v.is_empty()) {
+                merge_codec_params(args, "-x265-params", raw);
+            }
+        }
+        Family::SvtAv1 => {
+            if let Some(tune) = &encoder.tune {
+                let value = match tune.as_str() {
+                    "psnr" => 1,
+                    "ssim" => 2,
+                    _ => 0,
+                };
+                merge_codec_params(args, "-svtav1-params", &format!("tune={}", value));
+            }
+            if let Some(raw) = encoder.svtav1_params.as_deref().filter(|v| !v.is_empty()) {
+                merge_codec_params(args, "-svtav1-params", raw);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encoder(value: serde_json::Value) -> EncoderSettings {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn check(value: serde_json::Value, ffmpeg_codec: &str) -> Result<(), String> {
+        validate_encoder_settings(&encoder(value), ffmpeg_codec)
+    }
+
+    #[test]
+    fn presets_per_family() {
+        for (codec, valid, invalid) in [
+            ("libx264", "slow", "p4"),
+            ("libx265", "veryslow", "8"),
+            ("libsvtav1", "13", "14"),
+            ("libvpx-vp9", "8", "medium"),
+            ("h264_nvenc", "p7", "p8"),
+            ("hevc_qsv", "veryfast", "ultrafast"),
+            ("h264_amf", "balanced", "p1"),
+        ] {
+            assert!(check(json!({ "preset": valid }), codec).is_ok(), "{} {}", codec, valid);
+            assert!(check(json!({ "preset": invalid }), codec).is_err(), "{} {}", codec, invalid);
+        }
+        assert!(check(json!({ "preset": "medium" }), "h264_videotoolbox").is_err());
+    }
+
+    #[test]
+    fn tunes_per_family() {
+        for (codec, valid, invalid) in [
+            ("libx264", "film", "vq"),
+            ("libx265", "grain", "film"),
+            ("libsvtav1", "vq", "film"),
+            ("hevc_nvenc", "ll", "film"),
+        ] {
+            assert!(check(json!({ "tune": valid }), codec).is_ok(), "{} {}", codec, valid);
+            assert!(check(json!({ "tune": invalid }), codec).is_err(), "{} {}", codec, invalid);
+        }
+        for codec in ["libvpx-vp9", "h264_qsv", "hevc_amf", "hevc_videotoolbox"] {
+            assert!(check(json!({ "tune": "film" }), codec).is_err(), "{}", codec);
+        }
+    }
+
+    #[test]
+    fn profiles_per_family() {
+        for (codec, valid, invalid) in [
+            ("libx264", "high", "main10"),
+            ("libx265", "main10", "high"),
+            ("libsvtav1", "main", "main10"),
+            ("libvpx-vp9", "2", "main"),
+            ("h264_nvenc", "high444p", "main10"),
+            ("hevc_nvenc", "rext", "high"),
+            ("av1_nvenc", "main", "high"),
+            ("h264_qsv", "baseline", "main10"),
+            ("hevc_videotoolbox", "main10", "high"),
+            ("h264_amf", "constrained_high", "high10"),
+            ("hevc_amf", "main", "main10"),
+        ] {
+            assert!(check(json!({ "profile": valid }), codec).is_ok(), "{} {}", codec, valid);
+            assert!(check(json!({ "profile": invalid }), codec).is_err(), "{} {}", codec, invalid);
+        }
+    }
+
+    #[test]
+    fn levels() {
+        for level in ["1", "4", "4.1", "5.2", "6.2"] {
+            assert!(is_valid_level(level), "{}", level);
+        }
+        for level in ["0", "7", "4.4", "4.", ".1", "4.1.1", "high", ""] {
+            assert!(!is_valid_level(level), "{}", level);
+        }
+        for codec in ["libx264", "libx265", "h264_nvenc"] {
+            assert!(check(json!({ "level": "4.1" }), codec).is_ok(), "{}", codec);
+            assert!(check(json!({ "level": "7" }), codec).is_err(), "{}", codec);
+        }
+        for codec in ["libsvtav1", "libvpx-vp9", "h264_qsv", "h264_amf", "h264_videotoolbox"] {
+            assert!(check(json!({ "level": "4.1" }), codec).is_err(), "{}", codec);
+        }
+    }
+
+    #[test]
+    fn bframes_and_keyframe_interval() {
+        assert!(check(json!({ "bframes": 16, "keyframe_interval": 250 }), "libx264").is_ok());
+        assert!(check(json!({ "bframes": 17 }), "libx264").is_err());
+        assert!(check(json!({ "bframes": 2 }), "libsvtav1").is_err());
+        assert!(check(json!({ "keyframe_interval": 0 }), "libx264").is_err());
+        assert!(check(json!({ "keyframe_interval": 1001 }), "libx264").is_err());
+    }
+
+    #[test]
+    fn raw_params_only_on_the_matching_encoder() {
+        assert!(check(json!({ "x264_params": "ref=4:aq-mode=3" }), "libx264").is_ok());
+        assert!(check(json!({ "x265_params": "aq-mode=3" }), "libx265").is_ok());
+        assert!(check(json!({ "svtav1_params": "film-grain=8" }), "libsvtav1").is_ok());
+        assert!(check(json!({ "x264_params": "ref=4" }), "libx265").is_err());
+        assert!(check(json!({ "x265_params": "aq-mode=3" }), "hevc_nvenc").is_err());
+        assert!(check(json!({ "svtav1_params": "film-grain=8" }), "libx264").is_err());
+        // 空字符串视为未设置
+        assert!(check(json!({ "x264_params": "" }), "libx265").is_ok());
+    }
+
+    #[test]
+    fn raw_params_syntax() {
+        assert!(validate_raw_params("x265-params", "stats=C\\:/tmp/x265.log:pass=1").is_ok());
+        assert!(validate_raw_params("x265-params", "a=1\\:2\\:3").is_ok());
+        assert!(validate_raw_params("x265-params", "aq-mode=3:").is_err());
+        assert!(validate_raw_params("x265-params", "aq-mode").is_err());
+        assert!(validate_raw_params("x265-params", "=3").is_err());
+        assert!(validate_raw_params("x265-params", "a=1::b=2").is_err());
+        assert!(validate_raw_params("x265-params", "a=1\\").is_err());
+    }
+
+    #[test]
+    fn encoder_args_route_per_family() {
+        let settings = |codec: &str, enc: serde_json::Value| -> CompressionSettings {
+            serde_json::from_value(json!({
+                "format": "mp4",
+                "codec": codec,
+                "resolution": "original",
+                "quality_type": "crf",
+                "encoder": enc,
+            }))
+            .unwrap()
+        };
+        let args = |settings: &CompressionSettings, ffmpeg_codec: &str| {
+            let mut args = Vec::new();
+            encoder_args(settings, ffmpeg_codec, &mut args);
+            args.join(" ")
+        };
+
+        let x265 = settings("H.265", json!({ "preset": "slow", "level": "5.1", "x265_params": "aq-mode=3" }));
+        assert_eq!(args(&x265, "libx265"), "-preset slow -x265-params level-idc=5.1:aq-mode=3");
+        let x264 = settings("H.264", json!({ "level": "4.1", "bframes": 3, "x264_params": "ref=4" }));
+        assert_eq!(args(&x264, "libx264"), "-level 4.1 -bf 3 -x264-params ref=4");
+        let svt = settings("AV1", json!({ "preset": "6", "tune": "psnr", "keyframe_interval": 240 }));
+        assert_eq!(args(&svt, "libsvtav1"), "-preset 6 -g 240 -svtav1-params tune=1");
+        let vp9 = settings("VP9", json!({ "preset": "4", "profile": "2" }));
+        assert_eq!(args(&vp9, "libvpx-vp9"), "-cpu-used 4 -profile:v 2");
+        let amf = settings("H.264", json!({ "preset": "quality" }));
+        assert_eq!(args(&amf, "h264_amf"), "-quality quality");
+    }
+}
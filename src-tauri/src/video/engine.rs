@@ -0,0 +1,64 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::image::run_image_compression;
+use crate::video::compression::run_video_compression;
+use crate::video::events::SharedEventSink;
+use crate::video::history::{record_task_finished, record_task_started};
+use crate::video::types::{CompressionResult, CompressionSettings};
+use crate::video::utils::HardwareSupport;
+
+// 纯 Rust 的任务接口：Tauri 命令、命令行以及其它嵌入方都通过这里驱动压缩。
+// 任务控制见 compression.rs 中的 pause_job / resume_job / cancel_job / terminate_all_running_processes。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Video,
+    Image,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionJob {
+    #[serde(rename = "taskId")]
+    pub task_id: String,
+    pub kind: JobKind,
+    #[serde(rename = "inputPath")]
+    pub input_path: String,
+    // 使用输出命名（OutputNaming）时可以为空，由后端生成
+    #[serde(rename = "outputPath", default)]
+    pub output_path: String,
+    pub settings: CompressionSettings,
+}
+
+#[derive(Default, Clone)]
+pub struct JobOptions {
+    // Windows GPU 编码时用于选择可用的硬件编码器；为空时回退到软件编码
+    pub hardware_support: Option<HardwareSupport>,
+}
+
+/// 运行一个压缩任务，事件通过 sink 发出，开始与结束都会写入任务历史
+pub async fn run_job(job: CompressionJob, sink: SharedEventSink, options: JobOptions) -> Result<CompressionResult, String> {
+    record_task_started(&job);
+    let task_id = job.task_id.clone();
+    let outcome = match (ensure_output_dir(&job.output_path), job.kind) {
+        (Err(e), _) => Err(e),
+        (Ok(()), JobKind::Video) => {
+            run_video_compression(job.task_id, job.input_path, job.output_path, job.settings, options.hardware_support, sink).await
+        }
+        (Ok(()), JobKind::Image) => run_image_compression(job.task_id, job.input_path, job.output_path, job.settings, sink).await,
+    };
+    record_task_finished(&task_id, &outcome);
+    outcome
+}
+
+// 输出目录不存在时创建（还原子目录结构时目录通常尚不存在）
+fn ensure_output_dir(output_path: &str) -> Result<(), String> {
+    if output_path.is_empty() {
+        return Err("Output path is empty".to_string());
+    }
+    match Path::new(output_path).parent().filter(|dir| !dir.as_os_str().is_empty() && !dir.exists()) {
+        Some(dir) => std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create output directory {}: {}", dir.display(), e)),
+        None => Ok(()),
+    }
+}
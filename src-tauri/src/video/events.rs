@@ -0,0 +1,325 @@
+use std::sync::{Arc, Mutex};
+use serde_json::{json, Value};
+use tauri::Emitter;
+use crate::video::queue::QueueSnapshot;
+use crate::video::types::VideoMetadata;
+
+// 压缩过程中产生的事件。事件名与负载保持与前端约定一致：
+// compression-<name>-<taskId>，负载均包含 taskId
+#[derive(Debug, Clone)]
+pub enum CompressionEvent {
+    Command {
+        command: String,
+        args: Vec<String>,
+    },
+    Progress {
+        progress: f64,
+    },
+    Completed {
+        output_path: String,
+        original_size: u64,
+        compressed_size: Option<u64>,
+        compressed_metadata: Option<Box<VideoMetadata>>,
+    },
+    // 输出不小于原文件，按 oversize_policy 处理后的文件
+    NoGain {
+        output_path: String,
+        original_size: u64,
+        compressed_size: u64,
+        policy: String,
+    },
+    Error {
+        error: String,
+        stderr: Option<String>,
+    },
+    Paused,
+    Resumed,
+    Cancelled,
+}
+
+impl CompressionEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionEvent::Command { .. } => "compression-command",
+            CompressionEvent::Progress { .. } => "compression-progress",
+            CompressionEvent::Completed { .. } => "compression-completed",
+            CompressionEvent::NoGain { .. } => "compression-no-gain",
+            CompressionEvent::Error { .. } => "compression-error",
+            CompressionEvent::Paused => "compression-paused",
+            CompressionEvent::Resumed => "compression-resumed",
+            CompressionEvent::Cancelled => "compression-cancelled",
+        }
+    }
+
+    pub fn payload(&self, task_id: &str) -> Value {
+        match self {
+            CompressionEvent::Command { command, args } => json!({
+                "taskId": task_id,
+                "command": command,
+                "args": args,
+            }),
+            CompressionEvent::Progress { progress } => json!({
+                "taskId": task_id,
+                "progress": progress
+            }),
+            CompressionEvent::Completed { output_path, original_size, compressed_size, compressed_metadata } => json!({
+                "taskId": task_id,
+                "output_path": output_path,
+                "original_size": original_size,
+                "compressed_size": compressed_size,
+                "compressed_metadata": compressed_metadata
+            }),
+            CompressionEvent::NoGain { output_path, original_size, compressed_size, policy } => json!({
+                "taskId": task_id,
+                "output_path": output_path,
+                "original_size": original_size,
+                "compressed_size": compressed_size,
+                "policy": policy
+            }),
+            CompressionEvent::Error { error, stderr } => {
+                let mut payload = json!({
+                    "taskId": task_id,
+                    "error": error
+                });
+                if let Some(stderr) = stderr {
+                    payload["stderr"] = json!(stderr);
+                }
+                payload
+            }
+            CompressionEvent::Paused | CompressionEvent::Resumed => json!({
+                "taskId": task_id
+            }),
+            CompressionEvent::Cancelled => json!({
+                "taskId": task_id,
+                "status": "cancelled"
+            }),
+        }
+    }
+}
+
+/// 事件接收端：Tauri 下转发到 webview，命令行或嵌入场景可自定义
+pub trait EventSink: Send + Sync {
+    fn emit(&self, task_id: &str, event: CompressionEvent);
+
+    // 队列状态变化（入队、开始、结束、重排等），默认忽略
+    fn emit_queue_state(&self, _snapshot: &QueueSnapshot) {}
+}
+
+pub type SharedEventSink = Arc<dyn EventSink>;
+
+// 转发到 Tauri webview
+pub struct TauriEventSink {
+    app_handle: tauri::AppHandle,
+}
+
+impl TauriEventSink {
+    pub fn new(app_handle: tauri::AppHandle) -> Self {
+        Self { app_handle }
+    }
+
+    pub fn shared(app_handle: &tauri::AppHandle) -> SharedEventSink {
+        Arc::new(Self::new(app_handle.clone()))
+    }
+}
+
+impl EventSink for TauriEventSink {
+    fn emit(&self, task_id: &str, event: CompressionEvent) {
+        let payload = event.payload(task_id);
+        let event_name = format!("{}-{}", event.name(), task_id);
+        if let Err(e) = self.app_handle.emit(&event_name, payload.clone()) {
+            println!("❌ Failed to emit event {}: {}", event_name, e);
+        }
+        // 兼容旧前端：完成时额外发送 compression-finished-<taskId>
+        if let CompressionEvent::Completed { .. } = event {
+            let _ = self.app_handle.emit(&format!("compression-finished-{}", task_id), payload);
+        }
+    }
+
+    fn emit_queue_state(&self, snapshot: &QueueSnapshot) {
+        if let Err(e) = self.app_handle.emit("compression-queue-state", snapshot) {
+            println!("❌ Failed to emit queue state: {}", e);
+        }
+    }
+}
+
+// 多阶段任务（样片搜索、编码、画质评估）中，把某一阶段 0-100 的进度映射到 [start, start + span]
+pub struct ProgressRangeSink {
+    inner: SharedEventSink,
+    start: f64,
+    span: f64,
+}
+
+impl ProgressRangeSink {
+    pub fn shared(inner: SharedEventSink, start: f64, span: f64) -> SharedEventSink {
+        Arc::new(Self { inner, start, span })
+    }
+}
+
+impl EventSink for ProgressRangeSink {
+    fn emit(&self, task_id: &str, event: CompressionEvent) {
+        let event = match event {
+            CompressionEvent::Progress { progress } => CompressionEvent::Progress {
+                progress: self.start + progress.clamp(0.0, 100.0) / 100.0 * self.span,
+            },
+            other => other,
+        };
+        self.inner.emit(task_id, event);
+    }
+
+    fn emit_queue_state(&self, snapshot: &QueueSnapshot) {
+        self.inner.emit_queue_state(snapshot);
+    }
+}
+
+// 内存事件记录，便于在没有 webview 的环境下检查事件序列
+#[derive(Default)]
+pub struct MemoryEventSink {
+    events: Mutex<Vec<(String, CompressionEvent)>>,
+}
+
+impl MemoryEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<(String, CompressionEvent)> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn take(&self) -> Vec<(String, CompressionEvent)> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl EventSink for MemoryEventSink {
+    fn emit(&self, task_id: &str, event: CompressionEvent) {
+        self.events.lock().unwrap().push((task_id.to_string(), event));
+    }
+}
+
+// 丢弃所有事件
+pub struct NullEventSink;
+
+impl EventSink for NullEventSink {
+    fn emit(&self, _task_id: &str, _event: CompressionEvent) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress_values(sink: &MemoryEventSink) -> Vec<f64> {
+        sink.events()
+            .into_iter()
+            .filter_map(|(_, event)| match event {
+                CompressionEvent::Progress { progress } => Some(progress),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn progress_range_maps_into_span_and_clamps() {
+        let memory = Arc::new(MemoryEventSink::new());
+        let sink = ProgressRangeSink::shared(memory.clone(), 20.0, 50.0);
+        for progress in [0.0, 50.0, 100.0, 150.0, -10.0] {
+            sink.emit("t1", CompressionEvent::Progress { progress });
+        }
+        assert_eq!(progress_values(&memory), vec![20.0, 45.0, 70.0, 70.0, 20.0]);
+    }
+
+    #[test]
+    fn progress_ranges_nest() {
+        let memory = Arc::new(MemoryEventSink::new());
+        let outer = ProgressRangeSink::shared(memory.clone(), 50.0, 50.0);
+        let inner = ProgressRangeSink::shared(outer, 0.0, 50.0);
+        inner.emit("t1", CompressionEvent::Progress { progress: 100.0 });
+        assert_eq!(progress_values(&memory), vec![75.0]);
+    }
+
+    #[test]
+    fn progress_range_passes_other_events_through() {
+        let memory = Arc::new(MemoryEventSink::new());
+        let sink = ProgressRangeSink::shared(memory.clone(), 10.0, 10.0);
+        sink.emit("t1", CompressionEvent::Paused);
+        sink.emit("t2", CompressionEvent::Error { error: "boom".to_string(), stderr: None });
+
+        let events = memory.take();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, "t1");
+        assert!(matches!(events[0].1, CompressionEvent::Paused));
+        assert_eq!(events[1].0, "t2");
+        assert!(matches!(&events[1].1, CompressionEvent::Error { error, .. } if error == "boom"));
+        assert!(memory.events().is_empty());
+    }
+
+    #[test]
+    fn event_names_match_frontend_contract() {
+        let cases = [
+            (CompressionEvent::Command { command: String::new(), args: Vec::new() }, "compression-command"),
+            (CompressionEvent::Progress { progress: 0.0 }, "compression-progress"),
+            (
+                CompressionEvent::Completed {
+                    output_path: String::new(),
+                    original_size: 0,
+                    compressed_size: None,
+                    compressed_metadata: None,
+                },
+                "compression-completed",
+            ),
+            (
+                CompressionEvent::NoGain {
+                    output_path: String::new(),
+                    original_size: 0,
+                    compressed_size: 0,
+                    policy: String::new(),
+                },
+                "compression-no-gain",
+            ),
+            (CompressionEvent::Error { error: String::new(), stderr: None }, "compression-error"),
+            (CompressionEvent::Paused, "compression-paused"),
+            (CompressionEvent::Resumed, "compression-resumed"),
+            (CompressionEvent::Cancelled, "compression-cancelled"),
+        ];
+        for (event, name) in cases {
+            assert_eq!(event.name(), name);
+            assert_eq!(event.payload("t1")["taskId"], "t1");
+        }
+    }
+
+    #[test]
+    fn event_payloads() {
+        let progress = CompressionEvent::Progress { progress: 42.5 }.payload("t1");
+        assert_eq!(progress, json!({ "taskId": "t1", "progress": 42.5 }));
+
+        let completed = CompressionEvent::Completed {
+            output_path: "/out.mp4".to_string(),
+            original_size: 100,
+            compressed_size: Some(40),
+            compressed_metadata: None,
+        }
+        .payload("t1");
+        assert_eq!(completed["output_path"], "/out.mp4");
+        assert_eq!(completed["original_size"], 100);
+        assert_eq!(completed["compressed_size"], 40);
+        assert!(completed["compressed_metadata"].is_null());
+
+        let no_gain = CompressionEvent::NoGain {
+            output_path: "/in.mp4".to_string(),
+            original_size: 100,
+            compressed_size: 120,
+            policy: "discard".to_string(),
+        }
+        .payload("t1");
+        assert_eq!(no_gain["policy"], "discard");
+        assert_eq!(no_gain["compressed_size"], 120);
+
+        let error = CompressionEvent::Error { error: "failed".to_string(), stderr: None }.payload("t1");
+        assert_eq!(error, json!({ "taskId": "t1", "error": "failed" }));
+        let error = CompressionEvent::Error { error: "failed".to_string(), stderr: Some("tail".to_string()) }.payload("t1");
+        assert_eq!(error["stderr"], "tail");
+
+        assert_eq!(CompressionEvent::Cancelled.payload("t1"), json!({ "taskId": "t1", "status": "cancelled" }));
+        assert_eq!(CompressionEvent::Resumed.payload("t1"), json!({ "taskId": "t1" }));
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::Serialize;
+use crate::video::binaries::resolve_ffmpeg;
+use crate::video::color::{tonemap_filters, ResolvedHdr};
+use crate::video::frames::{capture_frame_data_url, frame_timestamp, LAST_FRAME_INDEX};
+use crate::video::types::{CompressionSettings, CropSettings, FrameRateSettings, StreamInfo, VideoFilters};
+use crate::video::utils::{get_video_metadata, tokio_command_with_no_window};
+
+// 视频滤镜链：按固定顺序组合为一个 -vf
+//   去隔行 → 帧率（丢帧/重复帧）→ 裁剪 → 降噪 → 旋转/翻转 → 缩放 → 帧率（运动插帧）→ 锐化 → 字幕烧录 → 像素格式
+// 缩放、帧率、字幕与像素格式由 build_video_encode_args 插入，这里只负责各类型滤镜本身。
+
+// r_frame_rate 与 avg_frame_rate 相差超过该比例视为可变帧率
+const VFR_TOLERANCE: f64 = 0.01;
+// 可变帧率转恒定帧率时吸附到的常见帧率
+const STANDARD_FRAME_RATES: [f64; 8] = [23.976, 24.0, 25.0, 29.97, 30.0, 50.0, 59.94, 60.0];
+// 需要恒定帧率的容器
+const CFR_CONTAINERS: [&str; 4] = ["avi", "flv", "wmv", "asf"];
+
+// 黑边检测：在与预览帧相同的时间点（跳过首尾两帧，片头片尾常为黑场）各检测一小段
+const CROPDETECT_SEGMENT_SECONDS: f64 = 2.0;
+// 自动裁剪时，多数采样段一致才应用
+const AUTO_CROP_MIN_CONFIDENCE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct CropRect {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl CropRect {
+    pub fn filter(&self) -> String {
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// 校验滤镜设置；source 为源视频流，用于检查裁剪范围
+pub(crate) fn validate_video_filters(filters: &VideoFilters, source: Option<&StreamInfo>) -> Result<(), String> {
+    if let Some(crop) = &filters.crop {
+        match crop.mode.as_str() {
+            "auto" => {}
+            "manual" | "" => {
+                let (Some(width), Some(height)) = (crop.width, crop.height) else {
+                    return Err("Manual crop requires width and height".to_string());
+                };
+                if width == 0 || height == 0 {
+                    return Err("Crop width and height must be greater than zero".to_string());
+                }
+                if let Some((src_w, src_h)) = source.and_then(|s| Some((s.width?, s.height?))) {
+                    let (x, y) = (crop.x.unwrap_or(0), crop.y.unwrap_or(0));
+                    if x.checked_add(width).is_none_or(|r| r > src_w) || y.checked_add(height).is_none_or(|b| b > src_h) {
+                        return Err(format!(
+                            "Crop {}x{}+{}+{} exceeds the {}x{} source",
+                            width, height, x, y, src_w, src_h
+                        ));
+                    }
+                }
+            }
+            other => return Err(format!("Unknown crop mode: {}", other)),
+        }
+    }
+    if let Some(rotate) = filters.rotate {
+        if !matches!(rotate, 0 | 90 | 180 | 270) {
+            return Err(format!("Rotation must be 0, 90, 180 or 270 degrees, got {}", rotate));
+        }
+    }
+    if let Some(flip) = &filters.flip {
+        if !matches!(flip.as_str(), "horizontal" | "vertical" | "both") {
+            return Err(format!("Unknown flip: {}", flip));
+        }
+    }
+    if let Some(deinterlace) = &filters.deinterlace {
+        if !matches!(deinterlace.as_str(), "yadif" | "bwdif") {
+            return Err(format!("Unknown deinterlace filter: {}", deinterlace));
+        }
+    }
+    if let Some(denoise) = &filters.denoise {
+        if !matches!(denoise.method.as_str(), "hqdn3d" | "nlmeans") {
+            return Err(format!("Unknown denoise filter: {}", denoise.method));
+        }
+    }
+    Ok(())
+}
+
+// 手动裁剪的尺寸取偶数，避免 4:2:0 编码器报错
+fn manual_crop_filter(crop: &CropSettings) -> Option<String> {
+    let rect = CropRect {
+        width: crop.width? & !1,
+        height: crop.height? & !1,
+        x: crop.x.unwrap_or(0),
+        y: crop.y.unwrap_or(0),
+    };
+    Some(rect.filter())
+}
+
+// 裁剪与旋转/翻转：改变画面几何形状，画质评估时也要作用于参考源
+fn crop_filter(filters: &VideoFilters) -> Option<String> {
+    // 自动裁剪在编码前已解析为具体数值（见 resolve_auto_crop），未解析时不裁剪
+    filters.crop.as_ref().filter(|c| c.mode != "auto").and_then(manual_crop_filter)
+}
+
+fn orientation_filters(filters: &VideoFilters) -> Vec<String> {
+    let mut out = Vec::new();
+    match filters.rotate.unwrap_or(0) {
+        90 => out.push("transpose=clock".to_string()),
+        180 => out.push("hflip,vflip".to_string()),
+        270 => out.push("transpose=cclock".to_string()),
+        _ => {}
+    }
+    match filters.flip.as_deref() {
+        Some("horizontal") => out.push("hflip".to_string()),
+        Some("vertical") => out.push("vflip".to_string()),
+        Some("both") => out.push("hflip,vflip".to_string()),
+        _ => {}
+    }
+    out
+}
+
+/// 缩放之前的滤镜：去隔行、帧率（丢帧/重复帧）、裁剪、降噪、旋转/翻转
+pub(crate) fn pre_scale_filters(settings: &CompressionSettings) -> Vec<String> {
+    let mut out = Vec::new();
+    let filters = settings.filters.clone().unwrap_or_default();
+    if let Some(deinterlace) = &filters.deinterlace {
+        // 只处理标记为隔行的帧，逐帧输出（帧率不变）
+        out.push(format!("{}=mode=send_frame:parity=auto:deint=interlaced", deinterlace));
+    }
+    // 降帧放在裁剪、降噪之前，减少后续滤镜处理的帧数
+    out.extend(frame_rate_pre_scale_filter(settings));
+    out.extend(crop_filter(&filters));
+    if let Some(denoise) = &filters.denoise {
+        match denoise.method.as_str() {
+            "nlmeans" => {
+                let s = denoise.strength.unwrap_or(1.0).clamp(1.0, 30.0);
+                out.push(format!("nlmeans=s={}", s));
+            }
+            _ => {
+                // 按 hqdn3d 默认比例 4:3:6:4.5 缩放强度
+                let s = denoise.strength.unwrap_or(4.0).clamp(0.0, 20.0);
+                out.push(format!("hqdn3d={}:{}:{}:{}", s, s * 0.75, s * 1.5, s * 1.125));
+            }
+        }
+    }
+    out.extend(orientation_filters(&filters));
+    out
+}
+
+/// 缩放之后的滤镜：帧率（运动插帧）、锐化
+pub(crate) fn post_scale_filters(settings: &CompressionSettings) -> Vec<String> {
+    let mut out: Vec<String> = frame_rate_post_scale_filter(settings).into_iter().collect();
+    match settings.filters.as_ref().and_then(|f| f.sharpen).map(|a| a.clamp(-1.5, 1.5)) {
+        Some(amount) if amount != 0.0 => out.push(format!("unsharp=5:5:{}:5:5:0", amount)),
+        _ => {}
+    }
+    out
+}
+
+/// 作用于参考源的滤镜（帧率、裁剪、旋转/翻转、色调映射），用于画质评估时对齐画面与帧
+pub(crate) fn reference_filters(settings: &CompressionSettings, hdr: Option<&ResolvedHdr>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    if let Some(fps) = fixed_fps(settings) {
+        out.push(format!("fps={}", fps));
+    }
+    if let Some(filters) = &settings.filters {
+        out.extend(crop_filter(filters));
+        out.extend(orientation_filters(filters));
+    }
+    out.extend(tonemap_filters(hdr, "yuv420p"));
+    out
+}
+
+fn is_vfr(source: &StreamInfo) -> bool {
+    match (source.frame_rate, source.avg_frame_rate) {
+        (Some(r), Some(avg)) => ((r - avg) / r).abs() > VFR_TOLERANCE,
+        _ => false,
+    }
+}
+
+// 吸附到最近的常见帧率（相差 2% 以内），否则保留三位小数
+fn snap_frame_rate(fps: f64) -> f64 {
+    STANDARD_FRAME_RATES
+        .iter()
+        .copied()
+        .min_by(|a, b| (a - fps).abs().total_cmp(&(b - fps).abs()))
+        .filter(|rate| ((rate - fps) / fps).abs() <= 0.02)
+        .unwrap_or((fps * 1000.0).round() / 1000.0)
+}
+
+/// 把帧率设置解析为具体的输出帧率：cap 根据源帧率决定是否降帧，
+/// keep 时可变帧率源输出到需要恒定帧率的容器会转为其平均帧率。返回 None 表示保持源帧率
+pub(crate) fn resolve_frame_rate(
+    frame_rate: Option<&FrameRateSettings>,
+    source: Option<&StreamInfo>,
+    output_path: &str,
+) -> Result<Option<FrameRateSettings>, String> {
+    let method = frame_rate.and_then(|f| f.method.clone());
+    if let Some(method) = &method {
+        if !matches!(method.as_str(), "drop" | "interpolate") {
+            return Err(format!("Unknown frame rate conversion method: {}", method));
+        }
+    }
+    let fixed = |fps: f64| Some(FrameRateSettings { mode: "fixed".to_string(), fps: Some(fps), method: method.clone() });
+    let source_fps = source.and_then(|s| s.frame_rate);
+    let mode = frame_rate.map(|f| f.mode.as_str()).filter(|m| !m.is_empty()).unwrap_or("keep");
+    let target = frame_rate.and_then(|f| f.fps);
+    match mode {
+        "fixed" | "cap" => {
+            let fps = target
+                .filter(|f| *f > 0.0 && *f <= 240.0)
+                .ok_or_else(|| format!("Frame rate mode \"{}\" requires fps between 0 and 240", mode))?;
+            if mode == "cap" && source_fps.is_some_and(|src| src <= fps * (1.0 + VFR_TOLERANCE)) {
+                println!("🎞️ Source frame rate {:?} is within the {} fps cap", source_fps, fps);
+                return Ok(None);
+            }
+            println!("🎞️ Output frame rate: {} fps (source {:?})", fps, source_fps);
+            Ok(fixed(fps))
+        }
+        "keep" => {
+            let ext = Path::new(output_path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase())
+                .unwrap_or_default();
+            match source.filter(|s| is_vfr(s)).and_then(|s| s.avg_frame_rate) {
+                Some(avg) if CFR_CONTAINERS.contains(&ext.as_str()) => {
+                    let fps = snap_frame_rate(avg);
+                    println!("🎞️ Variable frame rate source converted to {} fps for .{}", fps, ext);
+                    Ok(fixed(fps))
+                }
+                _ => Ok(None),
+            }
+        }
+        other => Err(format!("Unknown frame rate mode: {}", other)),
+    }
+}
+
+fn fixed_fps(settings: &CompressionSettings) -> Option<f64> {
+    settings.frame_rate.as_ref().filter(|f| f.mode == "fixed").and_then(|f| f.fps)
+}
+
+fn interpolate(settings: &CompressionSettings) -> bool {
+    settings.frame_rate.as_ref().and_then(|f| f.method.as_deref()) == Some("interpolate")
+}
+
+// 丢帧/重复帧方式的帧率转换，位于去隔行之后、裁剪之前
+fn frame_rate_pre_scale_filter(settings: &CompressionSettings) -> Option<String> {
+    fixed_fps(settings).filter(|_| !interpolate(settings)).map(|fps| format!("fps={}", fps))
+}
+
+// 运动插帧方式的帧率转换，位于缩放之后以减少计算量
+fn frame_rate_post_scale_filter(settings: &CompressionSettings) -> Option<String> {
+    fixed_fps(settings)
+        .filter(|_| interpolate(settings))
+        .map(|fps| format!("minterpolate=fps={}:mi_mode=mci:mc_mode=aobmc:me_mode=bidir:vsbmc=1", fps))
+}
+
+// 解析 cropdetect 输出中的 crop=w:h:x:y
+pub(crate) fn parse_cropdetect_line(line: &str) -> Option<CropRect> {
+    let value = &line[line.rfind("crop=")? + 5..];
+    let parts: Vec<u32> = value
+        .split_whitespace()
+        .next()?
+        .split(':')
+        .map(|p| p.parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    match parts.as_slice() {
+        [width, height, x, y] if *width > 0 && *height > 0 => Some(CropRect { width: *width, height: *height, x: *x, y: *y }),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CropDetection {
+    // 出现次数最多的裁剪区域；与源分辨率相同（没有黑边）或检测失败时为空
+    pub crop: Option<CropRect>,
+    // 与 crop 一致的采样段比例 0-1
+    pub confidence: f64,
+    // 有效采样段数
+    pub samples: usize,
+    #[serde(rename = "sourceWidth")]
+    pub source_width: Option<u32>,
+    #[serde(rename = "sourceHeight")]
+    pub source_height: Option<u32>,
+    // 标出裁剪区域的预览帧（JPEG data URL）
+    pub preview: Option<String>,
+}
+
+// 对一段视频运行 cropdetect；检测结果随帧累积，取最后一行作为该段的结果
+async fn detect_segment(ffmpeg_path: &Path, input_path: &str, seek: f64) -> Option<CropRect> {
+    let output = tokio_command_with_no_window(ffmpeg_path)
+        .args(["-hide_banner", "-ss", &format!("{:.2}", seek), "-i", input_path])
+        .args(["-t", &CROPDETECT_SEGMENT_SECONDS.to_string()])
+        .args(["-an", "-sn", "-vf", "cropdetect=round=2", "-f", "null", "-"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stderr).lines().rev().find_map(parse_cropdetect_line)
+}
+
+/// 在多个采样段上运行 cropdetect，返回出现次数最多的裁剪区域及其占比
+pub(crate) async fn detect_crop(ffmpeg_path: &Path, input_path: &str, start: f64, duration: f64) -> Result<Option<(CropRect, f64, usize)>, String> {
+    let mut votes: HashMap<CropRect, usize> = HashMap::new();
+    let mut samples = 0;
+    for index in 1..LAST_FRAME_INDEX {
+        let seek = start + frame_timestamp(duration, index);
+        if let Some(rect) = detect_segment(ffmpeg_path, input_path, seek).await {
+            *votes.entry(rect).or_insert(0) += 1;
+            samples += 1;
+        }
+    }
+    // 票数相同时取面积较大的区域，避免裁掉画面
+    let best = votes
+        .into_iter()
+        .max_by_key(|(rect, count)| (*count, rect.width as u64 * rect.height as u64));
+    Ok(best.map(|(rect, count)| (rect, count as f64 / samples as f64, samples)))
+}
+
+/// 把自动裁剪解析为手动裁剪参数；检测不到黑边或结果不稳定时返回 None（不裁剪）
+pub(crate) async fn resolve_auto_crop(
+    ffmpeg_path: &Path,
+    input_path: &str,
+    start: f64,
+    duration: f64,
+    source: Option<&StreamInfo>,
+) -> Result<Option<CropSettings>, String> {
+    let detected = detect_crop(ffmpeg_path, input_path, start, duration).await?;
+    let full = source.and_then(|s| Some((s.width?, s.height?)));
+    match detected {
+        Some((rect, confidence, _)) if full != Some((rect.width, rect.height)) && confidence >= AUTO_CROP_MIN_CONFIDENCE => {
+            println!("✂️ Auto crop detected: {} (confidence {:.2})", rect.filter(), confidence);
+            Ok(Some(CropSettings {
+                mode: "manual".to_string(),
+                width: Some(rect.width),
+                height: Some(rect.height),
+                x: Some(rect.x),
+                y: Some(rect.y),
+            }))
+        }
+        Some((rect, confidence, _)) if full != Some((rect.width, rect.height)) => {
+            println!("✂️ Auto crop skipped: {} only matched {:.0}% of samples", rect.filter(), confidence * 100.0);
+            Ok(None)
+        }
+        _ => {
+            println!("✂️ Auto crop: no black bars detected");
+            Ok(None)
+        }
+    }
+}
+
+/// 检测视频黑边，返回建议的裁剪区域、置信度与标出裁剪框的预览帧
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn detect_black_bars(
+    videoPath: String,
+    timeRangeStart: Option<f64>,
+    timeRangeEnd: Option<f64>,
+) -> Result<CropDetection, String> {
+    let ffmpeg_path = resolve_ffmpeg()?;
+    // ffprobe 与截帧都是同步进程调用，放到阻塞线程池执行，避免占用队列编码共享的异步工作线程
+    let metadata = {
+        let path = videoPath.clone();
+        tokio::task::spawn_blocking(move || get_video_metadata(path))
+            .await
+            .map_err(|e| format!("Failed to probe video: {}", e))??
+    };
+    let source = metadata.streams.iter().find(|s| s.codec_type == "video" && !s.attached_pic);
+    let (source_width, source_height) = (source.and_then(|s| s.width), source.and_then(|s| s.height));
+
+    let start = timeRangeStart.unwrap_or(0.0).max(0.0);
+    let end = timeRangeEnd.filter(|e| *e > start).unwrap_or(metadata.duration);
+    let duration = end - start;
+    if duration <= 0.0 {
+        return Err("Cannot detect black bars: video duration is unknown".to_string());
+    }
+
+    let detected = detect_crop(&ffmpeg_path, &videoPath, start, duration).await?;
+    let (crop, confidence, samples) = match detected {
+        Some((rect, confidence, samples)) => {
+            let full = source_width == Some(rect.width) && source_height == Some(rect.height);
+            ((!full).then_some(rect), confidence, samples)
+        }
+        None => (None, 0.0, 0),
+    };
+
+    // 预览：在中间时间点的画面上画出裁剪框
+    let preview = match crop {
+        Some(rect) => {
+            let filter = format!("drawbox=x={}:y={}:w={}:h={}:color=red@0.9:t=4", rect.x, rect.y, rect.width, rect.height);
+            let timestamp = start + frame_timestamp(duration, LAST_FRAME_INDEX / 2);
+            let (ffmpeg_path, path) = (ffmpeg_path.clone(), videoPath.clone());
+            let captured = tokio::task::spawn_blocking(move || capture_frame_data_url(&ffmpeg_path, &path, timestamp, Some(&filter)))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r);
+            match captured {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    tracing::warn!("Crop preview failed: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    println!("✂️ Black bar detection for {}: {:?} (confidence {:.2}, {} samples)", videoPath, crop, confidence, samples);
+    Ok(CropDetection {
+        crop,
+        confidence,
+        samples,
+        source_width,
+        source_height,
+        preview,
+    })
+}
@@ -1,47 +1,53 @@
 use std::process::Command;
-use tauri::Manager;
 use base64::{Engine as _, engine::general_purpose};
-use crate::video::{get_ffmpeg_binary, get_ffprobe_binary, command_with_no_window};
+use crate::video::command_with_no_window;
+use crate::video::binaries::{resolve_ffmpeg, resolve_ffprobe};
+
+// 预览帧数量，帧索引为 0..=LAST_FRAME_INDEX
+pub const LAST_FRAME_INDEX: u32 = 9;
+
+/// 第 frameIndex 个预览帧在时长内的时间偏移（秒），各处取帧共用：
+/// 第一帧使用一个小的偏移量以避免在最开始时出现问题，最后一帧使用一个远在结束前的时间戳以避免边缘情况
+pub fn frame_timestamp(duration: f64, frame_index: u32) -> f64 {
+    if frame_index == 0 {
+        0.1
+    } else if frame_index >= LAST_FRAME_INDEX {
+        (duration - 0.5).max(duration * 0.95).max(0.0)
+    } else {
+        (duration * frame_index as f64) / LAST_FRAME_INDEX as f64
+    }
+}
+
+/// 截取指定时间点的一帧（可附加滤镜），返回 JPEG data URL
+pub fn capture_frame_data_url(ffmpeg_path: &std::path::Path, video_path: &str, timestamp: f64, filter: Option<&str>) -> Result<String, String> {
+    let mut command = command_with_no_window(ffmpeg_path);
+    command.arg("-ss").arg(format!("{:.2}", timestamp)).arg("-i").arg(video_path);
+    if let Some(filter) = filter {
+        command.arg("-vf").arg(filter);
+    }
+    let output = command
+        .args(["-vframes", "1", "-q:v", "2", "-f", "image2pipe", "-vcodec", "mjpeg", "-"])
+        .output()
+        .map_err(|e| format!("Failed to capture frame at {:.2}s: {}", timestamp, e))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!(
+            "FFmpeg failed to capture frame at {:.2}s: {}",
+            timestamp,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(format!("data:image/jpeg;base64,{}", general_purpose::STANDARD.encode(&output.stdout)))
+}
 
 // 获取视频时长的单独函数 - 使用ffprobe快速获取
 #[allow(non_snake_case)]
 #[tauri::command]
-pub async fn get_video_duration(videoPath: String, _app_handle: tauri::AppHandle) -> Result<f64, String> {
+pub async fn get_video_duration(videoPath: String) -> Result<f64, String> {
     let start_time = std::time::Instant::now();
     tracing::debug!(target: "frames", "开始获取视频时长: {}", videoPath);
     
-    // 解析 ffprobe 路径（开发/生产均可用，含多路径回退）
-    let ffprobe_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffprobe_binary())
-    } else {
-        let resource_dir = _app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffprobe_binary()),
-            resource_dir.join("bin").join("ffprobe"),
-            resource_dir.join("bin").join("ffprobe.exe"),
-            exe_dir.join(get_ffprobe_binary()),
-            exe_dir.join("ffprobe"),
-            exe_dir.join("ffprobe.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFprobe binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+    // 解析 ffprobe 路径（统一解析器）
+    let ffprobe_path = resolve_ffprobe()?;
     
     // 使用ffprobe快速获取视频时长，比FFmpeg快得多
     let output = command_with_no_window(&ffprobe_path)
@@ -77,7 +83,7 @@ pub async fn get_video_duration(videoPath: String, _app_handle: tauri::AppHandle
 // 生成单帧函数，支持自定义时间范围
 #[allow(non_snake_case)]
 #[tauri::command]
-pub async fn generate_single_frame_with_time_range(videoPath: String, frameIndex: u32, timeRangeStart: f64, timeRangeEnd: f64, app_handle: tauri::AppHandle) -> Result<String, String> {
+pub async fn generate_single_frame_with_time_range(videoPath: String, frameIndex: u32, timeRangeStart: f64, timeRangeEnd: f64) -> Result<String, String> {
     let start_time = std::time::Instant::now();
     let time_range_duration = timeRangeEnd - timeRangeStart;
     tracing::debug!(target: "frames", "开始生成帧 {} for {}, 时间范围: {}s - {}s (时长: {}s)", frameIndex, videoPath, timeRangeStart, timeRangeEnd, time_range_duration);
@@ -93,38 +99,8 @@ pub async fn generate_single_frame_with_time_range(videoPath: String, frameIndex
     let path_check_start = std::time::Instant::now();
     tracing::debug!(target: "frames", "检查FFmpeg路径...");
     
-    // 获取 FFmpeg 路径（含多路径回退）
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffmpeg_binary()),
-            resource_dir.join("bin").join("ffmpeg"),
-            resource_dir.join("bin").join("ffmpeg.exe"),
-            exe_dir.join(get_ffmpeg_binary()),
-            exe_dir.join("ffmpeg"),
-            exe_dir.join("ffmpeg.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFmpeg binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+    // 获取 FFmpeg 路径（统一解析器）
+    let ffmpeg_path = resolve_ffmpeg()?;
     tracing::debug!(target: "frames", "FFmpeg路径获取完成, 耗时: {:?}, 路径: {:?}", path_check_start.elapsed(), ffmpeg_path);
     
     // Calculate timestamp within the custom time range
@@ -133,15 +109,7 @@ pub async fn generate_single_frame_with_time_range(videoPath: String, frameIndex
     let time_range_duration = timeRangeEnd - timeRangeStart;
     
     // 统一时间戳计算逻辑，与 generate_single_frame_with_duration 保持一致
-    let timestamp_offset = if frameIndex == 0 {
-        // 对于第一帧，使用一个小的偏移量以避免在最开始时出现问题
-        0.1
-    } else if frameIndex == 9 {
-        // 对于最后一帧，使用一个远在结束前的时间戳以避免边缘情况
-        (time_range_duration - 0.5).max(time_range_duration * 0.95).max(0.0)
-    } else {
-        (time_range_duration * frameIndex as f64) / 9.0
-    };
+    let timestamp_offset = frame_timestamp(time_range_duration, frameIndex);
     
     let timestamp = timeRangeStart + timestamp_offset;
     
@@ -196,58 +164,20 @@ pub async fn generate_single_frame_with_time_range(videoPath: String, frameIndex
 // 优化的生成单帧函数，接受预先计算的时长
 #[allow(non_snake_case)]
 #[tauri::command]
-pub async fn generate_single_frame_with_duration(videoPath: String, frameIndex: u32, duration: f64, app_handle: tauri::AppHandle) -> Result<String, String> {
+pub async fn generate_single_frame_with_duration(videoPath: String, frameIndex: u32, duration: f64) -> Result<String, String> {
     let start_time = std::time::Instant::now();
     tracing::debug!(target: "frames", "开始生成帧 {} for {}, 视频时长: {}s", frameIndex, videoPath, duration);
     
     let path_check_start = std::time::Instant::now();
     tracing::debug!(target: "frames", "检查FFmpeg路径...");
     
-    // 获取 FFmpeg 路径（含多路径回退）
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffmpeg_binary()),
-            resource_dir.join("bin").join("ffmpeg"),
-            resource_dir.join("bin").join("ffmpeg.exe"),
-            exe_dir.join(get_ffmpeg_binary()),
-            exe_dir.join("ffmpeg"),
-            exe_dir.join("ffmpeg.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFmpeg binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+    // 获取 FFmpeg 路径（统一解析器）
+    let ffmpeg_path = resolve_ffmpeg()?;
     tracing::debug!(target: "frames", "FFmpeg路径获取完成, 耗时: {:?}, 路径: {:?}", path_check_start.elapsed(), ffmpeg_path);
     
     // Calculate timestamp for the specific frame (跳过获取时长步骤)
     let timestamp_calc_start = std::time::Instant::now();
-    let timestamp = if frameIndex == 0 {
-        // For the first frame, use a small offset to avoid issues at the very beginning
-        0.1
-    } else if frameIndex == 9 {
-        // For the last frame, use a timestamp well before the end to avoid edge cases
-        (duration - 0.5).max(duration * 0.95).max(0.0)
-    } else {
-        (duration * frameIndex as f64) / 9.0
-    };
+    let timestamp = frame_timestamp(duration, frameIndex);
     let timestamp_str = format!("{:.2}", timestamp);
     tracing::debug!(target: "frames", "时间戳计算完成, 耗时: {:?}, 帧索引: {}, 时间戳: {}", timestamp_calc_start.elapsed(), frameIndex, timestamp_str);
     
@@ -287,78 +217,18 @@ pub async fn generate_single_frame_with_duration(videoPath: String, frameIndex:
 
 #[allow(non_snake_case, dead_code)]
 #[tauri::command]
-pub async fn generate_single_frame(videoPath: String, frameIndex: u32, app_handle: tauri::AppHandle) -> Result<String, String> {
+pub async fn generate_single_frame(videoPath: String, frameIndex: u32) -> Result<String, String> {
     let start_time = std::time::Instant::now();
     tracing::debug!(target: "frames", "开始生成帧 {} for {}", frameIndex, videoPath);
     
-    // 获取 FFmpeg 路径（含多路径回退）
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffmpeg_binary()),
-            resource_dir.join("bin").join("ffmpeg"),
-            resource_dir.join("bin").join("ffmpeg.exe"),
-            exe_dir.join(get_ffmpeg_binary()),
-            exe_dir.join("ffmpeg"),
-            exe_dir.join("ffmpeg.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFmpeg binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+    // 获取 FFmpeg 路径（统一解析器）
+    let ffmpeg_path = resolve_ffmpeg()?;
     
-    // 先用 ffprobe 获取时长（含多路径回退）
+    // 先用 ffprobe 获取时长
     let duration_start = std::time::Instant::now();
     tracing::debug!(target: "frames", "开始获取视频时长 for 帧 {}", frameIndex);
 
-    let ffprobe_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffprobe_binary())
-    } else {
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffprobe_binary()),
-            resource_dir.join("bin").join("ffprobe"),
-            resource_dir.join("bin").join("ffprobe.exe"),
-            exe_dir.join(get_ffprobe_binary()),
-            exe_dir.join("ffprobe"),
-            exe_dir.join("ffprobe.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFprobe binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+    let ffprobe_path = resolve_ffprobe()?;
 
     let duration_output = command_with_no_window(&ffprobe_path)
         .args([
@@ -387,15 +257,7 @@ pub async fn generate_single_frame(videoPath: String, frameIndex: u32, app_handl
     tracing::debug!(target: "frames", "获取视频时长完成 for 帧 {}, 耗时: {:?}", frameIndex, duration_start.elapsed());
     
     // Calculate timestamp for the specific frame
-    let timestamp = if frameIndex == 0 {
-        // For the first frame, use a small offset to avoid issues at the very beginning
-        0.1
-    } else if frameIndex == 9 {
-        // For the last frame, use a timestamp well before the end to avoid edge cases
-        (duration - 0.5).max(duration * 0.95).max(0.0)
-    } else {
-        (duration * frameIndex as f64) / 9.0
-    };
+    let timestamp = frame_timestamp(duration, frameIndex);
     let timestamp_str = format!("{:.2}", timestamp);
     
     // Create a safe temporary file path
@@ -442,72 +304,12 @@ pub async fn generate_single_frame(videoPath: String, frameIndex: u32, app_handl
 }
 
 #[tauri::command]
-pub async fn generate_video_frames(video_path: String, app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
-    // 获取 FFmpeg 路径（含多路径回退）
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffmpeg_binary()),
-            resource_dir.join("bin").join("ffmpeg"),
-            resource_dir.join("bin").join("ffmpeg.exe"),
-            exe_dir.join(get_ffmpeg_binary()),
-            exe_dir.join("ffmpeg"),
-            exe_dir.join("ffmpeg.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFmpeg binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+pub async fn generate_video_frames(video_path: String) -> Result<Vec<String>, String> {
+    // 获取 FFmpeg 路径（统一解析器）
+    let ffmpeg_path = resolve_ffmpeg()?;
     
-    // 解析 ffprobe 路径（含多路径回退）
-    let ffprobe_path = if cfg!(debug_assertions) {
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffprobe_binary())
-    } else {
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffprobe_binary()),
-            resource_dir.join("bin").join("ffprobe"),
-            resource_dir.join("bin").join("ffprobe.exe"),
-            exe_dir.join(get_ffprobe_binary()),
-            exe_dir.join("ffprobe"),
-            exe_dir.join("ffprobe.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFprobe binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+    // 解析 ffprobe 路径（统一解析器）
+    let ffprobe_path = resolve_ffprobe()?;
 
     // First, get video duration using ffprobe (fast)
     let duration_output = Command::new(&ffprobe_path)
@@ -595,47 +397,13 @@ pub async fn generate_video_frames(video_path: String, app_handle: tauri::AppHan
 
 #[allow(non_snake_case, dead_code)]
 #[tauri::command]
-pub async fn generate_thumbnail(videoPath: String, app_handle: tauri::AppHandle) -> Result<String, String> {
-    // In development mode, use the bin directory in src-tauri
-    // In production, use the resource directory
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        // Development mode: use bin directory relative to src-tauri
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        // Production mode: search multiple candidate locations
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .unwrap_or(std::env::current_dir().unwrap());
-        let candidates: Vec<std::path::PathBuf> = vec![
-            resource_dir.join("bin").join(get_ffmpeg_binary()),
-            resource_dir.join("bin").join("ffmpeg"),
-            resource_dir.join("bin").join("ffmpeg.exe"),
-            exe_dir.join(get_ffmpeg_binary()),
-            exe_dir.join("ffmpeg"),
-            exe_dir.join("ffmpeg.exe"),
-        ];
-        if let Some(found) = candidates.iter().find(|p| p.exists()).cloned() {
-            found
-        } else {
-            return Err(format!(
-                "FFmpeg binary not found. Tried: {}",
-                candidates
-                    .iter()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-    };
+pub async fn generate_thumbnail(videoPath: String) -> Result<String, String> {
+    let ffmpeg_path = resolve_ffmpeg()?;
 
     println!("[Thumbnail] Creating ffmpeg command for {}", videoPath);
 
     // 首先获取视频时长以计算中间帧位置
-    let duration_result = get_video_duration(videoPath.clone(), app_handle.clone()).await;
+    let duration_result = get_video_duration(videoPath.clone()).await;
     let middle_timestamp = match duration_result {
         Ok(duration) => {
             if duration > 10.0 {
@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::video::engine::{CompressionJob, JobKind};
+use crate::video::types::{CompressionResult, CompressionSettings};
+
+// 任务历史：每次执行的输入、输出、参数、状态、大小、时间与错误信息，
+// 持久化在 app_local_data_dir/task_history.json。未初始化存储路径时（如命令行）只保存在内存中。
+
+const MAX_HISTORY_RECORDS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    // 应用退出时仍在运行的任务，下次启动时标记
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRecord {
+    #[serde(rename = "taskId")]
+    pub task_id: String,
+    pub kind: JobKind,
+    #[serde(rename = "inputPath")]
+    pub input_path: String,
+    #[serde(rename = "outputPath")]
+    pub output_path: String,
+    pub settings: CompressionSettings,
+    pub status: HistoryStatus,
+    #[serde(rename = "originalSize")]
+    pub original_size: Option<u64>,
+    #[serde(rename = "compressedSize")]
+    pub compressed_size: Option<u64>,
+    pub error: Option<String>,
+    #[serde(rename = "startedAt")]
+    pub started_at: u64,
+    #[serde(rename = "finishedAt")]
+    pub finished_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistoryFilter {
+    // 为空表示不过滤
+    pub status: Option<Vec<HistoryStatus>>,
+    pub kind: Option<JobKind>,
+    pub codec: Option<String>,
+    // 输入/输出路径包含该文本（不区分大小写）
+    pub search: Option<String>,
+    // 开始时间范围（毫秒时间戳）
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPage {
+    pub total: usize,
+    pub records: Vec<TaskRecord>,
+}
+
+#[derive(Default)]
+struct HistoryState {
+    store_path: Option<PathBuf>,
+    records: Vec<TaskRecord>,
+}
+
+impl HistoryState {
+    fn save(&self) {
+        let Some(path) = &self.store_path else { return };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        match serde_json::to_string(&self.records) {
+            Ok(data) => {
+                if let Err(e) = std::fs::write(path, data) {
+                    println!("❌ Failed to save task history to {:?}: {}", path, e);
+                }
+            }
+            Err(e) => println!("❌ Failed to serialize task history: {}", e),
+        }
+    }
+}
+
+fn get_history() -> &'static Arc<Mutex<HistoryState>> {
+    static HISTORY: OnceLock<Arc<Mutex<HistoryState>>> = OnceLock::new();
+    HISTORY.get_or_init(|| Arc::new(Mutex::new(HistoryState::default())))
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 加载历史记录；上次退出时仍在运行的记录标记为 interrupted
+pub fn init_task_history(data_dir: Option<PathBuf>) {
+    let store_path = data_dir.map(|dir| dir.join("task_history.json"));
+    let mut records = store_path
+        .as_ref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|data| serde_json::from_str::<Vec<TaskRecord>>(&data).ok())
+        .unwrap_or_default();
+    let mut interrupted = 0;
+    for r in records.iter_mut().filter(|r| r.status == HistoryStatus::Running) {
+        r.status = HistoryStatus::Interrupted;
+        interrupted += 1;
+    }
+    println!("📜 Loaded {} history records ({} interrupted)", records.len(), interrupted);
+    let mut state = get_history().lock().unwrap();
+    state.store_path = store_path;
+    state.records = records;
+    if interrupted > 0 {
+        state.save();
+    }
+}
+
+/// 记录任务开始；同名任务（如恢复后重新执行）会覆盖旧记录
+pub fn record_task_started(job: &CompressionJob) {
+    let mut state = get_history().lock().unwrap();
+    state.records.retain(|r| r.task_id != job.task_id);
+    state.records.push(TaskRecord {
+        task_id: job.task_id.clone(),
+        kind: job.kind,
+        input_path: job.input_path.clone(),
+        output_path: job.output_path.clone(),
+        settings: job.settings.clone(),
+        status: HistoryStatus::Running,
+        original_size: std::fs::metadata(&job.input_path).ok().map(|m| m.len()),
+        compressed_size: None,
+        error: None,
+        started_at: now_millis(),
+        finished_at: None,
+    });
+    if state.records.len() > MAX_HISTORY_RECORDS {
+        let excess = state.records.len() - MAX_HISTORY_RECORDS;
+        state.records.drain(..excess);
+    }
+    state.save();
+}
+
+/// 记录任务结束。已标记为取消的记录保持取消状态
+pub fn record_task_finished(task_id: &str, outcome: &Result<CompressionResult, String>) {
+    let mut state = get_history().lock().unwrap();
+    let Some(record) = state.records.iter_mut().rev().find(|r| r.task_id == task_id) else { return };
+    if record.status == HistoryStatus::Cancelled {
+        return;
+    }
+    match outcome {
+        Ok(result) => {
+            record.status = if result.success { HistoryStatus::Completed } else { HistoryStatus::Failed };
+            record.original_size = Some(result.original_size);
+            record.compressed_size = result.compressed_size;
+            record.error = result.error.clone();
+            if let Some(p) = &result.output_path {
+                record.output_path = p.clone();
+            }
+        }
+        Err(e) => {
+            record.status = HistoryStatus::Failed;
+            record.error = Some(e.clone());
+        }
+    }
+    record.finished_at = Some(now_millis());
+    state.save();
+}
+
+/// 标记运行中的任务为已取消
+pub fn record_task_cancelled(task_id: &str) {
+    let mut state = get_history().lock().unwrap();
+    let Some(record) = state.records.iter_mut().rev().find(|r| r.task_id == task_id) else { return };
+    if record.status != HistoryStatus::Running {
+        return;
+    }
+    record.status = HistoryStatus::Cancelled;
+    record.finished_at = Some(now_millis());
+    state.save();
+}
+
+/// 按条件查询历史，最新的记录在前
+pub fn query_history(filter: &HistoryFilter) -> HistoryPage {
+    let state = get_history().lock().unwrap();
+    let search = filter.search.as_ref().map(|s| s.to_lowercase()).filter(|s| !s.is_empty());
+    let matched: Vec<&TaskRecord> = state
+        .records
+        .iter()
+        .rev()
+        .filter(|r| filter.status.as_ref().is_none_or(|s| s.is_empty() || s.contains(&r.status)))
+        .filter(|r| filter.kind.is_none_or(|k| r.kind == k))
+        .filter(|r| filter.codec.as_ref().is_none_or(|c| r.settings.codec.eq_ignore_ascii_case(c)))
+        .filter(|r| filter.since.is_none_or(|t| r.started_at >= t))
+        .filter(|r| filter.until.is_none_or(|t| r.started_at <= t))
+        .filter(|r| {
+            search.as_ref().is_none_or(|s| {
+                r.input_path.to_lowercase().contains(s) || r.output_path.to_lowercase().contains(s)
+            })
+        })
+        .collect();
+    let total = matched.len();
+    let records = matched
+        .into_iter()
+        .skip(filter.offset.unwrap_or(0))
+        .take(filter.limit.unwrap_or(usize::MAX))
+        .cloned()
+        .collect();
+    HistoryPage { total, records }
+}
+
+/// 删除指定的历史记录；为空时删除全部已结束的记录
+pub fn clear_history(task_ids: Option<&[String]>) {
+    let mut state = get_history().lock().unwrap();
+    match task_ids {
+        Some(ids) => state.records.retain(|r| !ids.contains(&r.task_id)),
+        None => state.records.retain(|r| r.status == HistoryStatus::Running),
+    }
+    state.save();
+}
+
+#[tauri::command]
+pub async fn query_task_history(filter: Option<HistoryFilter>) -> Result<HistoryPage, String> {
+    Ok(query_history(&filter.unwrap_or_default()))
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn clear_task_history(taskIds: Option<Vec<String>>) -> Result<(), String> {
+    clear_history(taskIds.as_deref());
+    Ok(())
+}
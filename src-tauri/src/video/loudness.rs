@@ -0,0 +1,151 @@
+use crate::video::compression::{build_input_args, run_ffmpeg_tracked, VideoJob};
+use crate::video::quality::stderr_tail;
+use crate::video::types::{CompressionSettings, LoudnormSettings, StreamInfo};
+
+// 响度标准化（EBU R128）：第一遍用 loudnorm 分析源音频，第二遍在编码时代入测量值做线性增益，
+// 编码完成后再测量一次输出响度用于结果展示。
+// 输出有多条音轨（流选择 all / custom）时逐条测量，并用 -filter:a:N 给每条音轨代入各自的测量值；
+// 结果中的响度对比以输出的第一条音轨为准。
+
+// 响度分析（源 + 输出）占总进度的比例，前后各一半
+pub const LOUDNORM_PROGRESS_SHARE: f64 = 10.0;
+const DEFAULT_TARGET_LUFS: f64 = -23.0;
+const DEFAULT_TRUE_PEAK: f64 = -1.0;
+const DEFAULT_LRA: f64 = 7.0;
+// loudnorm 内部以 192kHz 处理，未指定采样率且无法从源获取时的输出采样率
+const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+#[derive(Debug, Clone)]
+pub struct LoudnessMeasurement {
+    pub integrated: f64,
+    pub true_peak: f64,
+    pub lra: f64,
+    pub threshold: f64,
+    pub offset: f64,
+}
+
+fn targets(settings: &LoudnormSettings) -> (f64, f64, f64) {
+    (
+        settings.target_lufs.unwrap_or(DEFAULT_TARGET_LUFS).clamp(-70.0, -5.0),
+        settings.true_peak.unwrap_or(DEFAULT_TRUE_PEAK).clamp(-9.0, 0.0),
+        settings.lra.unwrap_or(DEFAULT_LRA).clamp(1.0, 50.0),
+    )
+}
+
+// 解析 loudnorm print_format=json 输出（stderr 中 [Parsed_loudnorm_0 ...] 之后的 JSON 块）
+fn parse_loudnorm_json(stderr: &str) -> Option<LoudnessMeasurement> {
+    let from = stderr.rfind("Parsed_loudnorm")?;
+    let tail = &stderr[from..];
+    let start = tail.find('{')?;
+    let end = tail[start..].find('}')? + start;
+    let json: serde_json::Value = serde_json::from_str(&tail[start..=end]).ok()?;
+    let field = |name: &str| -> Option<f64> {
+        let value = json[name].as_str()?.trim();
+        match value {
+            "-inf" => Some(-99.0),
+            "inf" => Some(99.0),
+            _ => value.parse::<f64>().ok(),
+        }
+    };
+    Some(LoudnessMeasurement {
+        integrated: field("input_i")?,
+        true_peak: field("input_tp")?,
+        lra: field("input_lra")?,
+        threshold: field("input_thresh")?,
+        offset: field("target_offset").unwrap_or(0.0),
+    })
+}
+
+/// 输出中各音轨对应的源流序号（按输出顺序）。没有 -map 时由 FFmpeg 自动选择一条音轨，返回 [None]
+pub(crate) fn mapped_audio_tracks(stream_map: &[String], streams: &[StreamInfo]) -> Vec<Option<u32>> {
+    if !stream_map.iter().any(|arg| arg == "-map") {
+        return vec![None];
+    }
+    stream_map
+        .windows(2)
+        .filter(|pair| pair[0] == "-map")
+        .filter_map(|pair| pair[1].strip_prefix("0:")?.parse::<u32>().ok())
+        .filter(|index| streams.iter().any(|s| s.index == *index && s.codec_type == "audio"))
+        .map(Some)
+        .collect()
+}
+
+/// 测量音频响度。stream 为 -map 流说明符（如 "0:2"），为空时测量 FFmpeg 自动选择的音轨；start/window 为空时测量整个文件
+pub(crate) async fn analyze_loudness(
+    job: &VideoJob<'_>,
+    path: &str,
+    stream: Option<&str>,
+    start_time: Option<f64>,
+    window: Option<f64>,
+    settings: &LoudnormSettings,
+) -> Result<LoudnessMeasurement, String> {
+    let (i, tp, lra) = targets(settings);
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-nostats".into()];
+    args.extend(build_input_args(path, start_time, window));
+    if let Some(stream) = stream {
+        args.push("-map".to_string());
+        args.push(stream.to_string());
+    }
+    args.push("-af".to_string());
+    args.push(format!("loudnorm=I={}:TP={}:LRA={}:print_format=json", i, tp, lra));
+    for arg in ["-vn", "-sn", "-f", "null", "-", "-progress", "pipe:1"] {
+        args.push(arg.to_string());
+    }
+    println!("🔊 Analyzing loudness of {} ({})", path, stream.unwrap_or("default audio"));
+    let run = run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, job.duration, &job.display_name, job.sink, |p| p).await?;
+    if !run.status.success() {
+        return Err(format!("Loudness analysis failed: {}", stderr_tail(&run.stderr, 5)));
+    }
+    let measured = parse_loudnorm_json(&run.stderr)
+        .ok_or_else(|| "Loudness analysis produced no measurement".to_string())?;
+    println!(
+        "🔊 Loudness: I {:.1} LUFS, TP {:.1} dBTP, LRA {:.1} LU",
+        measured.integrated, measured.true_peak, measured.lra
+    );
+    Ok(measured)
+}
+
+/// 第二遍 loudnorm 滤镜：代入测量值做线性标准化，并重采样回正常采样率
+pub(crate) fn loudnorm_filter(settings: &CompressionSettings, measured: &LoudnessMeasurement, source_sample_rate: Option<u32>) -> String {
+    let (i, tp, lra) = settings.loudnorm.as_ref().map(targets).unwrap_or((DEFAULT_TARGET_LUFS, DEFAULT_TRUE_PEAK, DEFAULT_LRA));
+    let sample_rate = settings
+        .audio
+        .as_ref()
+        .and_then(|a| a.sample_rate)
+        .or(source_sample_rate)
+        .unwrap_or(DEFAULT_SAMPLE_RATE);
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=summary,aresample={}",
+        i, tp, lra, measured.integrated, measured.true_peak, measured.lra, measured.threshold, measured.offset, sample_rate
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::streams::parse_stream_infos;
+
+    fn streams() -> Vec<StreamInfo> {
+        parse_stream_infos(&serde_json::json!({
+            "streams": [
+                { "index": 0, "codec_type": "video" },
+                { "index": 1, "codec_type": "audio" },
+                { "index": 2, "codec_type": "audio" },
+                { "index": 3, "codec_type": "subtitle" },
+            ]
+        }))
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn mapped_audio_tracks_follow_output_order() {
+        assert_eq!(mapped_audio_tracks(&[], &streams()), vec![None]);
+        let map = args(&["-map", "0:0", "-map", "0:2", "-map", "0:1", "-map", "0:3", "-disposition:a:0", "default"]);
+        assert_eq!(mapped_audio_tracks(&map, &streams()), vec![Some(2), Some(1)]);
+        let no_audio = args(&["-map", "[vout]", "-map", "0:3"]);
+        assert!(mapped_audio_tracks(&no_audio, &streams()).is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs::{File, FileTimes};
+use std::path::Path;
+use crate::video::types::CompressionSettings;
+
+// 元数据保留：
+//   tags        -map_metadata 复制源容器标签（creation_time、location 等）；MP4/MOV 另加 use_metadata_tags，
+//               以 mdta 形式写入 QuickTime 键（com.apple.quicktime.location.ISO6709、make、model 等），否则这些键会被丢弃
+//   chapters    -map_chapters 复制章节；按时间范围截取时 FFmpeg 会平移并裁掉范围外的章节
+//   file_times  完成后把输出文件的修改/访问时间设为源文件的时间
+// 这些参数只加在最终输出上（两遍编码的第一遍、分段编码的分段不需要）。
+// 图片经 FFmpeg 输出时 JPEG/PNG/WebP 的 EXIF 不会写入，tags 为 true 时由 image::exif 从源文件复制。
+
+fn is_quicktime(output_path: &str) -> bool {
+    let ext = Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    matches!(ext.as_str(), "mp4" | "m4v" | "mov")
+}
+
+/// 元数据与章节参数，source 为提供元数据的输入序号
+pub(crate) fn metadata_args(settings: &CompressionSettings, output_path: &str, source: usize) -> Vec<String> {
+    let Some(metadata) = &settings.metadata else { return Vec::new() };
+    let mut args: Vec<String> = Vec::new();
+    let mapping = |keep: bool| if keep { source.to_string() } else { "-1".to_string() };
+    if let Some(keep) = metadata.tags {
+        args.push("-map_metadata".to_string());
+        args.push(mapping(keep));
+        if keep && is_quicktime(output_path) {
+            args.push("-movflags".to_string());
+            args.push("+use_metadata_tags".to_string());
+        }
+    }
+    if let Some(keep) = metadata.chapters {
+        args.push("-map_chapters".to_string());
+        args.push(mapping(keep));
+    }
+    args
+}
+
+/// 需要保留时把输出文件的修改/访问时间设为源文件的时间。失败只打印警告
+pub fn apply_file_times(settings: &CompressionSettings, input_path: &str, output_path: &str) {
+    let enabled = settings.metadata.as_ref().and_then(|m| m.file_times) == Some(true);
+    // 输出就是源文件（例如 discard 策略）时无需处理
+    if !enabled || input_path == output_path {
+        return;
+    }
+    let result = std::fs::metadata(input_path).and_then(|source| {
+        let mut times = FileTimes::new().set_modified(source.modified()?);
+        if let Ok(accessed) = source.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        File::options().write(true).open(output_path)?.set_times(times)
+    });
+    match result {
+        Ok(()) => println!("🕒 Copied file times from {} to {}", input_path, output_path),
+        Err(e) => println!("⚠️ Failed to copy file times to {}: {}", output_path, e),
+    }
+}
@@ -1,9 +1,45 @@
 pub mod types;
+pub mod binaries;
+pub mod events;
+pub mod engine;
 pub mod compression;
+pub mod queue;
+pub mod history;
+pub mod resume;
+pub mod quality;
+pub mod loudness;
+pub mod streams;
+pub mod subtitles;
+pub mod filters;
+pub mod color;
+pub mod encoder;
+pub mod remux;
+pub mod oversize;
+pub mod output;
+pub mod naming;
+pub mod metadata;
+pub mod scale;
 pub mod frames;
 pub mod utils;
 
 pub use types::*;
+pub use binaries::*;
+pub use events::*;
+pub use engine::*;
 pub use compression::*;
+pub use queue::*;
+pub use history::*;
+pub use resume::*;
+pub use quality::*;
+pub use loudness::*;
+pub use streams::*;
+pub use subtitles::*;
+pub use filters::*;
+pub use color::*;
+pub use oversize::*;
+pub use output::*;
+pub use naming::*;
+pub use metadata::*;
+pub use scale::*;
 pub use frames::*;
 pub use utils::*;
\ No newline at end of file
@@ -0,0 +1,463 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::video::engine::{CompressionJob, JobKind};
+use crate::video::types::CompressionSettings;
+
+// 输出命名：由模板生成文件名（不含扩展名，扩展名取自 settings.format），可选地在输出目录下还原导入文件夹的子目录结构。
+// 模板占位符：
+//   {stem}        输入文件名（不含扩展名）
+//   {codec}       视频编码（图片为输出格式）
+//   {crf}         CRF / 质量值，未设置时为空
+//   {resolution}  输出分辨率设置，如 original、1280x720、fit1280x720、long1920、50pct
+//   {date}        任务规划时的本地日期 YYYYMMDD
+//   {index}       批量中的序号，从 1 开始，按批量大小补零
+// 同名文件处理（collision）：
+//   overwrite    覆盖已有文件（默认）
+//   skip         跳过该任务
+//   auto_number  追加 _1、_2… 直到不冲突
+// 同一批次内的重名总是自动编号；目标与输入文件相同时不会覆盖原文件，同样自动编号。
+// 规划之后才出现的同名文件在写入最终文件时再按策略处理（见 resolve_collision）：skip 时任务失败，auto_number 时改用下一个编号。
+
+const DEFAULT_TEMPLATE: &str = "{stem}";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputNaming {
+    // 文件名模板，默认 "{stem}"
+    pub template: Option<String>,
+    // 输出目录；为空时输出到输入文件所在目录
+    #[serde(rename = "outputDir")]
+    pub output_dir: Option<String>,
+    // 导入文件夹的根目录：设置后在输出目录下还原输入文件相对该目录的子目录
+    #[serde(rename = "inputRoot")]
+    pub input_root: Option<String>,
+    // "overwrite" | "skip" | "auto_number"
+    pub collision: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedOutput {
+    #[serde(rename = "taskId")]
+    pub task_id: String,
+    #[serde(rename = "inputPath")]
+    pub input_path: String,
+    #[serde(rename = "outputPath")]
+    pub output_path: String,
+    // 目标已存在：overwrite 时将被覆盖，skip 时任务被跳过
+    pub exists: bool,
+    pub skipped: bool,
+}
+
+fn policy_name(collision: Option<&str>) -> &str {
+    collision.filter(|c| !c.is_empty()).unwrap_or("overwrite")
+}
+
+fn collision_policy(naming: &OutputNaming) -> &str {
+    policy_name(naming.collision.as_deref())
+}
+
+/// 在规划之前校验命名设置
+pub fn validate_output_naming(naming: &OutputNaming) -> Result<(), String> {
+    match collision_policy(naming) {
+        "overwrite" | "skip" | "auto_number" => {}
+        other => return Err(format!("Unknown collision policy: {}", other)),
+    }
+    render_template(template(naming), &TemplateVars::default()).map(|_| ())
+}
+
+fn template(naming: &OutputNaming) -> &str {
+    naming.template.as_deref().filter(|t| !t.trim().is_empty()).unwrap_or(DEFAULT_TEMPLATE)
+}
+
+#[derive(Default)]
+struct TemplateVars {
+    stem: String,
+    codec: String,
+    crf: String,
+    resolution: String,
+    date: String,
+    index: String,
+}
+
+fn render_template(template: &str, vars: &TemplateVars) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .map(|c| open + c)
+            .ok_or_else(|| format!("Unclosed placeholder in output template: {}", template))?;
+        let value = match &rest[open + 1..close] {
+            "stem" => &vars.stem,
+            "codec" => &vars.codec,
+            "crf" => &vars.crf,
+            "resolution" => &vars.resolution,
+            "date" => &vars.date,
+            "index" => &vars.index,
+            other => return Err(format!("Unknown placeholder {{{}}} in output template", other)),
+        };
+        out.push_str(value);
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// 去掉文件名中不允许的字符（按 Windows 规则，跨平台一致）
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .collect();
+    cleaned.trim().trim_end_matches('.').to_string()
+}
+
+fn codec_label(kind: JobKind, settings: &CompressionSettings) -> String {
+    match kind {
+        JobKind::Video => settings.codec.replace(' ', ""),
+        JobKind::Image => settings.format.to_lowercase(),
+    }
+}
+
+fn resolution_label(settings: &CompressionSettings) -> String {
+    let num = |v: Option<u32>| v.map(|n| n.to_string()).unwrap_or_default();
+    if let Some(scale) = &settings.scale {
+        return match scale.mode.as_str() {
+            "long_edge" => format!("long{}", num(scale.edge)),
+            "short_edge" => format!("short{}", num(scale.edge)),
+            "percent" => format!("{}pct", scale.percent.unwrap_or(100.0)),
+            mode => format!("{}{}x{}", mode, num(scale.width), num(scale.height)),
+        };
+    }
+    match (settings.resolution.as_str(), &settings.custom_resolution) {
+        ("custom", Some(custom)) => format!("{}x{}", custom.width, custom.height),
+        (resolution, _) => resolution.to_string(),
+    }
+}
+
+// 本地日期 YYYYMMDD
+fn local_date() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    civil_date(secs + local_utc_offset(secs))
+}
+
+// Unix 秒数对应的日期 YYYYMMDD（公历换算，见 Howard Hinnant 的 civil_from_days）
+fn civil_date(secs: i64) -> String {
+    let z = secs.div_euclid(86_400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+// 本地时区相对 UTC 的偏移（秒，含夏令时）
+#[cfg(unix)]
+fn local_utc_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: localtime_r 只写入传入的 tm
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            0
+        } else {
+            tm.tm_gmtoff as i64
+        }
+    }
+}
+
+#[cfg(windows)]
+fn local_utc_offset(_secs: i64) -> i64 {
+    use windows_sys::Win32::System::Time::{GetTimeZoneInformation, TIME_ZONE_INFORMATION};
+    // SAFETY: GetTimeZoneInformation 只写入传入的结构体
+    unsafe {
+        let mut tz: TIME_ZONE_INFORMATION = std::mem::zeroed();
+        // Bias 为 UTC 减本地时间（分钟）；返回值 1/2 表示当前处于标准时间/夏令时
+        let bias = match GetTimeZoneInformation(&mut tz) {
+            0 => tz.Bias,
+            1 => tz.Bias + tz.StandardBias,
+            2 => tz.Bias + tz.DaylightBias,
+            _ => return 0,
+        };
+        -i64::from(bias) * 60
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn local_utc_offset(_secs: i64) -> i64 {
+    0
+}
+
+// 输出目录：指定输出目录时按需还原子目录，否则与输入文件同目录
+fn output_dir(input: &Path, naming: &OutputNaming) -> PathBuf {
+    let input_dir = input.parent().unwrap_or(Path::new("")).to_path_buf();
+    let Some(root) = naming.output_dir.as_deref().filter(|d| !d.is_empty()) else { return input_dir };
+    let mut dir = PathBuf::from(root);
+    if let Some(relative) = naming
+        .input_root
+        .as_deref()
+        .filter(|r| !r.is_empty())
+        .and_then(|r| input_dir.strip_prefix(r).ok())
+    {
+        dir.push(relative);
+    }
+    dir
+}
+
+fn with_extension(name: &str, ext: &str) -> String {
+    if ext.is_empty() { name.to_string() } else { format!("{}.{}", name, ext) }
+}
+
+// 追加 _1、_2… 直到路径可用
+fn numbered_path(dir: &Path, name: &str, ext: &str, is_free: impl Fn(&Path) -> bool) -> PathBuf {
+    (1..)
+        .map(|n| dir.join(with_extension(&format!("{}_{}", name, n), ext)))
+        .find(|path| is_free(path))
+        .unwrap_or_else(|| dir.join(with_extension(name, ext)))
+}
+
+/// 写入最终文件前按同名策略复查目标路径，返回实际写入的路径。
+/// 规划后才出现的同名文件：overwrite 时覆盖，skip 时报错，auto_number 时追加下一个可用编号
+pub fn resolve_collision(output_path: &str, collision: Option<&str>) -> Result<String, String> {
+    let path = Path::new(output_path);
+    let policy = policy_name(collision);
+    if !matches!(policy, "overwrite" | "skip" | "auto_number") {
+        return Err(format!("Unknown collision policy: {}", policy));
+    }
+    if !path.exists() {
+        return Ok(output_path.to_string());
+    }
+    match policy {
+        "overwrite" => {
+            println!("⚠️ Overwriting existing file {}", output_path);
+            return Ok(output_path.to_string());
+        }
+        "skip" => return Err(format!("Output already exists: {}", output_path)),
+        _ => {}
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let numbered = numbered_path(dir, name, ext, |p| !p.exists());
+    println!("🔢 {} appeared after planning, writing {} instead", output_path, numbered.display());
+    Ok(numbered.to_string_lossy().to_string())
+}
+
+/// 为一批任务规划最终输出路径，不创建目录也不写入文件
+pub fn plan_output_paths(jobs: &[CompressionJob], naming: &OutputNaming) -> Result<Vec<PlannedOutput>, String> {
+    validate_output_naming(naming)?;
+    let policy = collision_policy(naming);
+    let width = jobs.len().to_string().len();
+    let date = local_date();
+    // 批次内已占用的路径（按小写比较，兼顾大小写不敏感的文件系统）
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut planned = Vec::with_capacity(jobs.len());
+
+    for (i, job) in jobs.iter().enumerate() {
+        let input = Path::new(&job.input_path);
+        let settings = &job.settings;
+        let vars = TemplateVars {
+            stem: input.file_stem().and_then(|s| s.to_str()).unwrap_or("output").to_string(),
+            codec: codec_label(job.kind, settings),
+            crf: settings.crf_value.or(settings.qv_value).map(|v| v.to_string()).unwrap_or_default(),
+            resolution: resolution_label(settings),
+            date: date.clone(),
+            index: format!("{:0width$}", i + 1, width = width),
+        };
+        let name = sanitize_file_name(&render_template(template(naming), &vars)?);
+        if name.is_empty() {
+            return Err(format!("Output template produced an empty file name for {}", job.input_path));
+        }
+        let ext = match settings.format.trim_start_matches('.') {
+            "" => input.extension().and_then(|e| e.to_str()).unwrap_or("").to_string(),
+            format => format.to_lowercase(),
+        };
+        let dir = output_dir(input, naming);
+
+        let reserved = |path: &Path| path == input || taken.contains(&path.to_string_lossy().to_lowercase());
+        let path = dir.join(with_extension(&name, &ext));
+        let exists = path.exists();
+        let (path, exists, skipped) = if reserved(&path) || (exists && policy == "auto_number") {
+            (numbered_path(&dir, &name, &ext, |p| !p.exists() && !reserved(p)), false, false)
+        } else {
+            (path, exists, exists && policy == "skip")
+        };
+        let output_path = path.to_string_lossy().to_string();
+        if !skipped {
+            taken.insert(output_path.to_lowercase());
+        }
+        planned.push(PlannedOutput {
+            task_id: job.task_id.clone(),
+            input_path: job.input_path.clone(),
+            output_path,
+            exists,
+            skipped,
+        });
+    }
+    Ok(planned)
+}
+
+/// 预览一批任务的最终输出路径
+#[tauri::command]
+pub async fn preview_output_paths(jobs: Vec<CompressionJob>, naming: OutputNaming) -> Result<Vec<PlannedOutput>, String> {
+    plan_output_paths(&jobs, &naming)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试用的临时目录，结束时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("compressgo-naming-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str) -> String {
+            self.0.join(name).to_string_lossy().to_string()
+        }
+
+        fn touch(&self, name: &str) -> String {
+            let path = self.file(name);
+            std::fs::write(&path, b"x").unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn job(task_id: &str, input_path: &str) -> CompressionJob {
+        let settings = serde_json::from_value(serde_json::json!({
+            "format": "mp4",
+            "codec": "H.265",
+            "resolution": "original",
+            "quality_type": "crf",
+            "crf_value": 28,
+        }))
+        .unwrap();
+        CompressionJob {
+            task_id: task_id.to_string(),
+            kind: JobKind::Video,
+            input_path: input_path.to_string(),
+            output_path: String::new(),
+            settings,
+        }
+    }
+
+    fn naming(template: &str, collision: &str) -> OutputNaming {
+        OutputNaming {
+            template: Some(template.to_string()),
+            collision: Some(collision.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn render_template_substitutes_placeholders() {
+        let vars = TemplateVars {
+            stem: "clip".into(),
+            codec: "H.265".into(),
+            crf: "28".into(),
+            resolution: "1280x720".into(),
+            date: "20240301".into(),
+            index: "07".into(),
+        };
+        assert_eq!(
+            render_template("{index}-{stem}_{codec}_crf{crf}_{resolution}_{date}", &vars).unwrap(),
+            "07-clip_H.265_crf28_1280x720_20240301"
+        );
+        assert_eq!(render_template("plain", &vars).unwrap(), "plain");
+        assert_eq!(render_template("{crf}", &TemplateVars::default()).unwrap(), "");
+    }
+
+    #[test]
+    fn render_template_rejects_unknown_and_unclosed_placeholders() {
+        let vars = TemplateVars::default();
+        assert!(render_template("{stem}_{size}", &vars).unwrap_err().contains("{size}"));
+        assert!(render_template("{stem", &vars).unwrap_err().contains("Unclosed"));
+    }
+
+    #[test]
+    fn civil_date_converts_unix_seconds() {
+        assert_eq!(civil_date(0), "19700101");
+        assert_eq!(civil_date(-1), "19691231");
+        assert_eq!(civil_date(951_782_400), "20000229");
+        assert_eq!(civil_date(1_709_251_199), "20240229");
+        // UTC+8：UTC 16:00 之后已是本地的第二天
+        assert_eq!(civil_date(1_709_222_400 + 8 * 3600), "20240301");
+        assert_eq!(local_date().len(), 8);
+    }
+
+    #[test]
+    fn plan_applies_collision_policy() {
+        let dir = TempDir::new("plan");
+        let input = dir.touch("a.mov");
+        dir.touch("a.mp4");
+        let jobs = [job("1", &input)];
+
+        let overwrite = plan_output_paths(&jobs, &naming("{stem}", "overwrite")).unwrap().remove(0);
+        assert_eq!(overwrite.output_path, dir.file("a.mp4"));
+        assert!(overwrite.exists && !overwrite.skipped);
+
+        let skip = plan_output_paths(&jobs, &naming("{stem}", "skip")).unwrap().remove(0);
+        assert!(skip.exists && skip.skipped);
+
+        dir.touch("a_1.mp4");
+        let numbered = plan_output_paths(&jobs, &naming("{stem}", "auto_number")).unwrap().remove(0);
+        assert_eq!(numbered.output_path, dir.file("a_2.mp4"));
+        assert!(!numbered.exists && !numbered.skipped);
+    }
+
+    #[test]
+    fn plan_numbers_batch_duplicates_and_protects_input() {
+        let dir = TempDir::new("batch");
+        let first = dir.touch("a.mov");
+        let second = dir.touch("b.mov");
+        let jobs = [job("1", &first), job("2", &second)];
+        let planned = plan_output_paths(&jobs, &naming("out", "overwrite")).unwrap();
+        assert_eq!(planned[0].output_path, dir.file("out.mp4"));
+        assert_eq!(planned[1].output_path, dir.file("out_1.mp4"));
+
+        // 输出与输入同名时不覆盖原文件
+        let input = dir.touch("c.mp4");
+        let planned = plan_output_paths(&[job("3", &input)], &naming("{stem}", "overwrite")).unwrap();
+        assert_eq!(planned[0].output_path, dir.file("c_1.mp4"));
+    }
+
+    #[test]
+    fn plan_rejects_invalid_naming() {
+        let jobs = [job("1", "/tmp/a.mov")];
+        assert!(plan_output_paths(&jobs, &naming("{stem}", "rename")).is_err());
+        assert!(plan_output_paths(&jobs, &naming("{nope}", "overwrite")).is_err());
+        assert!(plan_output_paths(&jobs, &naming("...", "overwrite")).is_err());
+    }
+
+    #[test]
+    fn resolve_collision_rechecks_at_commit_time() {
+        let dir = TempDir::new("commit");
+        let free = dir.file("free.mp4");
+        assert_eq!(resolve_collision(&free, Some("skip")).unwrap(), free);
+
+        let taken = dir.touch("taken.mp4");
+        assert_eq!(resolve_collision(&taken, None).unwrap(), taken);
+        assert_eq!(resolve_collision(&taken, Some("overwrite")).unwrap(), taken);
+        assert!(resolve_collision(&taken, Some("skip")).is_err());
+        assert_eq!(resolve_collision(&taken, Some("auto_number")).unwrap(), dir.file("taken_1.mp4"));
+        assert!(resolve_collision(&taken, Some("rename")).is_err());
+    }
+}
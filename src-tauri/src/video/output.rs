@@ -0,0 +1,99 @@
+use std::path::Path;
+use crate::video::naming::resolve_collision;
+use crate::video::quality::stderr_tail;
+use crate::video::utils::tokio_command_with_no_window;
+
+// 原子写出：FFmpeg 先写入输出旁的临时文件（.<文件名>.compressgo-partial.<扩展名>，扩展名保持不变以便选择封装格式），
+// 校验通过后再重命名为目标文件。失败、中断或删除任务时只删除临时文件，已有的同名输出保持不变。
+
+// 输出时长与预期相差超过该值（秒）或 5% 时视为不完整
+const DURATION_TOLERANCE_SECONDS: f64 = 2.0;
+const DURATION_TOLERANCE_RATIO: f64 = 0.05;
+
+/// 输出文件对应的临时文件路径
+pub fn partial_output_path(output_path: &str) -> String {
+    let path = Path::new(output_path);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!(".{}.compressgo-partial.{}", stem, ext),
+        None => format!(".{}.compressgo-partial", stem),
+    };
+    dir.join(name).to_string_lossy().to_string()
+}
+
+/// 临时输出文件。未提交时在释放时删除（包括出错返回与任务被删除的情况）
+pub struct PartialOutput {
+    path: String,
+    committed: bool,
+}
+
+impl PartialOutput {
+    pub fn new(output_path: &str) -> Self {
+        let path = partial_output_path(output_path);
+        // 清理上次崩溃遗留的临时文件
+        let _ = std::fs::remove_file(&path);
+        Self { path, committed: false }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 重命名为目标文件，返回实际写入的路径。目标已存在时按同名策略处理（见 naming::resolve_collision）
+    pub fn commit(&mut self, output_path: &str, collision: Option<&str>) -> Result<String, String> {
+        let target = resolve_collision(output_path, collision)?;
+        std::fs::rename(&self.path, &target)
+            .map_err(|e| format!("Failed to move {} to {}: {}", self.path, target, e))?;
+        self.committed = true;
+        Ok(target)
+    }
+}
+
+impl Drop for PartialOutput {
+    fn drop(&mut self) {
+        if !self.committed && Path::new(&self.path).exists() {
+            match std::fs::remove_file(&self.path) {
+                Ok(()) => println!("🧹 Removed partial output {}", self.path),
+                Err(e) => println!("Failed to remove partial output {}: {}", self.path, e),
+            }
+        }
+    }
+}
+
+/// 校验输出：非空、ffprobe 能识别出流，给定预期时长时实际时长需在误差范围内
+pub async fn validate_output(ffprobe_path: &Path, path: &str, expected_duration: Option<f64>) -> Result<(), String> {
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size == 0 {
+        return Err("Output file is missing or empty".to_string());
+    }
+    let output = tokio_command_with_no_window(ffprobe_path)
+        .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams", path])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Output failed validation: {}",
+            stderr_tail(&String::from_utf8_lossy(&output.stderr), 3)
+        ));
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Output failed validation: {}", e))?;
+    if json["streams"].as_array().is_none_or(|s| s.is_empty()) {
+        return Err("Output failed validation: no streams found".to_string());
+    }
+    if let Some(expected) = expected_duration.filter(|d| *d > 0.0) {
+        // 部分封装格式不记录时长，无法比较时跳过
+        if let Some(actual) = json["format"]["duration"].as_str().and_then(|d| d.parse::<f64>().ok()) {
+            let tolerance = DURATION_TOLERANCE_SECONDS.max(expected * DURATION_TOLERANCE_RATIO);
+            if (actual - expected).abs() > tolerance {
+                return Err(format!(
+                    "Output failed validation: duration {:.2}s differs from the expected {:.2}s",
+                    actual, expected
+                ));
+            }
+        }
+    }
+    Ok(())
+}
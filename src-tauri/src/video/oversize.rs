@@ -0,0 +1,96 @@
+use std::path::Path;
+use crate::video::events::{CompressionEvent, SharedEventSink};
+use crate::video::naming::resolve_collision;
+use crate::video::types::CompressionSettings;
+
+// 输出不小于原文件时的处理策略：
+//   keep           保留输出（默认）
+//   discard        删除输出，结果指向原文件
+//   copy_original  删除输出，把原文件复制到输出目录（扩展名沿用原文件；目标已存在时按同名策略处理）
+// 无论哪种策略，结果状态都标记为 no_gain 并发送 compression-no-gain 事件。
+
+pub const STATUS_COMPRESSED: &str = "compressed";
+pub const STATUS_NO_GAIN: &str = "no_gain";
+
+fn oversize_policy(settings: &CompressionSettings) -> &str {
+    settings.oversize_policy.as_deref().filter(|p| !p.is_empty()).unwrap_or("keep")
+}
+
+/// 在压缩开始前校验策略取值
+pub fn validate_oversize_policy(settings: &CompressionSettings) -> Result<(), String> {
+    match oversize_policy(settings) {
+        "keep" | "discard" | "copy_original" => Ok(()),
+        other => Err(format!("Unknown oversize policy: {}", other)),
+    }
+}
+
+// 输出没有变小时最终交给用户的文件
+pub struct OversizeOutcome {
+    pub output_path: String,
+    pub size: u64,
+    // 是否保留编码结果（keep）；否则编码结果已删除
+    pub kept_encoded: bool,
+}
+
+/// 输出不小于原文件时按策略处理并发送 NoGain 事件；输出变小时返回 None。
+/// encoded_path 为编码结果（临时文件），output_path 为目标路径
+#[allow(clippy::too_many_arguments)]
+pub fn apply_oversize_policy(
+    task_id: &str,
+    settings: &CompressionSettings,
+    input_path: &str,
+    encoded_path: &str,
+    output_path: &str,
+    original_size: u64,
+    compressed_size: Option<u64>,
+    sink: &SharedEventSink,
+) -> Result<Option<OversizeOutcome>, String> {
+    let Some(compressed_size) = compressed_size.filter(|size| *size >= original_size) else { return Ok(None) };
+    let policy = oversize_policy(settings);
+    println!(
+        "📏 Output is not smaller than the input ({} >= {} bytes), policy: {}",
+        compressed_size, original_size, policy
+    );
+
+    let outcome = match policy {
+        "discard" => {
+            remove_output(encoded_path, input_path)?;
+            OversizeOutcome { output_path: input_path.to_string(), size: original_size, kept_encoded: false }
+        }
+        "copy_original" => {
+            let output = Path::new(output_path);
+            let target = match Path::new(input_path).extension() {
+                Some(ext) => output.with_extension(ext),
+                None => output.to_path_buf(),
+            };
+            remove_output(encoded_path, input_path)?;
+            // 目标就是原文件时不复制；否则不直接覆盖已有文件（如原扩展名的另一个同名文件）
+            let target = if target == Path::new(input_path) {
+                input_path.to_string()
+            } else {
+                let target = resolve_collision(&target.to_string_lossy(), settings.collision.as_deref())?;
+                std::fs::copy(input_path, &target)
+                    .map_err(|e| format!("Failed to copy original to {}: {}", target, e))?;
+                target
+            };
+            OversizeOutcome { output_path: target, size: original_size, kept_encoded: false }
+        }
+        _ => OversizeOutcome { output_path: output_path.to_string(), size: compressed_size, kept_encoded: true },
+    };
+
+    sink.emit(task_id, CompressionEvent::NoGain {
+        output_path: outcome.output_path.clone(),
+        original_size,
+        compressed_size,
+        policy: policy.to_string(),
+    });
+    Ok(Some(outcome))
+}
+
+// 删除没有变小的编码结果；与原文件是同一路径时保留
+fn remove_output(encoded_path: &str, input_path: &str) -> Result<(), String> {
+    if encoded_path == input_path || !Path::new(encoded_path).exists() {
+        return Ok(());
+    }
+    std::fs::remove_file(encoded_path).map_err(|e| format!("Failed to remove output {}: {}", encoded_path, e))
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use crate::video::compression::{
+    build_input_args, build_video_encode_args, run_ffmpeg_tracked, track_temp_dirs, VideoJob,
+};
+use crate::video::filters::reference_filters;
+use crate::video::resume::remove_temp_dir;
+use crate::video::types::{CompressionSettings, QualityMetrics};
+use crate::video::utils::command_with_no_window;
+
+// 画质评估：基于 FFmpeg 的 libvmaf / ssim / psnr 滤镜比较输出与源视频。
+// 自动 CRF 在若干短样片上二分查找满足目标得分的最大 CRF。
+
+// 自动 CRF 时样片搜索占总进度的比例
+pub const CRF_SEARCH_PROGRESS_SHARE: f64 = 25.0;
+// 编码后画质评估占总进度的比例
+pub const METRICS_PROGRESS_SHARE: f64 = 15.0;
+const DEFAULT_SAMPLES: u8 = 3;
+const DEFAULT_SAMPLE_DURATION: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityMetric {
+    Vmaf,
+    Ssim,
+    Psnr,
+}
+
+impl QualityMetric {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "vmaf" => Some(QualityMetric::Vmaf),
+            "ssim" => Some(QualityMetric::Ssim),
+            "psnr" => Some(QualityMetric::Psnr),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QualityMetric::Vmaf => "vmaf",
+            QualityMetric::Ssim => "ssim",
+            QualityMetric::Psnr => "psnr",
+        }
+    }
+
+    fn filter(self) -> &'static str {
+        match self {
+            QualityMetric::Vmaf => "libvmaf",
+            QualityMetric::Ssim => "ssim",
+            QualityMetric::Psnr => "psnr",
+        }
+    }
+
+    // 从 FFmpeg stderr 中解析最终得分：
+    //   VMAF score: 95.43
+    //   SSIM Y:0.98 U:0.99 V:0.99 All:0.985 (18.2)
+    //   PSNR y:41.2 u:44.0 v:44.5 average:42.0 min:38.1 max:48.3
+    pub fn parse_score(self, stderr: &str) -> Option<f64> {
+        let (marker, key) = match self {
+            QualityMetric::Vmaf => ("VMAF score", "VMAF score:"),
+            QualityMetric::Ssim => ("SSIM Y:", "All:"),
+            QualityMetric::Psnr => ("PSNR y:", "average:"),
+        };
+        let line = stderr.lines().rev().find(|l| l.contains(marker))?;
+        let value = &line[line.find(key)? + key.len()..];
+        let token = value.split_whitespace().next()?;
+        if token == "inf" {
+            // 完全相同的画面 PSNR 为无穷大，按 100 dB 处理
+            return Some(100.0);
+        }
+        token.parse::<f64>().ok()
+    }
+}
+
+pub struct CrfChoice {
+    pub crf: u8,
+    pub metric: String,
+    pub score: f64,
+}
+
+fn filter_cache() -> &'static Mutex<HashMap<(PathBuf, String), bool>> {
+    static CACHE: OnceLock<Mutex<HashMap<(PathBuf, String), bool>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 当前 FFmpeg 是否带指定滤镜（结果按路径缓存）
+pub fn has_filter(ffmpeg_path: &Path, name: &str) -> bool {
+    let key = (ffmpeg_path.to_path_buf(), name.to_string());
+    if let Some(cached) = filter_cache().lock().unwrap().get(&key) {
+        return *cached;
+    }
+    let available = command_with_no_window(ffmpeg_path)
+        .args(["-hide_banner", "-filters"])
+        .output()
+        .map(|out| {
+            String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .any(|l| l.split_whitespace().nth(1) == Some(name))
+        })
+        .unwrap_or(false);
+    println!("[Filters] {} available: {}", name, available);
+    filter_cache().lock().unwrap().insert(key, available);
+    available
+}
+
+/// 当前 FFmpeg 是否带 libvmaf 滤镜
+pub fn has_libvmaf(ffmpeg_path: &Path) -> bool {
+    has_filter(ffmpeg_path, "libvmaf")
+}
+
+/// 比较滤镜图：输入 0 为待评估视频，输入 1 为参考源。参考源先经过与编码相同的帧率与几何滤镜（裁剪、旋转），
+/// 待评估视频缩放到参考分辨率后逐个计算指标
+pub fn comparison_graph(metrics: &[QualityMetric], reference_filters: &[String]) -> String {
+    let (prefix, reference) = if reference_filters.is_empty() {
+        (String::new(), "[1:v]")
+    } else {
+        (format!("[1:v]{}[src];", reference_filters.join(",")), "[src]")
+    };
+    let mut graph = format!(
+        "{}[0:v]{}scale2ref=flags=bicubic[d0][r0];\
+         [d0]setpts=PTS-STARTPTS,format=yuv420p[dist];\
+         [r0]setpts=PTS-STARTPTS,format=yuv420p[ref]",
+        prefix, reference
+    );
+    if metrics.len() == 1 {
+        graph.push_str(&format!(";[dist][ref]{}", metrics[0].filter()));
+        return graph;
+    }
+    let labels: Vec<usize> = (0..metrics.len()).collect();
+    let split = |prefix: &str| labels.iter().map(|i| format!("[{}{}]", prefix, i)).collect::<String>();
+    graph.push_str(&format!(";[dist]split={}{}", metrics.len(), split("d")));
+    graph.push_str(&format!(";[ref]split={}{}", metrics.len(), split("r")));
+    for (i, m) in metrics.iter().enumerate() {
+        graph.push_str(&format!(";[d{}][r{}]{}", i, i, m.filter()));
+    }
+    graph
+}
+
+pub(crate) fn stderr_tail(stderr: &str, lines: usize) -> String {
+    let all: Vec<&str> = stderr.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+// 支持 CRF 搜索的软件编码器及其默认搜索范围
+fn crf_search_range(ffmpeg_codec: &str) -> Option<(u8, u8)> {
+    match ffmpeg_codec {
+        "libx264" | "libx265" => Some((16, 40)),
+        "libvpx-vp9" => Some((15, 50)),
+        "libsvtav1" => Some((20, 55)),
+        _ => None,
+    }
+}
+
+// 在时长范围内均匀选取样片起点
+fn sample_windows(start: f64, duration: f64, count: u8, sample_duration: f64) -> Vec<(f64, f64)> {
+    if duration <= sample_duration * count as f64 {
+        return vec![(start, duration)];
+    }
+    (0..count)
+        .map(|i| {
+            let center = start + duration * (i as f64 + 1.0) / (count as f64 + 1.0);
+            ((center - sample_duration / 2.0).max(start), sample_duration)
+        })
+        .collect()
+}
+
+/// 自动 CRF：对样片用不同 CRF 编码并与源比较，二分查找得分不低于目标的最大 CRF。
+/// 达不到目标时返回搜索范围内的最小 CRF
+pub(crate) async fn search_crf(job: &VideoJob<'_>, settings: &CompressionSettings) -> Result<CrfChoice, String> {
+    let target = settings
+        .quality_target
+        .as_ref()
+        .ok_or_else(|| "quality_type \"auto_crf\" requires quality_target".to_string())?;
+    let metric = match QualityMetric::parse(&target.metric) {
+        Some(m @ (QualityMetric::Vmaf | QualityMetric::Ssim)) => m,
+        _ => return Err(format!("Unsupported quality metric for CRF search: {}", target.metric)),
+    };
+    if metric == QualityMetric::Vmaf && !has_libvmaf(job.ffmpeg_path) {
+        return Err("This FFmpeg build has no libvmaf filter; use the ssim metric instead".to_string());
+    }
+
+    let mut crf_settings = settings.clone();
+    crf_settings.quality_type = "crf".to_string();
+    let (ffmpeg_codec, _) = build_video_encode_args(&crf_settings, job.hardware_support, job.output_path, None, job.hdr.as_ref());
+    let (default_min, default_max) = crf_search_range(&ffmpeg_codec)
+        .ok_or_else(|| format!("Automatic CRF search is not supported for encoder {}", ffmpeg_codec))?;
+    let min_crf = target.min_crf.unwrap_or(default_min);
+    let max_crf = target.max_crf.unwrap_or(default_max).max(min_crf);
+
+    let samples = sample_windows(
+        job.start_time.unwrap_or(0.0),
+        job.duration,
+        target.samples.unwrap_or(DEFAULT_SAMPLES).max(1),
+        target.sample_duration.unwrap_or(DEFAULT_SAMPLE_DURATION).max(1.0),
+    );
+    let sample_dir = std::env::temp_dir().join("compressgo-samples").join(job.task_id);
+    std::fs::create_dir_all(&sample_dir).map_err(|e| format!("Failed to create sample dir {:?}: {}", sample_dir, e))?;
+    track_temp_dirs(job.task_id, vec![sample_dir.clone()]).await?;
+
+    println!(
+        "🔎 CRF search {}..={} with {} on {} samples, target {} {}",
+        min_crf,
+        max_crf,
+        ffmpeg_codec,
+        samples.len(),
+        metric.name(),
+        target.score
+    );
+    // 最多 log2(范围) 轮，每轮每个样片编码 + 评估各一次
+    let rounds = (max_crf - min_crf + 1).next_power_of_two().trailing_zeros() as usize + 1;
+    let total_steps = rounds * samples.len() * 2;
+    let graph = comparison_graph(&[metric], &reference_filters(settings, job.hdr.as_ref()));
+
+    let mut step = 0usize;
+    let mut scores: HashMap<u8, f64> = HashMap::new();
+    let mut best: Option<u8> = None;
+    let (mut lo, mut hi) = (min_crf as i32, max_crf as i32);
+    let outcome: Result<(), String> = async {
+        while lo <= hi {
+            let crf = ((lo + hi) / 2) as u8;
+            crf_settings.crf_value = Some(crf);
+            let (_, encode_args) = build_video_encode_args(&crf_settings, job.hardware_support, job.output_path, None, job.hdr.as_ref());
+            let mut total = 0.0;
+            for (i, (start, duration)) in samples.iter().enumerate() {
+                let to_overall = move |p: f64| ((step as f64 + p / 100.0) / total_steps as f64 * 100.0).min(100.0);
+                let sample_path = sample_dir.join(format!("sample_{}_{}.mp4", crf, i)).to_string_lossy().to_string();
+                let mut args = build_input_args(job.input_path, Some(*start), Some(*duration));
+                args.extend(encode_args.iter().cloned());
+                for arg in ["-an", "-sn", "-y", sample_path.as_str(), "-progress", "pipe:1"] {
+                    args.push(arg.to_string());
+                }
+                let run = run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, *duration, &job.display_name, job.sink, to_overall).await?;
+                if !run.status.success() {
+                    return Err(format!("Sample encode failed at CRF {}: {}", crf, stderr_tail(&run.stderr, 5)));
+                }
+                step += 1;
+
+                let to_overall = move |p: f64| ((step as f64 + p / 100.0) / total_steps as f64 * 100.0).min(100.0);
+                let mut args: Vec<String> = vec!["-hide_banner".into(), "-nostats".into(), "-i".into(), sample_path.clone()];
+                args.extend(build_input_args(job.input_path, Some(*start), Some(*duration)));
+                for arg in ["-lavfi", graph.as_str(), "-f", "null", "-", "-progress", "pipe:1"] {
+                    args.push(arg.to_string());
+                }
+                let run = run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, *duration, &job.display_name, job.sink, to_overall).await?;
+                let score = metric
+                    .parse_score(&run.stderr)
+                    .ok_or_else(|| format!("Failed to read {} score: {}", metric.name(), stderr_tail(&run.stderr, 5)))?;
+                total += score;
+                step += 1;
+                let _ = std::fs::remove_file(&sample_path);
+            }
+            let score = total / samples.len() as f64;
+            println!("🔎 CRF {} -> {} {:.4}", crf, metric.name(), score);
+            scores.insert(crf, score);
+            if score >= target.score {
+                best = Some(crf);
+                lo = crf as i32 + 1;
+            } else {
+                hi = crf as i32 - 1;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    remove_temp_dir(&sample_dir);
+    outcome?;
+
+    let crf = match best {
+        Some(crf) => crf,
+        None => {
+            println!("⚠️ Quality target {} not reachable, using CRF {}", target.score, min_crf);
+            min_crf
+        }
+    };
+    let score = scores.get(&crf).copied().unwrap_or(0.0);
+    println!("✅ CRF search chose {} ({} {:.4})", crf, metric.name(), score);
+    Ok(CrfChoice { crf, metric: metric.name().to_string(), score })
+}
+
+/// 编码后画质评估：在压缩的时间范围内比较输出与源视频，计算 PSNR、SSIM，以及可用时的 VMAF
+pub(crate) async fn measure_quality(job: &VideoJob<'_>, settings: &CompressionSettings, output_path: &str) -> Result<QualityMetrics, String> {
+    let with_vmaf = has_libvmaf(job.ffmpeg_path);
+    let mut metrics = vec![QualityMetric::Psnr, QualityMetric::Ssim];
+    if with_vmaf {
+        metrics.push(QualityMetric::Vmaf);
+    }
+    let graph = comparison_graph(&metrics, &reference_filters(settings, job.hdr.as_ref()));
+
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-nostats".into(), "-i".into(), output_path.to_string()];
+    args.extend(build_input_args(job.input_path, job.start_time, job.window));
+    for arg in ["-lavfi", graph.as_str(), "-f", "null", "-", "-progress", "pipe:1"] {
+        args.push(arg.to_string());
+    }
+    println!("📏 Measuring quality ({}) for {}", metrics.iter().map(|m| m.name()).collect::<Vec<_>>().join(", "), job.display_name);
+    let run = run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, job.duration, &job.display_name, job.sink, |p| p).await?;
+    if !run.status.success() {
+        return Err(format!("Quality measurement failed: {}", stderr_tail(&run.stderr, 5)));
+    }
+    let result = QualityMetrics {
+        psnr: QualityMetric::Psnr.parse_score(&run.stderr),
+        ssim: QualityMetric::Ssim.parse_score(&run.stderr),
+        vmaf: if with_vmaf { QualityMetric::Vmaf.parse_score(&run.stderr) } else { None },
+    };
+    println!("📏 Quality: PSNR {:?} SSIM {:?} VMAF {:?}", result.psnr, result.ssim, result.vmaf);
+    Ok(result)
+}
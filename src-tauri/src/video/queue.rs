@@ -0,0 +1,634 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use serde::{Deserialize, Serialize};
+use crate::video::compression::cancel_job;
+use crate::video::engine::{run_job, CompressionJob, JobKind, JobOptions};
+use crate::video::events::{CompressionEvent, EventSink, NullEventSink, SharedEventSink};
+use crate::video::history::now_millis;
+use crate::video::naming::{plan_output_paths, OutputNaming};
+use crate::video::types::CompressionResult;
+use crate::video::utils::get_hardware_encoder_support;
+
+// 后端任务队列：批量任务由 Rust 侧调度，webview 刷新后队列依旧继续执行。
+// 视频与图片分别限制并发数；同一优先级按队列顺序执行，优先级高的先执行。
+// 队列在每次状态变化时写入 app_local_data_dir/task_queue.json，启动时恢复未完成的任务。
+
+const DEFAULT_VIDEO_CONCURRENCY: usize = 1;
+const DEFAULT_IMAGE_CONCURRENCY: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+    // 输出已存在且同名策略为 skip，未执行
+    Skipped,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Skipped)
+    }
+
+    // 占用并发名额的状态（暂停的进程仍然存在）
+    fn is_active(self) -> bool {
+        matches!(self, JobStatus::Running | JobStatus::Paused)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QueuedJob {
+    #[serde(flatten)]
+    pub job: CompressionJob,
+    pub priority: i32,
+    pub status: JobStatus,
+    pub progress: f64,
+    #[serde(rename = "enqueuedAt")]
+    pub enqueued_at: u64,
+    #[serde(rename = "startedAt")]
+    pub started_at: Option<u64>,
+    #[serde(rename = "finishedAt")]
+    pub finished_at: Option<u64>,
+    pub result: Option<CompressionResult>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    options: JobOptions,
+    // 任务流程仍在执行（取消后到流程真正返回之前仍占用并发名额）
+    #[serde(skip)]
+    running: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub jobs: Vec<QueuedJob>,
+    #[serde(rename = "videoConcurrency")]
+    pub video_concurrency: usize,
+    #[serde(rename = "imageConcurrency")]
+    pub image_concurrency: usize,
+    pub paused: bool,
+}
+
+struct QueueState {
+    jobs: Vec<QueuedJob>,
+    video_concurrency: usize,
+    image_concurrency: usize,
+    paused: bool,
+    sink: SharedEventSink,
+    store_path: Option<PathBuf>,
+}
+
+impl QueueState {
+    fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            jobs: self.jobs.clone(),
+            video_concurrency: self.video_concurrency,
+            image_concurrency: self.image_concurrency,
+            paused: self.paused,
+        }
+    }
+
+    fn find_mut(&mut self, task_id: &str) -> Option<&mut QueuedJob> {
+        self.jobs.iter_mut().find(|j| j.job.task_id == task_id)
+    }
+
+    // 选出下一个可以启动的任务：优先级最高者，相同优先级取队列中靠前者
+    fn next_runnable(&self) -> Option<usize> {
+        let running = |kind: JobKind| {
+            self.jobs.iter().filter(|j| j.job.kind == kind && (j.status.is_active() || j.running)).count()
+        };
+        let video_free = running(JobKind::Video) < self.video_concurrency;
+        let image_free = running(JobKind::Image) < self.image_concurrency;
+
+        let mut best: Option<usize> = None;
+        for (idx, j) in self.jobs.iter().enumerate() {
+            if j.status != JobStatus::Queued {
+                continue;
+            }
+            let free = match j.job.kind {
+                JobKind::Video => video_free,
+                JobKind::Image => image_free,
+            };
+            if !free {
+                continue;
+            }
+            if best.is_none_or(|b| j.priority > self.jobs[b].priority) {
+                best = Some(idx);
+            }
+        }
+        best
+    }
+}
+
+fn get_queue() -> &'static Arc<Mutex<QueueState>> {
+    static QUEUE: OnceLock<Arc<Mutex<QueueState>>> = OnceLock::new();
+    QUEUE.get_or_init(|| {
+        Arc::new(Mutex::new(QueueState {
+            jobs: Vec::new(),
+            video_concurrency: DEFAULT_VIDEO_CONCURRENCY,
+            image_concurrency: DEFAULT_IMAGE_CONCURRENCY,
+            paused: false,
+            sink: Arc::new(NullEventSink),
+            store_path: None,
+        }))
+    })
+}
+
+// 广播当前队列状态并持久化
+fn emit_queue_state() {
+    let (sink, snapshot, store_path) = {
+        let state = get_queue().lock().unwrap();
+        (state.sink.clone(), state.snapshot(), state.store_path.clone())
+    };
+    if let Some(path) = store_path {
+        save_queue(&path, &snapshot);
+    }
+    sink.emit_queue_state(&snapshot);
+}
+
+fn save_queue(path: &PathBuf, snapshot: &QueueSnapshot) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    match serde_json::to_string(snapshot) {
+        Ok(data) => {
+            if let Err(e) = std::fs::write(path, data) {
+                println!("❌ Failed to save task queue to {:?}: {}", path, e);
+            }
+        }
+        Err(e) => println!("❌ Failed to serialize task queue: {}", e),
+    }
+}
+
+// 包装任务事件：转发的同时把进度与暂停状态记录到队列中
+struct QueueJobSink {
+    inner: SharedEventSink,
+}
+
+impl EventSink for QueueJobSink {
+    fn emit(&self, task_id: &str, event: CompressionEvent) {
+        let status_changed = {
+            let mut state = get_queue().lock().unwrap();
+            match (state.find_mut(task_id), &event) {
+                (Some(j), CompressionEvent::Progress { progress }) => {
+                    j.progress = *progress;
+                    false
+                }
+                (Some(j), CompressionEvent::Paused) if j.status == JobStatus::Running => {
+                    j.status = JobStatus::Paused;
+                    true
+                }
+                (Some(j), CompressionEvent::Resumed) if j.status == JobStatus::Paused => {
+                    j.status = JobStatus::Running;
+                    true
+                }
+                _ => false,
+            }
+        };
+        self.inner.emit(task_id, event);
+        if status_changed {
+            emit_queue_state();
+        }
+    }
+}
+
+/// 初始化队列：设置事件接收端（任务事件与队列状态事件都通过它发出），
+/// 并从 data_dir 恢复上次未完成的任务。运行中/暂停中的任务会重新排队，返回恢复的任务数。
+/// 恢复的任务不会自动启动，需调用 dispatch 或 start_restored_jobs
+pub fn init_job_queue(sink: SharedEventSink, data_dir: Option<PathBuf>) -> usize {
+    let store_path = data_dir.map(|dir| dir.join("task_queue.json"));
+    let saved = store_path
+        .as_ref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|data| serde_json::from_str::<QueueSnapshot>(&data).ok());
+
+    let mut state = get_queue().lock().unwrap();
+    state.sink = sink;
+    state.store_path = store_path;
+    let Some(saved) = saved else { return 0 };
+    state.video_concurrency = saved.video_concurrency.max(1);
+    state.image_concurrency = saved.image_concurrency.max(1);
+    state.paused = saved.paused;
+    let mut restored = 0;
+    for mut j in saved.jobs {
+        if state.jobs.iter().any(|existing| existing.job.task_id == j.job.task_id) {
+            continue;
+        }
+        if !j.status.is_finished() {
+            if j.status.is_active() {
+                j.error = Some("Interrupted by application exit".to_string());
+            }
+            j.status = JobStatus::Queued;
+            j.progress = 0.0;
+            j.started_at = None;
+            restored += 1;
+        }
+        state.jobs.push(j);
+    }
+    println!("📥 Restored {} pending tasks from queue store", restored);
+    restored
+}
+
+// Windows 下 GPU 视频编码需要已检测的硬件编码器信息
+fn needs_hardware_support(job: &CompressionJob) -> bool {
+    cfg!(target_os = "windows")
+        && job.kind == JobKind::Video
+        && job.settings.hardware_acceleration.as_deref() == Some("gpu")
+}
+
+/// 启动恢复的任务：补齐硬件编码器信息后开始调度
+pub async fn start_restored_jobs(app_handle: tauri::AppHandle) {
+    let needs_hardware = get_queue()
+        .lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .any(|j| j.status == JobStatus::Queued && needs_hardware_support(&j.job));
+    if needs_hardware {
+        if let Ok(hardware_support) = get_hardware_encoder_support(app_handle) {
+            let mut state = get_queue().lock().unwrap();
+            for j in state.jobs.iter_mut().filter(|j| j.status == JobStatus::Queued && needs_hardware_support(&j.job)) {
+                j.options.hardware_support = Some(hardware_support.clone());
+            }
+        }
+    }
+    emit_queue_state();
+    dispatch();
+}
+
+/// 将任务加入队列。已结束的同名任务会被替换，未结束的同名任务返回错误
+pub fn enqueue_job(job: CompressionJob, priority: i32, options: JobOptions) -> Result<(), String> {
+    push_job(job, priority, options, None)?;
+    emit_queue_state();
+    dispatch();
+    Ok(())
+}
+
+/// 记录一个因输出已存在而跳过的任务（直接处于 skipped 状态，不会执行）
+pub fn skip_job(job: CompressionJob, priority: i32) -> Result<(), String> {
+    println!("⏭️ Skipped task {}: {} already exists", job.task_id, job.output_path);
+    let reason = format!("Output already exists: {}", job.output_path);
+    push_job(job, priority, JobOptions::default(), Some(reason))?;
+    emit_queue_state();
+    Ok(())
+}
+
+// skipped 为跳过原因；为 None 时以等待状态入队
+fn push_job(job: CompressionJob, priority: i32, options: JobOptions, skipped: Option<String>) -> Result<(), String> {
+    {
+        let mut state = get_queue().lock().unwrap();
+        if let Some(pos) = state.jobs.iter().position(|j| j.job.task_id == job.task_id) {
+            if !state.jobs[pos].status.is_finished() {
+                return Err(format!("Task {} is already queued", job.task_id));
+            }
+            state.jobs.remove(pos);
+        }
+        if skipped.is_none() {
+            println!("📥 Enqueued {:?} task: {} (priority {})", job.kind, job.task_id, priority);
+        }
+        let now = now_millis();
+        state.jobs.push(QueuedJob {
+            job,
+            priority,
+            status: if skipped.is_some() { JobStatus::Skipped } else { JobStatus::Queued },
+            progress: 0.0,
+            enqueued_at: now,
+            started_at: None,
+            finished_at: skipped.as_ref().map(|_| now),
+            result: None,
+            error: skipped,
+            options,
+            running: false,
+        });
+    }
+    Ok(())
+}
+
+/// 启动所有可以运行的任务
+pub fn dispatch() {
+    let (to_start, sink) = {
+        let mut state = get_queue().lock().unwrap();
+        let mut to_start = Vec::new();
+        if !state.paused {
+            while let Some(idx) = state.next_runnable() {
+                let j = &mut state.jobs[idx];
+                j.status = JobStatus::Running;
+                j.running = true;
+                j.started_at = Some(now_millis());
+                to_start.push((j.job.clone(), j.options.clone()));
+            }
+        }
+        (to_start, state.sink.clone())
+    };
+    if to_start.is_empty() {
+        return;
+    }
+    for (job, options) in to_start {
+        println!("▶️ Starting queued task: {}", job.task_id);
+        tokio::spawn(run_queued_job(job, options, sink.clone()));
+    }
+    emit_queue_state();
+}
+
+async fn run_queued_job(job: CompressionJob, options: JobOptions, sink: SharedEventSink) {
+    let task_id = job.task_id.clone();
+    let outcome = run_job(job, Arc::new(QueueJobSink { inner: sink }), options).await;
+    {
+        let mut state = get_queue().lock().unwrap();
+        if let Some(j) = state.find_mut(&task_id).filter(|j| j.running) {
+            j.running = false;
+            // 已取消的任务保持取消状态，不被 "Process was interrupted" 覆盖
+            if j.status != JobStatus::Cancelled {
+                match outcome {
+                    Ok(result) => {
+                        j.status = if result.success { JobStatus::Completed } else { JobStatus::Failed };
+                        if result.success {
+                            j.progress = 100.0;
+                        }
+                        j.error = result.error.clone();
+                        j.result = Some(result);
+                    }
+                    Err(e) => {
+                        j.status = JobStatus::Failed;
+                        j.error = Some(e);
+                    }
+                }
+            }
+            j.finished_at = Some(now_millis());
+        }
+    }
+    println!("🏁 Queued task finished: {}", task_id);
+    emit_queue_state();
+    dispatch();
+}
+
+/// 当前队列状态
+pub fn queue_snapshot() -> QueueSnapshot {
+    get_queue().lock().unwrap().snapshot()
+}
+
+/// 按给定顺序重排任务；未列出的任务保持原有相对顺序排在其后
+pub fn reorder_jobs(task_ids: &[String]) {
+    {
+        let mut state = get_queue().lock().unwrap();
+        let mut ordered = Vec::with_capacity(state.jobs.len());
+        for id in task_ids {
+            if let Some(pos) = state.jobs.iter().position(|j| &j.job.task_id == id) {
+                ordered.push(state.jobs.remove(pos));
+            }
+        }
+        ordered.append(&mut state.jobs);
+        state.jobs = ordered;
+    }
+    emit_queue_state();
+}
+
+pub fn set_job_priority(task_id: &str, priority: i32) -> Result<(), String> {
+    {
+        let mut state = get_queue().lock().unwrap();
+        let j = state.find_mut(task_id).ok_or_else(|| format!("Task {} not found in queue", task_id))?;
+        j.priority = priority;
+    }
+    emit_queue_state();
+    Ok(())
+}
+
+/// 调整并发数（至少为 1），调大后会立即启动等待中的任务
+pub fn set_concurrency(video: Option<usize>, image: Option<usize>) {
+    {
+        let mut state = get_queue().lock().unwrap();
+        if let Some(v) = video {
+            state.video_concurrency = v.max(1);
+        }
+        if let Some(i) = image {
+            state.image_concurrency = i.max(1);
+        }
+    }
+    emit_queue_state();
+    dispatch();
+}
+
+/// 暂停/恢复调度。暂停只阻止新任务启动，不影响正在运行的任务
+pub fn set_queue_paused(paused: bool) {
+    get_queue().lock().unwrap().paused = paused;
+    emit_queue_state();
+    if !paused {
+        dispatch();
+    }
+}
+
+/// 标记任务为已取消，返回其原先的状态
+pub fn mark_job_cancelled(task_id: &str) -> Option<JobStatus> {
+    let previous = {
+        let mut state = get_queue().lock().unwrap();
+        let j = state.find_mut(task_id)?;
+        let previous = j.status;
+        if !previous.is_finished() {
+            j.status = JobStatus::Cancelled;
+            j.finished_at = Some(now_millis());
+        }
+        previous
+    };
+    emit_queue_state();
+    Some(previous)
+}
+
+/// 取消队列中的任务：等待中的直接标记取消，运行中的同时终止 FFmpeg 进程
+pub async fn cancel_queued_job(task_id: &str) -> Result<(), String> {
+    match mark_job_cancelled(task_id) {
+        Some(status) if status.is_active() => {
+            cancel_job(task_id).await?;
+            dispatch();
+            Ok(())
+        }
+        Some(_) => Ok(()),
+        None => Err(format!("Task {} not found in queue", task_id)),
+    }
+}
+
+/// 移除已结束的任务记录
+pub fn clear_finished_jobs() {
+    get_queue().lock().unwrap().jobs.retain(|j| !j.status.is_finished());
+    emit_queue_state();
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnqueueJobRequest {
+    #[serde(flatten)]
+    pub job: CompressionJob,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// naming 不为空时由后端按模板生成 outputPath（忽略请求中的 outputPath），目标已存在且策略为 skip 的任务记为 skipped
+#[tauri::command]
+pub async fn enqueue_compression_jobs(
+    mut jobs: Vec<EnqueueJobRequest>,
+    naming: Option<OutputNaming>,
+    app_handle: tauri::AppHandle,
+) -> Result<QueueSnapshot, String> {
+    let mut skipped = vec![false; jobs.len()];
+    if let Some(naming) = &naming {
+        let batch: Vec<CompressionJob> = jobs.iter().map(|r| r.job.clone()).collect();
+        for (i, planned) in plan_output_paths(&batch, naming)?.into_iter().enumerate() {
+            jobs[i].job.output_path = planned.output_path;
+            jobs[i].job.settings.collision = naming.collision.clone();
+            skipped[i] = planned.skipped;
+        }
+    }
+
+    // 硬件编码器信息只检测一次
+    let hardware_support = if jobs.iter().any(|r| needs_hardware_support(&r.job)) {
+        get_hardware_encoder_support(app_handle.clone()).ok()
+    } else {
+        None
+    };
+    for (request, skipped) in jobs.into_iter().zip(skipped) {
+        if skipped {
+            skip_job(request.job, request.priority)?;
+            continue;
+        }
+        let options = JobOptions {
+            hardware_support: if needs_hardware_support(&request.job) { hardware_support.clone() } else { None },
+        };
+        enqueue_job(request.job, request.priority, options)?;
+    }
+    Ok(queue_snapshot())
+}
+
+#[tauri::command]
+pub async fn get_queue_state() -> Result<QueueSnapshot, String> {
+    Ok(queue_snapshot())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn reorder_queue(taskIds: Vec<String>) -> Result<QueueSnapshot, String> {
+    reorder_jobs(&taskIds);
+    Ok(queue_snapshot())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn set_queue_job_priority(taskId: String, priority: i32) -> Result<QueueSnapshot, String> {
+    set_job_priority(&taskId, priority)?;
+    Ok(queue_snapshot())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn set_queue_concurrency(videoConcurrency: Option<usize>, imageConcurrency: Option<usize>) -> Result<QueueSnapshot, String> {
+    set_concurrency(videoConcurrency, imageConcurrency);
+    Ok(queue_snapshot())
+}
+
+#[tauri::command]
+pub async fn pause_queue() -> Result<QueueSnapshot, String> {
+    set_queue_paused(true);
+    Ok(queue_snapshot())
+}
+
+#[tauri::command]
+pub async fn resume_queue() -> Result<QueueSnapshot, String> {
+    set_queue_paused(false);
+    Ok(queue_snapshot())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn cancel_queue_job(taskId: String) -> Result<QueueSnapshot, String> {
+    cancel_queued_job(&taskId).await?;
+    Ok(queue_snapshot())
+}
+
+#[tauri::command]
+pub async fn clear_finished_queue_jobs() -> Result<QueueSnapshot, String> {
+    clear_finished_jobs();
+    Ok(queue_snapshot())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::time::Duration;
+    use crate::video::binaries::{set_binary_override, BinaryKind};
+
+    fn write_script(path: &Path, body: &str) {
+        std::fs::write(path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    // ffprobe 探测期间（第一次启动 ffmpeg 之前）取消：任务不应继续编码，也不应写出输出
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cancel_before_first_ffmpeg_spawn() {
+        let dir = std::env::temp_dir().join(format!("compressgo-queue-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("ffmpeg-ran");
+        let (ffmpeg, ffprobe) = (dir.join("ffmpeg"), dir.join("ffprobe"));
+        write_script(
+            &ffprobe,
+            r#"[ "$1" = "-version" ] && { echo "ffprobe version test"; exit 0; }
+sleep 1
+echo '{"format":{"duration":"10.0"},"streams":[{"index":0,"codec_type":"video","codec_name":"h264","width":64,"height":64}]}'"#,
+        );
+        write_script(
+            &ffmpeg,
+            &format!(r#"[ "$1" = "-version" ] && {{ echo "ffmpeg version test"; exit 0; }}
+echo "$@" >> "{}""#, marker.display()),
+        );
+        set_binary_override(BinaryKind::Ffmpeg, Some(ffmpeg.to_string_lossy().to_string())).unwrap();
+        set_binary_override(BinaryKind::Ffprobe, Some(ffprobe.to_string_lossy().to_string())).unwrap();
+
+        let input = dir.join("input.mp4");
+        std::fs::write(&input, b"not a video").unwrap();
+        let output = dir.join("output.mp4");
+        let settings = serde_json::from_value(serde_json::json!({
+            "format": "mp4",
+            "codec": "H.264",
+            "resolution": "original",
+            "quality_type": "crf",
+            "crf_value": 28,
+        }))
+        .unwrap();
+        let task_id = "queue-cancel-before-spawn";
+        enqueue_job(
+            CompressionJob {
+                task_id: task_id.to_string(),
+                kind: JobKind::Video,
+                input_path: input.to_string_lossy().to_string(),
+                output_path: output.to_string_lossy().to_string(),
+                settings,
+            },
+            0,
+            JobOptions::default(),
+        )
+        .unwrap();
+
+        let status = |id: &str| queue_snapshot().jobs.into_iter().find(|j| j.job.task_id == id).map(|j| (j.status, j.running));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(status(task_id), Some((JobStatus::Running, true)));
+        cancel_queued_job(task_id).await.unwrap();
+        // 流程返回之前仍占用并发名额
+        assert_eq!(status(task_id), Some((JobStatus::Cancelled, true)));
+
+        for _ in 0..50 {
+            if status(task_id).is_some_and(|(_, running)| !running) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(status(task_id), Some((JobStatus::Cancelled, false)));
+        assert!(!marker.exists(), "ffmpeg was started after the task was cancelled");
+        assert!(!output.exists());
+
+        set_binary_override(BinaryKind::Ffmpeg, None).unwrap();
+        set_binary_override(BinaryKind::Ffprobe, None).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
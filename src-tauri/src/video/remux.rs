@@ -0,0 +1,248 @@
+use std::path::Path;
+use serde_json::Value;
+use crate::video::color::{tonemap_filters, ResolvedHdr};
+use crate::video::filters::{post_scale_filters, pre_scale_filters};
+use crate::video::types::{CompressionSettings, StreamInfo};
+use crate::video::utils::tokio_command_with_no_window;
+
+// 无损转封装：视频流直接复制（-c:v copy），只换容器或裁剪时几秒完成。
+//   off   始终重新编码（默认）
+//   auto  源编码与所选编码一致、且没有需要重新编码的设置时复制，否则编码；
+//         设置了码率、CRF/质量值或两遍编码也视为要求重新编码，auto 只处理换容器与裁剪时长
+//   copy  强制复制视频流，无法复制时报错
+// 复制时只能在关键帧处切开，起点前移到最近的关键帧。
+
+fn remux_mode(settings: &CompressionSettings) -> &str {
+    settings.remux.as_deref().filter(|m| !m.is_empty()).unwrap_or("off")
+}
+
+// 前端编码名称对应的 ffprobe codec_name
+fn codec_stream_name(codec: &str) -> &str {
+    match codec {
+        "H.264" | "libx264" => "h264",
+        "H.265" | "HEVC" | "libx265" => "hevc",
+        "AV1" | "libsvtav1" => "av1",
+        "VP8" | "libvpx" => "vp8",
+        "VP9" | "libvpx-vp9" => "vp9",
+        "MPEG-4" => "mpeg4",
+        "MPEG-2" => "mpeg2video",
+        "ProRes" => "prores",
+        other => other,
+    }
+}
+
+// 容器能否直接容纳该视频编码
+fn container_accepts_video(ext: &str, codec_name: &str) -> bool {
+    match ext {
+        "mp4" | "m4v" => matches!(codec_name, "h264" | "hevc" | "av1" | "vp9" | "mpeg4"),
+        "mov" => matches!(codec_name, "h264" | "hevc" | "av1" | "mpeg4" | "prores" | "mjpeg"),
+        "webm" => matches!(codec_name, "vp8" | "vp9" | "av1"),
+        "mkv" => true,
+        "avi" => matches!(codec_name, "h264" | "mpeg4" | "mjpeg"),
+        "ts" | "m2ts" => matches!(codec_name, "h264" | "hevc" | "mpeg2video"),
+        "flv" => codec_name == "h264",
+        _ => false,
+    }
+}
+
+// 由像素格式推断位深
+fn pix_fmt_bit_depth(pix_fmt: &str) -> u8 {
+    if pix_fmt.contains("12") {
+        12
+    } else if pix_fmt.contains("10") {
+        10
+    } else {
+        8
+    }
+}
+
+/// 判断本次任务是否复制视频流。auto 模式下不满足条件时返回 false 并打印原因；copy 模式下报错
+pub(crate) fn should_stream_copy(
+    settings: &CompressionSettings,
+    probe: &Value,
+    source: Option<&StreamInfo>,
+    output_path: &str,
+    subtitle_burn: bool,
+    hdr: Option<&ResolvedHdr>,
+) -> Result<bool, String> {
+    let mode = remux_mode(settings);
+    if !matches!(mode, "off" | "auto" | "copy") {
+        return Err(format!("Unknown remux mode: {}", mode));
+    }
+    if mode == "off" {
+        return Ok(false);
+    }
+
+    let ext = Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let mut blockers: Vec<String> = Vec::new();
+    let Some(source) = source else { return Err("No video stream found".to_string()) };
+    if !container_accepts_video(&ext, &source.codec_name) {
+        blockers.push(format!("{} video cannot be stored in .{}", source.codec_name, ext));
+    }
+    if settings.scale.is_some() || settings.resolution != "original" {
+        blockers.push("scaling".to_string());
+    }
+    if !pre_scale_filters(settings).is_empty() || !post_scale_filters(settings).is_empty() {
+        blockers.push("video filters or frame rate conversion".to_string());
+    }
+    if !tonemap_filters(hdr, "yuv420p").is_empty() {
+        blockers.push("HDR tone mapping".to_string());
+    }
+    if subtitle_burn {
+        blockers.push("subtitle burn-in".to_string());
+    }
+    if matches!(settings.quality_type.as_str(), "target_size" | "auto_crf") {
+        blockers.push(format!("quality type {}", settings.quality_type));
+    }
+    // auto 时还要求编码设置与源一致
+    if mode == "auto" {
+        let target = codec_stream_name(&settings.codec);
+        if target != source.codec_name {
+            blockers.push(format!("codec change {} → {}", source.codec_name, target));
+        }
+        let source_depth = probe["streams"]
+            .as_array()
+            .and_then(|streams| streams.iter().find(|s| s["index"].as_u64() == Some(source.index as u64)))
+            .and_then(|s| s["pix_fmt"].as_str())
+            .map(pix_fmt_bit_depth);
+        if settings.bit_depth.is_some_and(|d| Some(d) != source_depth) {
+            blockers.push("bit depth change".to_string());
+        }
+        if settings.encoder.is_some() {
+            blockers.push("encoder options".to_string());
+        }
+        if settings.quality_type == "bitrate" && settings.bitrate.is_some() {
+            blockers.push("target bitrate".to_string());
+        }
+        if settings.crf_value.is_some() || settings.qv_value.is_some() {
+            blockers.push("quality setting".to_string());
+        }
+        if settings.two_pass == Some(true) {
+            blockers.push("two-pass encoding".to_string());
+        }
+    }
+
+    if blockers.is_empty() {
+        println!("📦 Stream copy: {} video into .{}", source.codec_name, ext);
+        return Ok(true);
+    }
+    match mode {
+        "copy" => Err(format!("Stream copy is not possible: {}", blockers.join(", "))),
+        _ => {
+            println!("📦 Re-encoding instead of stream copy: {}", blockers.join(", "));
+            Ok(false)
+        }
+    }
+}
+
+/// 不晚于 time 的最近关键帧时间；探测失败时返回 None
+pub(crate) async fn keyframe_at_or_before(ffprobe_path: &Path, input_path: &str, time: f64) -> Option<f64> {
+    // 从 time 前 30 秒开始只解码关键帧
+    let interval = format!("{}%{}", (time - 30.0).max(0.0), time + 0.001);
+    let output = tokio_command_with_no_window(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-select_streams", "v:0",
+            "-skip_frame", "nokey",
+            "-read_intervals", &interval,
+            "-show_entries", "frame=pts_time,best_effort_timestamp_time",
+            input_path,
+        ])
+        .output()
+        .await
+        .ok()?;
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    json["frames"]
+        .as_array()?
+        .iter()
+        .filter_map(|f| {
+            f["pts_time"]
+                .as_str()
+                .or_else(|| f["best_effort_timestamp_time"].as_str())
+                .and_then(|t| t.parse::<f64>().ok())
+        })
+        .filter(|t| *t <= time + 0.001)
+        .max_by(|a, b| a.total_cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::video::streams::parse_stream_infos;
+
+    fn probe() -> Value {
+        json!({
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "h264", "pix_fmt": "yuv420p", "width": 1920, "height": 1080 },
+                { "index": 1, "codec_type": "audio", "codec_name": "aac" },
+            ]
+        })
+    }
+
+    // 只换容器的基础设置，extra 覆盖其中的字段
+    fn settings(extra: Value) -> CompressionSettings {
+        let mut base = json!({
+            "format": "mp4",
+            "codec": "H.264",
+            "resolution": "original",
+            "quality_type": "crf",
+            "remux": "auto",
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            base[key] = value.clone();
+        }
+        serde_json::from_value(base).unwrap()
+    }
+
+    fn stream_copy(extra: Value, output_path: &str) -> Result<bool, String> {
+        let probe = probe();
+        let streams = parse_stream_infos(&probe);
+        let source = streams.iter().find(|s| s.codec_type == "video");
+        should_stream_copy(&settings(extra), &probe, source, output_path, false, None)
+    }
+
+    #[test]
+    fn auto_copies_container_only_changes() {
+        assert_eq!(stream_copy(json!({}), "out.mp4"), Ok(true));
+        assert_eq!(stream_copy(json!({}), "out.mkv"), Ok(true));
+        assert_eq!(stream_copy(json!({ "bitDepth": 8, "time_range": { "start": 5.0, "end": 10.0 } }), "out.mov"), Ok(true));
+        assert_eq!(stream_copy(json!({ "remux": "off" }), "out.mp4"), Ok(false));
+    }
+
+    #[test]
+    fn auto_reencodes_when_settings_require_it() {
+        for extra in [
+            json!({ "quality_type": "bitrate", "bitrate": "1M" }),
+            json!({ "crf_value": 23 }),
+            json!({ "quality_type": "qv", "qv_value": 60 }),
+            json!({ "two_pass": true }),
+            json!({ "codec": "H.265" }),
+            json!({ "bitDepth": 10 }),
+            json!({ "encoder": { "preset": "slow" } }),
+            json!({ "resolution": "1280x720" }),
+            json!({ "quality_type": "target_size", "target_size": 1000000 }),
+        ] {
+            assert_eq!(stream_copy(extra.clone(), "out.mp4"), Ok(false), "{}", extra);
+        }
+        // webm 不能容纳 H.264
+        assert_eq!(stream_copy(json!({}), "out.webm"), Ok(false));
+    }
+
+    #[test]
+    fn copy_mode_ignores_rate_settings_but_rejects_filters() {
+        let copy = |extra: Value| {
+            let mut extra = extra;
+            extra["remux"] = json!("copy");
+            stream_copy(extra, "out.mp4")
+        };
+        assert_eq!(copy(json!({ "quality_type": "bitrate", "bitrate": "1M", "crf_value": 23 })), Ok(true));
+        assert!(copy(json!({ "resolution": "1280x720" })).unwrap_err().contains("scaling"));
+        assert!(stream_copy(json!({ "remux": "always" }), "out.mp4").is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::video::types::CompressionSettings;
+
+// 可续传编码：按固定时长分段（segment 复用器）输出到临时目录，中断后从最后一个完整分段继续，
+// 全部完成后用 concat 无损拼接。分段目录为 <临时目录>/compressgo-resume/<指纹>，
+// 指纹由输入文件（路径、大小、修改时间）、输出路径与压缩参数决定，
+// 所以同一任务再次执行（包括应用重启后恢复的队列任务）会自动续传。
+
+// 分段会按 SEGMENT_SECONDS 强制关键帧并多一次拼接，改变输出的 GOP 结构，所以只在 resumable 为 true 时启用。
+
+// 每段时长，同时也是强制关键帧的间隔
+pub const SEGMENT_SECONDS: f64 = 60.0;
+// 超过 7 天未更新的分段目录在下次分段编码时清理
+const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 3600);
+
+#[derive(Serialize, Deserialize)]
+struct ResumeManifest {
+    input_path: String,
+    output_path: String,
+    settings: CompressionSettings,
+    created_at: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletedSegment {
+    pub file: String,
+    pub duration: f64,
+}
+
+pub struct ResumeState {
+    pub dir: PathBuf,
+    ext: String,
+}
+
+/// 是否采用分段编码（需显式开启）
+pub fn should_segment(settings: &CompressionSettings, duration: f64) -> bool {
+    settings.resumable == Some(true) && duration > SEGMENT_SECONDS
+}
+
+fn resume_root() -> PathBuf {
+    std::env::temp_dir().join("compressgo-resume")
+}
+
+// 64 位 FNV-1a。指纹用作目录名，需要在不同 Rust 版本与应用版本之间保持不变，
+// 所以不用算法不固定的 DefaultHasher
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // 字段之间加分隔，避免 "ab"+"c" 与 "a"+"bc" 相同
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(bytes);
+        self.write(&[0xff]);
+    }
+}
+
+fn fingerprint(input_path: &str, output_path: &str, settings: &CompressionSettings) -> String {
+    let mut hasher = Fnv1a::new();
+    hasher.write_field(input_path.as_bytes());
+    if let Ok(meta) = std::fs::metadata(input_path) {
+        hasher.write_field(&meta.len().to_le_bytes());
+        if let Ok(modified) = meta.modified() {
+            let millis = modified.duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+            hasher.write_field(&millis.to_le_bytes());
+        }
+    }
+    hasher.write_field(output_path.as_bytes());
+    hasher.write_field(serde_json::to_string(settings).unwrap_or_default().as_bytes());
+    format!("{:016x}", hasher.0)
+}
+
+// 清理长时间未使用的分段目录
+fn prune_stale(root: &Path) {
+    let Ok(entries) = std::fs::read_dir(root) else { return };
+    for entry in entries.flatten() {
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if stale {
+            println!("🧹 Removing stale resume dir: {:?}", entry.path());
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+impl ResumeState {
+    /// 打开（或新建）任务对应的分段目录
+    pub fn open(input_path: &str, output_path: &str, settings: &CompressionSettings) -> Result<Self, String> {
+        let root = resume_root();
+        prune_stale(&root);
+        let dir = root.join(fingerprint(input_path, output_path, settings));
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create resume dir {:?}: {}", dir, e))?;
+
+        let manifest_path = dir.join("manifest.json");
+        if !manifest_path.exists() {
+            let manifest = ResumeManifest {
+                input_path: input_path.to_string(),
+                output_path: output_path.to_string(),
+                settings: settings.clone(),
+                created_at: crate::video::history::now_millis(),
+            };
+            let data = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+            std::fs::write(&manifest_path, data).map_err(|e| format!("Failed to write resume manifest: {}", e))?;
+        }
+
+        let ext = Path::new(output_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .filter(|e| !e.is_empty())
+            .unwrap_or_else(|| "mkv".to_string());
+        Ok(Self { dir, ext })
+    }
+
+    // 每次运行写一个分段列表：list_<n>.csv，按 n 顺序即为时间顺序
+    fn list_files(&self) -> Vec<PathBuf> {
+        let mut lists: Vec<(usize, PathBuf)> = std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        let n = name.strip_prefix("list_")?.strip_suffix(".csv")?.parse().ok()?;
+                        Some((n, e.path()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        lists.sort_by_key(|(n, _)| *n);
+        lists.into_iter().map(|(_, p)| p).collect()
+    }
+
+    /// 已完成的分段。segment 复用器只在分段写完后才写入列表，列表中的即为完整分段
+    pub fn completed_segments(&self) -> Vec<CompletedSegment> {
+        let mut segments = Vec::new();
+        for list in self.list_files() {
+            let Ok(data) = std::fs::read_to_string(&list) else { continue };
+            for line in data.lines() {
+                // CSV: 文件名,开始时间,结束时间
+                let cols: Vec<&str> = line.split(',').collect();
+                if cols.len() < 3 {
+                    continue;
+                }
+                let (Ok(start), Ok(end)) = (cols[1].trim().parse::<f64>(), cols[2].trim().parse::<f64>()) else { continue };
+                let file = Path::new(cols[0].trim().trim_matches('"'))
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if file.is_empty() || !self.dir.join(&file).is_file() {
+                    continue;
+                }
+                segments.push(CompletedSegment { file, duration: (end - start).max(0.0) });
+            }
+        }
+        segments
+    }
+
+    pub fn next_list_path(&self) -> PathBuf {
+        self.dir.join(format!("list_{}.csv", self.list_files().len()))
+    }
+
+    pub fn segment_pattern(&self) -> PathBuf {
+        self.dir.join(format!("part_%05d.{}", self.ext))
+    }
+
+    /// 生成 concat 复用器使用的列表文件
+    pub fn write_concat_list(&self, segments: &[CompletedSegment]) -> Result<PathBuf, String> {
+        let path = self.dir.join("concat.txt");
+        let body: String = segments
+            .iter()
+            .map(|s| format!("file '{}'\n", s.file.replace('\'', "'\\''")))
+            .collect();
+        std::fs::write(&path, body).map_err(|e| format!("Failed to write concat list: {}", e))?;
+        Ok(path)
+    }
+
+    pub fn remove(&self) {
+        remove_temp_dir(&self.dir);
+    }
+}
+
+/// 删除任务使用的临时目录（分段、passlog 等）
+pub fn remove_temp_dir(dir: &Path) {
+    if dir.exists() {
+        match std::fs::remove_dir_all(dir) {
+            Ok(_) => println!("🧹 Removed temp dir: {:?}", dir),
+            Err(e) => println!("Failed to remove temp dir {:?}: {}", dir, e),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::video::types::ScaleSettings;
+
+// 保持宽高比的缩放：按模式计算统一的缩放系数 F，输出尺寸为 iw*F × ih*F，
+// 向下取整到编码器要求的倍数（4:2:0 视频为 2）。默认不放大，F 不超过 1。
+//   fit         缩放到 width × height 框内（只给一边时按该边）
+//   fill        铺满 width × height 框后居中裁剪多余部分
+//   long_edge   长边不超过 edge
+//   short_edge  短边不超过 edge
+//   percent     按百分比缩放
+
+// 视频编码器要求的尺寸倍数
+pub const VIDEO_DIMENSION_MULTIPLE: u32 = 2;
+// 图片编码器没有尺寸限制
+pub const IMAGE_DIMENSION_MULTIPLE: u32 = 1;
+
+/// 校验缩放设置
+pub fn validate_scale(scale: &ScaleSettings) -> Result<(), String> {
+    let positive = |v: Option<u32>| v.is_some_and(|v| v > 0);
+    match scale.mode.as_str() {
+        "fit" => {
+            if !positive(scale.width) && !positive(scale.height) {
+                return Err("Scale mode \"fit\" requires width or height".to_string());
+            }
+        }
+        "fill" => {
+            if !positive(scale.width) || !positive(scale.height) {
+                return Err("Scale mode \"fill\" requires width and height".to_string());
+            }
+        }
+        "long_edge" | "short_edge" => {
+            if !positive(scale.edge) {
+                return Err(format!("Scale mode \"{}\" requires edge", scale.mode));
+            }
+        }
+        "percent" => {
+            if !scale.percent.is_some_and(|p| p > 0.0) {
+                return Err("Scale mode \"percent\" requires a positive percent".to_string());
+            }
+        }
+        other => return Err(format!("Unknown scale mode: {}", other)),
+    }
+    Ok(())
+}
+
+// 缩放系数表达式（FFmpeg 表达式，iw/ih 为输入尺寸）
+fn scale_factor(scale: &ScaleSettings) -> Option<String> {
+    let factor = match scale.mode.as_str() {
+        "fit" => match (scale.width.filter(|w| *w > 0), scale.height.filter(|h| *h > 0)) {
+            (Some(w), Some(h)) => format!("min({}/iw,{}/ih)", w, h),
+            (Some(w), None) => format!("{}/iw", w),
+            (None, Some(h)) => format!("{}/ih", h),
+            (None, None) => return None,
+        },
+        "fill" => format!("max({}/iw,{}/ih)", scale.width?, scale.height?),
+        "long_edge" => format!("{}/max(iw,ih)", scale.edge?),
+        "short_edge" => format!("{}/min(iw,ih)", scale.edge?),
+        "percent" => format!("{}/100", scale.percent?),
+        _ => return None,
+    };
+    if scale.allow_upscale == Some(true) {
+        Some(factor)
+    } else {
+        Some(format!("min(1,{})", factor))
+    }
+}
+
+// 按倍数向下取整，且不小于一个倍数
+fn round_to(expr: &str, multiple: u32) -> String {
+    if multiple <= 1 {
+        format!("max(1,trunc({}))", expr)
+    } else {
+        format!("max({m},trunc(({e})/{m})*{m})", e = expr, m = multiple)
+    }
+}
+
+/// 生成缩放（及 fill 模式的裁剪）滤镜；设置无效时返回空
+pub fn scale_filters(scale: &ScaleSettings, multiple: u32) -> Vec<String> {
+    let Some(factor) = scale_factor(scale) else { return Vec::new() };
+    let mut filters = vec![format!(
+        "scale=w='{}':h='{}':flags=bicubic",
+        round_to(&format!("iw*{}", factor), multiple),
+        round_to(&format!("ih*{}", factor), multiple)
+    )];
+    if scale.mode == "fill" {
+        if let (Some(w), Some(h)) = (scale.width, scale.height) {
+            // 未放大时画面可能小于目标框，裁剪尺寸不超过画面
+            filters.push(format!(
+                "crop=w='{}':h='{}'",
+                round_to(&format!("min({},iw)", w), multiple),
+                round_to(&format!("min({},ih)", h), multiple)
+            ));
+        }
+    }
+    filters
+}
+
+/// 按倍数向下取整固定尺寸（custom 分辨率），避免编码器拒绝奇数尺寸
+pub fn round_dimension(value: u32, multiple: u32) -> u32 {
+    if multiple <= 1 {
+        value.max(1)
+    } else {
+        (value / multiple * multiple).max(multiple)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scale(mode: &str) -> ScaleSettings {
+        ScaleSettings { mode: mode.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn factor_per_mode_never_upscales_by_default() {
+        let fit = ScaleSettings { width: Some(1280), height: Some(720), ..scale("fit") };
+        assert_eq!(scale_factor(&fit).as_deref(), Some("min(1,min(1280/iw,720/ih))"));
+        let fit_width = ScaleSettings { width: Some(1280), height: Some(0), ..scale("fit") };
+        assert_eq!(scale_factor(&fit_width).as_deref(), Some("min(1,1280/iw)"));
+        let fit_height = ScaleSettings { height: Some(720), ..scale("fit") };
+        assert_eq!(scale_factor(&fit_height).as_deref(), Some("min(1,720/ih)"));
+        let fill = ScaleSettings { width: Some(1080), height: Some(1080), ..scale("fill") };
+        assert_eq!(scale_factor(&fill).as_deref(), Some("min(1,max(1080/iw,1080/ih))"));
+        let long = ScaleSettings { edge: Some(1920), ..scale("long_edge") };
+        assert_eq!(scale_factor(&long).as_deref(), Some("min(1,1920/max(iw,ih))"));
+        let short = ScaleSettings { edge: Some(720), ..scale("short_edge") };
+        assert_eq!(scale_factor(&short).as_deref(), Some("min(1,720/min(iw,ih))"));
+        let percent = ScaleSettings { percent: Some(50.0), ..scale("percent") };
+        assert_eq!(scale_factor(&percent).as_deref(), Some("min(1,50/100)"));
+
+        let upscale = ScaleSettings { percent: Some(150.0), allow_upscale: Some(true), ..scale("percent") };
+        assert_eq!(scale_factor(&upscale).as_deref(), Some("150/100"));
+        assert_eq!(scale_factor(&scale("fit")), None);
+        assert_eq!(scale_factor(&scale("stretch")), None);
+    }
+
+    #[test]
+    fn expressions_round_down_to_the_multiple() {
+        assert_eq!(round_to("iw*0.5", 2), "max(2,trunc((iw*0.5)/2)*2)");
+        assert_eq!(round_to("iw*0.5", 1), "max(1,trunc(iw*0.5))");
+
+        let long = ScaleSettings { edge: Some(1920), ..scale("long_edge") };
+        assert_eq!(
+            scale_filters(&long, VIDEO_DIMENSION_MULTIPLE),
+            vec!["scale=w='max(2,trunc((iw*min(1,1920/max(iw,ih)))/2)*2)':h='max(2,trunc((ih*min(1,1920/max(iw,ih)))/2)*2)':flags=bicubic"]
+        );
+        assert!(scale_filters(&scale("fit"), VIDEO_DIMENSION_MULTIPLE).is_empty());
+    }
+
+    #[test]
+    fn fill_crops_to_the_box_clamped_to_the_frame() {
+        let fill = ScaleSettings { width: Some(1080), height: Some(1350), ..scale("fill") };
+        let filters = scale_filters(&fill, VIDEO_DIMENSION_MULTIPLE);
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters[1], "crop=w='max(2,trunc((min(1080,iw))/2)*2)':h='max(2,trunc((min(1350,ih))/2)*2)'");
+        let image = scale_filters(&fill, IMAGE_DIMENSION_MULTIPLE);
+        assert_eq!(image[1], "crop=w='max(1,trunc(min(1080,iw)))':h='max(1,trunc(min(1350,ih)))'");
+    }
+
+    #[test]
+    fn fixed_dimensions_round_to_even() {
+        assert_eq!(round_dimension(1081, 2), 1080);
+        assert_eq!(round_dimension(1080, 2), 1080);
+        assert_eq!(round_dimension(1, 2), 2);
+        assert_eq!(round_dimension(1081, 1), 1081);
+        assert_eq!(round_dimension(0, 1), 1);
+    }
+
+    #[test]
+    fn validation_requires_mode_parameters() {
+        assert!(validate_scale(&ScaleSettings { width: Some(1280), ..scale("fit") }).is_ok());
+        assert!(validate_scale(&scale("fit")).is_err());
+        assert!(validate_scale(&ScaleSettings { width: Some(1280), ..scale("fill") }).is_err());
+        assert!(validate_scale(&ScaleSettings { edge: Some(0), ..scale("long_edge") }).is_err());
+        assert!(validate_scale(&ScaleSettings { percent: Some(0.0), ..scale("percent") }).is_err());
+        assert!(validate_scale(&scale("stretch")).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use serde_json::Value;
+use crate::video::types::{StreamInfo, StreamSelection};
+
+// 流选择：根据 ffprobe 的全部流信息生成 -map 与 -disposition 参数，
+// 多音轨、多字幕的 MKV 等文件压缩后仍保留所选的语言轨道。
+
+/// 从 ffprobe JSON（-show_streams）解析全部流
+pub fn parse_stream_infos(probe: &Value) -> Vec<StreamInfo> {
+    let Some(streams) = probe["streams"].as_array() else { return Vec::new() };
+    streams
+        .iter()
+        .filter_map(|s| {
+            let flag = |name: &str| s["disposition"][name].as_i64() == Some(1);
+            let tag = |name: &str| s["tags"][name].as_str().map(|v| v.to_string()).filter(|v| !v.is_empty());
+            Some(StreamInfo {
+                index: s["index"].as_u64()? as u32,
+                codec_type: s["codec_type"].as_str().unwrap_or("unknown").to_string(),
+                codec_name: s["codec_name"].as_str().unwrap_or("unknown").to_string(),
+                language: tag("language"),
+                title: tag("title"),
+                channels: s["channels"].as_u64().map(|c| c as u32),
+                channel_layout: s["channel_layout"].as_str().map(|c| c.to_string()),
+                sample_rate: s["sample_rate"].as_str().and_then(|r| r.parse().ok()),
+                width: s["width"].as_u64().map(|w| w as u32),
+                height: s["height"].as_u64().map(|h| h as u32),
+                is_default: flag("default"),
+                is_forced: flag("forced"),
+                attached_pic: flag("attached_pic"),
+                frame_rate: s["r_frame_rate"].as_str().and_then(parse_rational),
+                avg_frame_rate: s["avg_frame_rate"].as_str().and_then(parse_rational),
+            })
+        })
+        .collect()
+}
+
+// 解析 "30000/1001" 形式的帧率，0/0 视为未知
+fn parse_rational(value: &str) -> Option<f64> {
+    let rate = match value.split_once('/') {
+        Some((num, den)) => {
+            let den = den.parse::<f64>().ok()?;
+            if den == 0.0 {
+                return None;
+            }
+            num.parse::<f64>().ok()? / den
+        }
+        None => value.parse::<f64>().ok()?,
+    };
+    (rate > 0.0).then_some(rate)
+}
+
+// 按序号与语言选出某一类型的流
+fn select_streams<'a>(
+    streams: &'a [StreamInfo],
+    codec_type: &str,
+    indices: Option<&Vec<u32>>,
+    languages: Option<&Vec<String>>,
+) -> Result<Vec<&'a StreamInfo>, String> {
+    let candidates: Vec<&StreamInfo> = streams.iter().filter(|s| s.codec_type == codec_type).collect();
+    if let Some(indices) = indices {
+        if let Some(missing) = indices.iter().find(|i| !candidates.iter().any(|s| s.index == **i)) {
+            return Err(format!("Stream #{} is not a {} stream of the input", missing, codec_type));
+        }
+    }
+    if indices.is_none() && languages.is_none() {
+        return Ok(candidates);
+    }
+    Ok(candidates
+        .into_iter()
+        .filter(|s| {
+            indices.is_some_and(|i| i.contains(&s.index))
+                || languages.is_some_and(|langs| {
+                    s.language
+                        .as_ref()
+                        .is_some_and(|l| langs.iter().any(|lang| lang.eq_ignore_ascii_case(l)))
+                })
+        })
+        .collect())
+}
+
+// 输出中某一类型流的 disposition 参数（按输出中的顺序编号）
+fn disposition_args(
+    selected: &[&StreamInfo],
+    specifier: &str,
+    default: Option<u32>,
+    forced: Option<&Vec<u32>>,
+) -> Result<Vec<String>, String> {
+    if default.is_none() && forced.is_none() {
+        return Ok(Vec::new());
+    }
+    for index in default.iter().chain(forced.into_iter().flatten()) {
+        if !selected.iter().any(|s| s.index == *index) {
+            return Err(format!("Stream #{} is not among the selected {} streams", index, specifier_name(specifier)));
+        }
+    }
+    let mut args = Vec::new();
+    for (position, stream) in selected.iter().enumerate() {
+        let is_default = match default {
+            Some(index) => index == stream.index,
+            None => stream.is_default,
+        };
+        let is_forced = match forced {
+            Some(list) => list.contains(&stream.index),
+            None => stream.is_forced,
+        };
+        let value = match (is_default, is_forced) {
+            (true, true) => "default+forced",
+            (true, false) => "default",
+            (false, true) => "forced",
+            (false, false) => "0",
+        };
+        args.push(format!("-disposition:{}:{}", specifier, position));
+        args.push(value.to_string());
+    }
+    Ok(args)
+}
+
+fn specifier_name(specifier: &str) -> &'static str {
+    if specifier == "a" { "audio" } else { "subtitle" }
+}
+
+/// 生成流映射参数（位于 -i 之后）。mode 为 default 或未设置时返回空，由 FFmpeg 自动选择；
+/// video_output 为滤镜图输出标签（如图形字幕烧录的 [vout]）时用它代替源视频流，此时需要显式映射音频。
+/// keep_subtitle 过滤输出容器无法容纳或不需要保留的字幕流
+pub(crate) fn build_stream_map_args(
+    selection: Option<&StreamSelection>,
+    streams: &[StreamInfo],
+    video_output: Option<&str>,
+    keep_subtitle: impl Fn(&StreamInfo) -> bool,
+) -> Result<Vec<String>, String> {
+    let selection = match selection {
+        Some(selection) if !matches!(selection.mode.as_str(), "" | "default") => selection,
+        _ => {
+            let Some(label) = video_output else { return Ok(Vec::new()) };
+            // 一旦使用 -map，FFmpeg 不再自动选择音频：取默认音轨，没有则取第一条
+            let audio_streams: Vec<&StreamInfo> = streams.iter().filter(|s| s.codec_type == "audio").collect();
+            let mut args = vec!["-map".to_string(), label.to_string()];
+            if let Some(audio) = audio_streams.iter().find(|s| s.is_default).or(audio_streams.first()) {
+                args.push("-map".to_string());
+                args.push(format!("0:{}", audio.index));
+            }
+            return Ok(args);
+        }
+    };
+    let (audio, subtitles) = match selection.mode.as_str() {
+        "all" => (
+            select_streams(streams, "audio", None, None)?,
+            select_streams(streams, "subtitle", None, None)?,
+        ),
+        "custom" => (
+            select_streams(streams, "audio", selection.audio_indices.as_ref(), selection.audio_languages.as_ref())?,
+            select_streams(streams, "subtitle", selection.subtitle_indices.as_ref(), selection.subtitle_languages.as_ref())?,
+        ),
+        other => return Err(format!("Unknown stream selection mode: {}", other)),
+    };
+    let (subtitles, dropped): (Vec<&StreamInfo>, Vec<&StreamInfo>) = subtitles.into_iter().partition(|s| keep_subtitle(s));
+    if !dropped.is_empty() {
+        println!(
+            "Subtitle streams {:?} are not kept in the output",
+            dropped.iter().map(|s| s.index).collect::<Vec<_>>()
+        );
+    }
+
+    // 主视频流：第一个非封面图的视频流
+    let video = streams
+        .iter()
+        .find(|s| s.codec_type == "video" && !s.attached_pic)
+        .ok_or_else(|| "No video stream found".to_string())?;
+    let mut args = vec![
+        "-map".to_string(),
+        video_output.map(|l| l.to_string()).unwrap_or_else(|| format!("0:{}", video.index)),
+    ];
+    for stream in audio.iter().chain(subtitles.iter()) {
+        args.push("-map".to_string());
+        args.push(format!("0:{}", stream.index));
+    }
+    args.extend(disposition_args(&audio, "a", selection.default_audio, None)?);
+    args.extend(disposition_args(&subtitles, "s", selection.default_subtitle, selection.forced_subtitles.as_ref())?);
+    println!(
+        "🎚️ Stream selection: video #{}, audio {:?}, subtitles {:?}",
+        video.index,
+        audio.iter().map(|s| s.index).collect::<Vec<_>>(),
+        subtitles.iter().map(|s| s.index).collect::<Vec<_>>()
+    );
+    Ok(args)
+}
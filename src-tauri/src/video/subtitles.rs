@@ -0,0 +1,187 @@
+use std::path::Path;
+use crate::video::compression::{build_input_args, run_ffmpeg_tracked, VideoJob};
+use crate::video::quality::stderr_tail;
+use crate::video::types::{CompressionSettings, StreamInfo, SubtitleSettings};
+
+// 字幕处理：
+//   keep    保留字幕流，按容器自动转换格式（MP4/MOV 转 mov_text，WebM 转 WebVTT），容器无法容纳的图形字幕丢弃
+//   burn    把一条字幕（文本或图形轨道，或外部 .srt/.ass 文件）烧录进画面
+//   extract 把字幕轨道导出为输出文件旁的独立文件，输出视频不含字幕
+//   none    去除字幕
+
+// 字幕烧录方式
+#[derive(Debug, Clone)]
+pub enum SubtitleBurn {
+    // 文本字幕：subtitles 滤镜，位于 -vf 链中缩放之后
+    Text { filter: String },
+    // 图形字幕（PGS/VobSub 等）：与主视频流 overlay，需要 -filter_complex
+    Bitmap { video_index: u32, subtitle_index: u32 },
+}
+
+fn subtitle_mode(settings: &CompressionSettings) -> &str {
+    settings
+        .subtitles
+        .as_ref()
+        .map(|s| s.mode.as_str())
+        .filter(|m| !m.is_empty())
+        .unwrap_or("keep")
+}
+
+pub fn is_bitmap_subtitle(codec_name: &str) -> bool {
+    matches!(codec_name, "hdmv_pgs_subtitle" | "dvd_subtitle" | "dvb_subtitle" | "xsub")
+}
+
+fn output_extension(output_path: &str) -> String {
+    Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default()
+}
+
+// 容器保留字幕时使用的编码器；None 表示容器不支持字幕
+fn container_subtitle_codec(ext: &str) -> Option<&'static str> {
+    match ext {
+        "mp4" | "m4v" | "mov" | "3gp" => Some("mov_text"),
+        "webm" => Some("webvtt"),
+        "mkv" => Some("copy"),
+        _ => None,
+    }
+}
+
+/// 该字幕流能否保留在输出容器中（keep 模式）
+pub fn subtitle_fits_container(settings: &CompressionSettings, output_path: &str, stream: &StreamInfo) -> bool {
+    if subtitle_mode(settings) != "keep" {
+        return false;
+    }
+    match container_subtitle_codec(&output_extension(output_path)) {
+        Some("copy") => true,
+        Some(_) => !is_bitmap_subtitle(&stream.codec_name),
+        None => false,
+    }
+}
+
+/// 字幕编码参数（位于 -i 之后）
+pub(crate) fn subtitle_codec_args(settings: &CompressionSettings, output_path: &str) -> Vec<String> {
+    let codec = match subtitle_mode(settings) {
+        "keep" => container_subtitle_codec(&output_extension(output_path)),
+        _ => None,
+    };
+    match codec {
+        Some(codec) => vec!["-c:s".to_string(), codec.to_string()],
+        None => vec!["-sn".to_string()],
+    }
+}
+
+// 滤镜参数中的路径转义：统一使用 '/'，转义 ':' 与 '\''，整体加引号
+fn escape_filter_path(path: &str) -> String {
+    let path = path.replace('\\', "/").replace(':', "\\:").replace('\'', "'\\''");
+    format!("'{}'", path)
+}
+
+/// 校验字幕设置并确定烧录方式。start_time 为输入起点（-ss 会把时间戳归零，需要平移字幕时间）
+pub(crate) fn resolve_subtitle_burn(
+    settings: &CompressionSettings,
+    input_path: &str,
+    streams: &[StreamInfo],
+    start_time: Option<f64>,
+) -> Result<Option<SubtitleBurn>, String> {
+    let Some(subtitles) = settings.subtitles.as_ref() else { return Ok(None) };
+    match subtitle_mode(settings) {
+        "keep" | "none" | "extract" => return Ok(None),
+        "burn" => {}
+        other => return Err(format!("Unknown subtitle mode: {}", other)),
+    }
+
+    let source = match (&subtitles.external_file, subtitles.stream_index) {
+        (Some(file), _) => {
+            let ext = output_extension(file);
+            if !matches!(ext.as_str(), "srt" | "ass" | "ssa" | "vtt") {
+                return Err(format!("Unsupported subtitle file: {} (expected .srt, .ass, .ssa or .vtt)", file));
+            }
+            if !Path::new(file).is_file() {
+                return Err(format!("Subtitle file not found: {}", file));
+            }
+            escape_filter_path(file)
+        }
+        (None, Some(index)) => {
+            let subtitle_streams: Vec<&StreamInfo> = streams.iter().filter(|s| s.codec_type == "subtitle").collect();
+            let position = subtitle_streams
+                .iter()
+                .position(|s| s.index == index)
+                .ok_or_else(|| format!("Stream #{} is not a subtitle stream of the input", index))?;
+            if is_bitmap_subtitle(&subtitle_streams[position].codec_name) {
+                let video = streams
+                    .iter()
+                    .find(|s| s.codec_type == "video" && !s.attached_pic)
+                    .ok_or_else(|| "No video stream found".to_string())?;
+                return Ok(Some(SubtitleBurn::Bitmap { video_index: video.index, subtitle_index: index }));
+            }
+            format!("{}:si={}", escape_filter_path(input_path), position)
+        }
+        (None, None) => return Err("Subtitle burn-in requires stream_index or external_file".to_string()),
+    };
+
+    // subtitles 滤镜按文件时间渲染，输入跳转后先平移回原时间，渲染后再归零
+    let filter = match start_time.filter(|s| *s > 0.0) {
+        Some(offset) => format!("setpts=PTS+{}/TB,subtitles={},setpts=PTS-STARTPTS", offset, source),
+        None => format!("subtitles={}", source),
+    };
+    Ok(Some(SubtitleBurn::Text { filter }))
+}
+
+// 导出时的编码器与扩展名：文本字幕保持原格式，mov_text 转为 SRT，图形字幕 PGS 导出为 .sup，其它封装为 .mks
+fn sidecar_format(codec_name: &str) -> (&'static str, &'static str) {
+    match codec_name {
+        "subrip" | "text" | "mov_text" => ("srt", "srt"),
+        "ass" | "ssa" => ("copy", "ass"),
+        "webvtt" => ("copy", "vtt"),
+        "hdmv_pgs_subtitle" => ("copy", "sup"),
+        _ => ("copy", "mks"),
+    }
+}
+
+/// 把字幕轨道导出为输出文件旁的独立文件：<输出名>.<序号>[.<语言>].<扩展名>，返回导出的文件路径
+pub(crate) async fn extract_subtitles(
+    job: &VideoJob<'_>,
+    subtitles: &SubtitleSettings,
+    streams: &[StreamInfo],
+) -> Result<Vec<String>, String> {
+    let selected: Vec<&StreamInfo> = streams
+        .iter()
+        .filter(|s| s.codec_type == "subtitle")
+        .filter(|s| subtitles.extract_indices.as_ref().is_none_or(|list| list.contains(&s.index)))
+        .collect();
+    if selected.is_empty() {
+        println!("No subtitle streams to extract");
+        return Ok(Vec::new());
+    }
+
+    let output = Path::new(job.output_path);
+    let stem = output.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let dir = output.parent().unwrap_or(Path::new("."));
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    args.extend(build_input_args(job.input_path, job.start_time, job.window));
+    let mut files = Vec::new();
+    for stream in selected {
+        let (codec, ext) = sidecar_format(&stream.codec_name);
+        let name = match &stream.language {
+            Some(lang) => format!("{}.{}.{}.{}", stem, stream.index, lang, ext),
+            None => format!("{}.{}.{}", stem, stream.index, ext),
+        };
+        let path = dir.join(name).to_string_lossy().to_string();
+        for arg in ["-map", &format!("0:{}", stream.index), "-c:s", codec, "-y", path.as_str()] {
+            args.push(arg.to_string());
+        }
+        files.push(path);
+    }
+    args.push("-progress".to_string());
+    args.push("pipe:1".to_string());
+
+    println!("💬 Extracting {} subtitle track(s) for {}", files.len(), job.display_name);
+    let run = run_ffmpeg_tracked(job.task_id, job.ffmpeg_path, &args, job.duration, &job.display_name, job.sink, |p| p).await?;
+    if !run.status.success() {
+        return Err(format!("Subtitle extraction failed: {}", stderr_tail(&run.stderr, 5)));
+    }
+    Ok(files)
+}
@@ -12,7 +12,7 @@ pub struct CompressionSettings {
     pub codec: String,
     pub resolution: String,
     pub custom_resolution: Option<CustomResolution>,
-    pub quality_type: String, // "crf" or "bitrate" or "qv"
+    pub quality_type: String, // "crf" or "bitrate" or "qv" or "target_size" or "auto_crf"
     pub crf_value: Option<u8>,
     pub qv_value: Option<u8>,
     pub bitrate: Option<String>,
@@ -21,6 +21,173 @@ pub struct CompressionSettings {
     pub hardware_acceleration: Option<String>, // "cpu" or "gpu"
     #[serde(rename = "bitDepth")]
     pub bit_depth: Option<u8>, // 8, 10, or 12 bit
+    // 分段可续传编码（每 60 秒强制关键帧），默认关闭
+    pub resumable: Option<bool>,
+    // 码率模式下使用两遍编码（libx264/libx265/libvpx-vp9/libsvtav1）
+    pub two_pass: Option<bool>,
+    // quality_type 为 "target_size" 时的目标文件大小（字节）
+    pub target_size: Option<u64>,
+    // quality_type 为 "auto_crf" 时的画质目标
+    pub quality_target: Option<QualityTarget>,
+    // 编码完成后计算 PSNR / SSIM / VMAF
+    pub measure_quality: Option<bool>,
+    // 音频编码设置：None 时 WebM 转为 Opus 128k，其它容器直接复制
+    pub audio: Option<AudioSettings>,
+    // 响度标准化（EBU R128 两遍 loudnorm），None 时不处理
+    pub loudnorm: Option<LoudnormSettings>,
+    // 音频/字幕流选择，None 时沿用 FFmpeg 默认（一个视频流 + 一个音频流）
+    pub streams: Option<StreamSelection>,
+    // 字幕处理方式，None 时按容器保留字幕
+    pub subtitles: Option<SubtitleSettings>,
+    // 视频滤镜（裁剪、旋转/翻转、去隔行、降噪、锐化）
+    pub filters: Option<VideoFilters>,
+    // 保持宽高比的缩放，设置后代替 resolution
+    pub scale: Option<ScaleSettings>,
+    // 输出帧率，None 时保持源帧率
+    pub frame_rate: Option<FrameRateSettings>,
+    // HDR 源的处理方式，None 时按 auto 处理
+    pub hdr: Option<HdrSettings>,
+    // 编码器高级参数，None 时使用 FFmpeg 默认值
+    pub encoder: Option<EncoderSettings>,
+    // 无损转封装："off"（默认）| "auto"（可行时复制视频流）| "copy"（强制复制）
+    pub remux: Option<String>,
+    // 输出不小于原文件时："keep"（默认）| "discard"（保留原文件）| "copy_original"（复制原文件到输出目录）
+    pub oversize_policy: Option<String>,
+    // 元数据、章节与文件时间的保留方式，None 时沿用 FFmpeg 默认行为
+    pub metadata: Option<MetadataSettings>,
+    // 目标文件已存在时："overwrite"（默认）| "skip" | "auto_number"，由输出命名写入，在写入最终文件时复查
+    pub collision: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityTarget {
+    pub metric: String, // "vmaf" or "ssim"
+    pub score: f64,     // VMAF 0-100，SSIM 0-1
+    pub min_crf: Option<u8>,
+    pub max_crf: Option<u8>,
+    pub samples: Option<u8>,
+    pub sample_duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AudioSettings {
+    // "copy"、"none"（去除音频）或编码器名称（AAC、Opus、MP3…），None 按容器默认
+    pub codec: Option<String>,
+    pub bitrate: Option<String>, // e.g. "128k"
+    pub sample_rate: Option<u32>,
+    // 输出声道数，少于源声道数时下混（1 单声道，2 立体声，6 为 5.1）
+    pub channels: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoudnormSettings {
+    pub target_lufs: Option<f64>, // 默认 -23 LUFS
+    pub true_peak: Option<f64>,   // 默认 -1 dBTP
+    pub lra: Option<f64>,         // 默认 7 LU
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamSelection {
+    pub mode: String, // "default" | "all" | "custom"
+    // custom 模式下按 ffprobe 流序号或语言（ISO 639-2，如 "eng"）选择，两者取并集；
+    // 都为空时保留该类型的全部流，给出空列表表示不保留
+    pub audio_indices: Option<Vec<u32>>,
+    pub audio_languages: Option<Vec<String>>,
+    pub subtitle_indices: Option<Vec<u32>>,
+    pub subtitle_languages: Option<Vec<String>>,
+    // 输出中设为默认的音频/字幕流（源流序号），其余同类流取消默认
+    pub default_audio: Option<u32>,
+    pub default_subtitle: Option<u32>,
+    // 标记为强制显示的字幕流（源流序号）
+    pub forced_subtitles: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubtitleSettings {
+    pub mode: String, // "keep" | "burn" | "extract" | "none"
+    // burn：要烧录的字幕流（源流序号）或外部 .srt/.ass 文件，外部文件优先
+    pub stream_index: Option<u32>,
+    pub external_file: Option<String>,
+    // extract：要导出的字幕流，None 时导出全部
+    pub extract_indices: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScaleSettings {
+    pub mode: String, // "fit" | "fill" | "long_edge" | "short_edge" | "percent"
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub edge: Option<u32>,
+    pub percent: Option<f64>,
+    // 默认不放大
+    pub allow_upscale: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameRateSettings {
+    pub mode: String, // "keep" | "fixed" | "cap"（只降低高于 fps 的帧率）
+    pub fps: Option<f64>,
+    // "drop"（丢帧/重复帧，默认）| "interpolate"（minterpolate 运动插帧，较慢）
+    pub method: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EncoderSettings {
+    // 速度预设，取值随编码器而定（x264/x265 "medium"，SVT-AV1 "0"～"13"，NVENC "p1"～"p7" 等）
+    pub preset: Option<String>,
+    pub tune: Option<String>,
+    pub profile: Option<String>,
+    pub level: Option<String>, // 如 "4.1"
+    // 关键帧间隔（帧数）
+    pub keyframe_interval: Option<u32>,
+    pub bframes: Option<u32>,
+    // 原始私有参数，"key=value" 以 ':' 分隔，仅对对应编码器有效
+    pub x264_params: Option<String>,
+    pub x265_params: Option<String>,
+    pub svtav1_params: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataSettings {
+    // 容器标签（creation_time、GPS location、相机型号等；图片为 EXIF）：true 保留，false 清除，None 按 FFmpeg 默认
+    pub tags: Option<bool>,
+    // 章节：true 保留，false 清除，None 按 FFmpeg 默认
+    pub chapters: Option<bool>,
+    // 输出文件的修改/访问时间设为与源文件相同
+    pub file_times: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HdrSettings {
+    // "auto"（10-bit 输出保留 HDR，8-bit 输出色调映射为 SDR）| "preserve" | "tonemap"
+    pub mode: String,
+    // 色调映射算法："hable"（默认）| "mobius" | "reinhard" | "clip"
+    pub tonemap: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VideoFilters {
+    pub crop: Option<CropSettings>,
+    pub rotate: Option<u16>,          // 顺时针 0 / 90 / 180 / 270
+    pub flip: Option<String>,         // "horizontal" | "vertical" | "both"
+    pub deinterlace: Option<String>,  // "yadif" | "bwdif"
+    pub denoise: Option<DenoiseSettings>,
+    pub sharpen: Option<f64>,         // unsharp 强度 -1.5..1.5，负值为模糊
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CropSettings {
+    pub mode: String, // "manual" | "auto"（cropdetect 检测黑边）
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub x: Option<u32>,
+    pub y: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DenoiseSettings {
+    pub method: String, // "hqdn3d" | "nlmeans"
+    pub strength: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,7 +196,7 @@ pub struct CustomResolution {
     pub height: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoMetadata {
     pub format: String,
     #[serde(rename = "videoCodec")]
@@ -44,9 +211,86 @@ pub struct VideoMetadata {
     pub fps: f64,
     #[serde(rename = "colorDepth")]
     pub color_depth: Option<String>,
+    // 全部流（视频、音频、字幕等）
+    #[serde(default)]
+    pub streams: Vec<StreamInfo>,
+    // 主视频流的色彩信息（色域、传输特性、HDR 母版元数据）
+    #[serde(default)]
+    pub color: Option<ColorInfo>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColorInfo {
+    #[serde(rename = "colorPrimaries")]
+    pub primaries: Option<String>, // bt709 | bt2020 ...
+    #[serde(rename = "colorTransfer")]
+    pub transfer: Option<String>, // bt709 | smpte2084 (PQ) | arib-std-b67 (HLG) ...
+    #[serde(rename = "colorSpace")]
+    pub space: Option<String>, // bt709 | bt2020nc ...
+    #[serde(rename = "colorRange")]
+    pub range: Option<String>, // tv | pc
+    #[serde(rename = "isHdr")]
+    pub is_hdr: bool,
+    #[serde(rename = "masteringDisplay")]
+    pub mastering_display: Option<MasteringDisplay>,
+    #[serde(rename = "contentLight")]
+    pub content_light: Option<ContentLight>,
+}
+
+// 母版显示元数据（SMPTE ST 2086），色度坐标为 CIE 1931 xy，亮度单位 cd/m²
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MasteringDisplay {
+    pub red: [f64; 2],
+    pub green: [f64; 2],
+    pub blue: [f64; 2],
+    #[serde(rename = "whitePoint")]
+    pub white_point: [f64; 2],
+    #[serde(rename = "maxLuminance")]
+    pub max_luminance: f64,
+    #[serde(rename = "minLuminance")]
+    pub min_luminance: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// 内容亮度级别（MaxCLL / MaxFALL，cd/m²）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentLight {
+    #[serde(rename = "maxCll")]
+    pub max_cll: u32,
+    #[serde(rename = "maxFall")]
+    pub max_fall: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub index: u32,
+    #[serde(rename = "codecType")]
+    pub codec_type: String, // "video" | "audio" | "subtitle" | "data" | "attachment"
+    #[serde(rename = "codecName")]
+    pub codec_name: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub channels: Option<u32>,
+    #[serde(rename = "channelLayout")]
+    pub channel_layout: Option<String>,
+    #[serde(rename = "sampleRate")]
+    pub sample_rate: Option<u32>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    #[serde(rename = "isDefault")]
+    pub is_default: bool,
+    #[serde(rename = "isForced")]
+    pub is_forced: bool,
+    // 封面图等附加图片（视频流）
+    #[serde(rename = "attachedPic")]
+    pub attached_pic: bool,
+    // r_frame_rate 与 avg_frame_rate，两者差异较大时为可变帧率（VFR）
+    #[serde(rename = "frameRate", default)]
+    pub frame_rate: Option<f64>,
+    #[serde(rename = "avgFrameRate", default)]
+    pub avg_frame_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompressionResult {
     pub success: bool,
     #[serde(rename = "outputPath")]
@@ -58,4 +302,53 @@ pub struct CompressionResult {
     pub compressed_size: Option<u64>,
     #[serde(rename = "compressedMetadata")]
     pub compressed_metadata: Option<VideoMetadata>,
+    // 自动 CRF 搜索选中的 CRF 及其样片得分
+    #[serde(rename = "chosenCrf", default, skip_serializing_if = "Option::is_none")]
+    pub chosen_crf: Option<u8>,
+    #[serde(rename = "qualityMetric", default, skip_serializing_if = "Option::is_none")]
+    pub quality_metric: Option<String>,
+    #[serde(rename = "qualityScore", default, skip_serializing_if = "Option::is_none")]
+    pub quality_score: Option<f64>,
+    // 编码后与源视频比较的客观画质指标
+    #[serde(rename = "qualityMetrics", default, skip_serializing_if = "Option::is_none")]
+    pub quality_metrics: Option<QualityMetrics>,
+    // 响度标准化前后测得的响度
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness: Option<LoudnessReport>,
+    // 导出的字幕文件
+    #[serde(rename = "subtitleFiles", default, skip_serializing_if = "Option::is_none")]
+    pub subtitle_files: Option<Vec<String>>,
+    // 视频流的处理方式："copy"（无损转封装）| "encode"
+    #[serde(rename = "processingMode", default, skip_serializing_if = "Option::is_none")]
+    pub processing_mode: Option<String>,
+    // 不影响成功的提示（如转封装起点前移到关键帧）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<String>>,
+    // 成功时的结果状态："compressed" | "no_gain"（输出没有变小）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoudnessReport {
+    #[serde(rename = "inputIntegrated")]
+    pub input_integrated: f64,
+    #[serde(rename = "inputTruePeak")]
+    pub input_true_peak: f64,
+    #[serde(rename = "inputLra")]
+    pub input_lra: f64,
+    #[serde(rename = "outputIntegrated")]
+    pub output_integrated: Option<f64>,
+    #[serde(rename = "outputTruePeak")]
+    pub output_true_peak: Option<f64>,
+    #[serde(rename = "outputLra")]
+    pub output_lra: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QualityMetrics {
+    pub psnr: Option<f64>,
+    pub ssim: Option<f64>,
+    // FFmpeg 不带 libvmaf 时为空
+    pub vmaf: Option<f64>,
 }
\ No newline at end of file
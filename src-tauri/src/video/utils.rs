@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 use tauri::Manager;
 use crate::video::types::VideoMetadata;
+use crate::video::streams::parse_stream_infos;
+use crate::video::color::parse_color_info;
+use crate::video::binaries::{resolve_ffmpeg, resolve_ffprobe};
 use std::path::Path; // 新增: 路径检查所需
 use std::time::{SystemTime, UNIX_EPOCH};
 #[cfg(windows)]
@@ -184,7 +187,7 @@ pub async fn open_output_folder(folder_path: String) -> Result<(), String> {
 
 #[allow(non_snake_case)]
 #[tauri::command]
-pub fn get_video_metadata(app_handle: tauri::AppHandle, videoPath: String) -> Result<VideoMetadata, String> {
+pub fn get_video_metadata(videoPath: String) -> Result<VideoMetadata, String> {
     // 先输出调试日志，明确传入的目标路径与存在性、文件大小
     println!(
         "[METADATA] get_video_metadata called with videoPath = {:?}",
@@ -201,53 +204,10 @@ pub fn get_video_metadata(app_handle: tauri::AppHandle, videoPath: String) -> Re
         p.parent().map(|pp| pp.exists()).unwrap_or(false)
     );
 
-    // In development mode, use the bin directory in src-tauri
-    // In production, use the resource directory with robust fallbacks
-    let ffprobe_path = if cfg!(debug_assertions) {
-        // Development mode: use bin directory relative to src-tauri
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffprobe_binary())
-    } else {
-        // Production mode: try multiple candidate locations
-        let mut candidates: Vec<std::path::PathBuf> = Vec::new();
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        let resource_bin = resource_dir.join("bin");
-        candidates.push(resource_bin.join(get_ffprobe_binary()));
-        candidates.push(resource_bin.join("ffprobe"));
-        candidates.push(resource_bin.join("ffprobe.exe"));
-
-        if let Ok(current_exe) = std::env::current_exe() {
-            if let Some(exe_dir) = current_exe.parent() {
-                candidates.push(exe_dir.join(get_ffprobe_binary()));
-                candidates.push(exe_dir.join("ffprobe"));
-                candidates.push(exe_dir.join("ffprobe.exe"));
-            }
-        }
-
-        // Pick the first existing path; if none, keep the first for error message
-        match candidates.iter().find(|p| p.exists()) {
-            Some(p) => p.clone(),
-            None => {
-                let tried = candidates
-                    .iter()
-                    .map(|p| p.display().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                return Err(format!(
-                    "FFprobe binary not found. Tried: {}",
-                    tried
-                ));
-            }
-        }
-    };
+    let ffprobe_path = resolve_ffprobe()?;
 
     println!("FFprobe path for metadata: {:?}", ffprobe_path);
 
-    if !ffprobe_path.exists() {
-        return Err(format!("FFprobe binary not found at: {:?}", ffprobe_path));
-    }
-
     let output = command_with_no_window(&ffprobe_path)
         .args([
             "-v", "quiet",
@@ -305,26 +265,11 @@ pub struct Codec {
 }
 
 #[tauri::command]
-pub fn detect_all_codecs(app_handle: tauri::AppHandle) -> Result<Vec<Codec>, String> {
-    // In development mode, use the bin directory in src-tauri
-    // In production, use the resource directory
-    let ffmpeg_path = if cfg!(debug_assertions) {
-        // Development mode: use bin directory relative to src-tauri
-        let current_exe = std::env::current_exe().unwrap();
-        let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-        src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-    } else {
-        // Production mode: use resource directory
-        let resource_dir = app_handle.path().resource_dir().unwrap();
-        resource_dir.join("bin").join(get_ffmpeg_binary())
-    };
+pub fn detect_all_codecs() -> Result<Vec<Codec>, String> {
+    let ffmpeg_path = resolve_ffmpeg()?;
     
     println!("FFmpeg path for codec detection: {:?}", ffmpeg_path);
     
-    if !ffmpeg_path.exists() {
-        return Err(format!("FFmpeg binary not found at: {:?}", ffmpeg_path));
-    }
-    
     let mut all_codecs = Vec::new();
     
     // 获取所有编码器
@@ -561,6 +506,8 @@ fn parse_ffprobe_json(json_str: &str) -> Result<VideoMetadata, String> {
         duration,
         fps,
         color_depth,
+        streams: parse_stream_infos(&json),
+        color: parse_color_info(&json),
     })
 }
 
@@ -745,21 +692,24 @@ fn map_codec(name: &str) -> &'static str {
 fn detect_hardware_support_internal(app_handle: &tauri::AppHandle) -> HardwareSupport {
   let platform = current_platform();
 
-  // 与 detect_all_codecs 相同的 ffmpeg 路径解析：优先打包/本地 bin，失败回退 PATH
-  let mut ffmpeg_path = if cfg!(debug_assertions) {
-    // dev：相对 src-tauri/bin
-    let current_exe = std::env::current_exe().unwrap();
-    let src_tauri_dir = current_exe.parent().unwrap().parent().unwrap().parent().unwrap();
-    src_tauri_dir.join("bin").join(get_ffmpeg_binary())
-  } else {
-    // prod：资源目录 bin
-    let resource_dir = app_handle.path().resource_dir().unwrap();
-    resource_dir.join("bin").join(get_ffmpeg_binary())
+  // 与 detect_all_codecs 相同的 ffmpeg 路径解析（统一解析器，含 PATH 回退）
+  let ffmpeg_path = match resolve_ffmpeg() {
+    Ok(p) => p,
+    Err(e) => {
+      let encoders = encoders_to_test()
+        .into_iter()
+        .map(|name| EncoderSupport {
+          name: name.to_string(),
+          codec: map_codec(name).to_string(),
+          vendor: map_vendor(name).to_string(),
+          supported: false,
+          error_message: Some(e.clone()),
+        })
+        .collect();
+      let tested_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+      return HardwareSupport { platform, tested_at, encoders };
+    }
   };
-  if !ffmpeg_path.exists() {
-    if cfg!(debug_assertions) { println!("[HW Detect] Packaged ffmpeg not found at {:?}, fallback to 'ffmpeg' in PATH", ffmpeg_path); }
-    ffmpeg_path = PathBuf::from("ffmpeg");
-  }
   if cfg!(debug_assertions) {
     println!("[HW Detect] Using ffmpeg at: {:?}", ffmpeg_path);
     // 打印一次可用硬件编码器列表，便于观察